@@ -1,6 +1,14 @@
-use generator_core::{MapStyle, create_map};
+use generator_core::{
+    BuildPipeline, DrawOptions, LayoutCanvas, MapBuilderConfig, MapStyle,
+    generate_dual_castle_with_report, generate_floor_plan_with_report,
+    generate_inverted_castle_with_report, generate_layout_with_report, render, render_dual_castle,
+    render_floor_plan, render_inverted_castle,
+};
 
-use std::{fs::create_dir as create_generated_dir, path::Path};
+use std::{
+    fs::create_dir as create_generated_dir,
+    path::{Path, PathBuf},
+};
 
 use clap::Parser;
 use svg::save as save_as_svg;
@@ -20,9 +28,29 @@ struct Args {
     #[clap(short, long, default_value_t, value_enum)]
     style: MapStyle,
 
+    /// Seed used to generate the map, a random one is used if not provided
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// TOML or JSON file with generation parameters overriding the style preset
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     #[clap(short, long, default_value_t = false)]
     /// If true, the map will not be saved to a file
     dry_run: bool,
+
+    /// Draw the secret rooms and the secret doors leading into them
+    #[arg(long, default_value_t = false)]
+    reveal_secrets: bool,
+
+    /// Draw the name of every region over its rooms
+    #[arg(long, default_value_t = false)]
+    region_labels: bool,
+
+    /// Fill the rooms with the colours of their region
+    #[arg(long, default_value_t = false)]
+    tint_regions: bool,
 }
 
 fn main() {
@@ -32,7 +60,140 @@ fn main() {
         .with_max_level(tracing::Level::DEBUG)
         .init();
 
-    let map_data = create_map(args.columns, args.rows, args.style);
+    let seed = args.seed.unwrap_or_else(rand::random);
+
+    event!(tracing::Level::INFO, "Generating map with seed: {}", seed);
+
+    let config = match &args.config {
+        Some(path) => match MapBuilderConfig::from_style_with_overrides(args.style, path) {
+            Ok(config) => config,
+            Err(e) => {
+                event!(tracing::Level::ERROR, "Invalid config file: {}", e);
+                return;
+            }
+        },
+        None => MapBuilderConfig::from_style(args.style),
+    };
+
+    let mut draw_options = DrawOptions::default();
+    draw_options.reveal_secrets = args.reveal_secrets;
+    draw_options.region_labels = args.region_labels;
+    draw_options.tint_regions = args.tint_regions;
+
+    // Maps with many floors are saved as one SVG per floor, and inverted or dual
+    // castles as one SVG per castle
+    let generated = if config.floors > 1 {
+        generate_floor_plan_with_report(
+            args.columns,
+            args.rows,
+            args.style,
+            &config,
+            &BuildPipeline::default(),
+            seed,
+        )
+        .map(|(floor_plan, report)| {
+            let documents = render_floor_plan(&floor_plan, args.style, &draw_options)
+                .into_iter()
+                .enumerate()
+                .map(|(floor, document)| (format!("floor-{}", floor), document))
+                .collect::<Vec<_>>();
+
+            (documents, report)
+        })
+    } else if config.inverted_castle {
+        generate_inverted_castle_with_report(
+            args.columns,
+            args.rows,
+            args.style,
+            &config,
+            &BuildPipeline::default(),
+            seed,
+        )
+        .map(|(inverted_castle, report)| {
+            let [castle, inverted] =
+                render_inverted_castle(&inverted_castle, args.style, &draw_options);
+
+            (
+                vec![
+                    ("castle".to_string(), castle),
+                    ("inverted-castle".to_string(), inverted),
+                ],
+                report,
+            )
+        })
+    } else if config.dual_castle {
+        generate_dual_castle_with_report(
+            args.columns,
+            args.rows,
+            args.style,
+            &config,
+            &BuildPipeline::default(),
+            seed,
+        )
+        .map(|(dual_castle, report)| {
+            let [castle_a, castle_b] = render_dual_castle(&dual_castle, args.style, &draw_options);
+
+            (
+                vec![
+                    ("castle-a".to_string(), castle_a),
+                    ("castle-b".to_string(), castle_b),
+                ],
+                report,
+            )
+        })
+    } else {
+        generate_layout_with_report(
+            &LayoutCanvas::new(args.columns, args.rows),
+            args.style,
+            &config,
+            &BuildPipeline::default(),
+            seed,
+        )
+        .map(|(layout, report)| {
+            (
+                vec![(
+                    "map".to_string(),
+                    render(&layout, args.style, &draw_options),
+                )],
+                report,
+            )
+        })
+    };
+
+    let (documents, report) = match generated {
+        Ok(result) => result,
+        Err(e) => {
+            event!(tracing::Level::ERROR, "Failed to generate map: {}", e);
+            return;
+        }
+    };
+
+    event!(
+        tracing::Level::INFO,
+        "Generated {} rooms and {} doors ({} locked, {} needing an ability) over {} regions in {:.2}ms",
+        report.room_count,
+        report.doors_created,
+        report.locked_doors,
+        report.ability_doors,
+        report.region_count,
+        report.total_duration.as_secs_f64() * 1000.0
+    );
+    event!(
+        tracing::Level::INFO,
+        "Rooms merged: {}, bisected: {}, removed as orphans: {}, removed in small groups: {}",
+        report.rooms_merged,
+        report.rooms_bisected,
+        report.rooms_removed_as_orphans,
+        report.rooms_removed_in_small_groups
+    );
+    for pass in report.pass_durations.iter() {
+        event!(
+            tracing::Level::DEBUG,
+            "Pass {} took {:.2}ms",
+            pass.name,
+            pass.duration.as_secs_f64() * 1000.0
+        );
+    }
 
     if args.dry_run {
         event!(
@@ -42,13 +203,12 @@ fn main() {
         return;
     }
 
-    let map_filename = {
+    let now = {
         use std::time::SystemTime;
 
-        let now = SystemTime::now()
+        SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-        format!("generated/{:?}-map.svg", now)
+            .unwrap()
     };
 
     match Path::new("generated").try_exists() {
@@ -67,11 +227,15 @@ fn main() {
         _ => {}
     }
 
-    event!(
-        tracing::Level::INFO,
-        "Saving map as SVG to: {}",
-        map_filename
-    );
+    for (name, map_data) in documents {
+        let map_filename = format!("generated/{:?}-{}.svg", now, name);
 
-    save_as_svg(map_filename, &map_data).expect("Failed to save SVG file!");
+        event!(
+            tracing::Level::INFO,
+            "Saving map as SVG to: {}",
+            map_filename
+        );
+
+        save_as_svg(map_filename, &map_data).expect("Failed to save SVG file!");
+    }
 }
@@ -0,0 +1,495 @@
+use super::{
+    progression::solve_progression,
+    room_graph::{RoomGraph, RoomLink, iter_room_refs, region_connections, start_room},
+};
+use crate::types::{Cell, Door, DoorModifier, DualCastle, MapLayout, RoomModifier, RoomRef};
+
+use std::collections::{BTreeSet, HashMap, hash_map::Entry};
+
+use thiserror::Error;
+
+/// A broken invariant found by [`validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum Violation {
+    #[error("room {room} has no cells")]
+    EmptyRoom { room: RoomRef },
+    #[error("cell {cell} belongs to rooms {first} and {second}")]
+    SharedCell {
+        cell: Cell,
+        first: RoomRef,
+        second: RoomRef,
+    },
+    #[error("the cells of room {room} are not 4-connected")]
+    DisconnectedRoom { room: RoomRef },
+    #[error("door between {} and {} joins cells that are not adjacent", door.from, door.to)]
+    DoorNotAdjacent { door: Door },
+    #[error("door between {} and {} has a cell outside of any room", door.from, door.to)]
+    DoorOutsideRooms { door: Door },
+    #[error("door between {} and {} is inside room {room}", door.from, door.to)]
+    DoorInsideRoom { door: Door, room: RoomRef },
+    #[error("room {room} cannot be reached from room {from}")]
+    UnreachableRoom { room: RoomRef, from: RoomRef },
+    #[error("room {room} has no way back to room {to}")]
+    NoWayBack { room: RoomRef, to: RoomRef },
+    #[error("pinned room {room} is not connected to any other room")]
+    DisconnectedPinnedRoom { room: RoomRef },
+    #[error("room {room} has the modifier {modifier:?}, which is not valid for it")]
+    InvalidModifier {
+        room: RoomRef,
+        modifier: RoomModifier,
+    },
+    #[error("the first castle can be completed without any pickup from the second castle")]
+    SelfContainedCastle,
+}
+
+/// Checks the invariants every generated layout should hold, returning
+/// all the violations found.
+///
+/// - No cell belongs to more than one room.
+/// - The cells of every room are 4-connected.
+/// - Every door joins two adjacent cells of different rooms.
+/// - Every room can be reached from any other room through doors, or
+///   through the [`RoomModifier::RegionConnection`] links between regions,
+///   even when some of the doors are one-way.
+/// - Save, navigation and warp rooms are single cell rooms, and region connections
+///   point to a neighbouring region with a matching connection.
+/// - Secret rooms are only entered through a single [`DoorModifier::Secret`] door.
+/// - [Pinned](crate::Room::pinned) rooms are connected to at least one other room.
+pub fn validate(layout: &MapLayout) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    let mut cell_owners = HashMap::<Cell, RoomRef>::new();
+
+    for (region_idx, region) in layout.regions.iter().enumerate() {
+        for (room_idx, room) in region.rooms.iter().enumerate() {
+            let room_ref = RoomRef {
+                region: region_idx,
+                room: room_idx,
+            };
+
+            if room.cells.is_empty() {
+                violations.push(Violation::EmptyRoom { room: room_ref });
+                continue;
+            }
+
+            for cell in room.cells.iter() {
+                match cell_owners.entry(*cell) {
+                    Entry::Occupied(owner) if *owner.get() != room_ref => {
+                        violations.push(Violation::SharedCell {
+                            cell: *cell,
+                            first: *owner.get(),
+                            second: room_ref,
+                        });
+                    }
+                    Entry::Occupied(_) => {}
+                    Entry::Vacant(owner) => {
+                        owner.insert(room_ref);
+                    }
+                }
+            }
+
+            if !is_four_connected(&room.cells) {
+                violations.push(Violation::DisconnectedRoom { room: room_ref });
+            }
+        }
+    }
+
+    for region in layout.regions.iter() {
+        for door in region.doors.iter() {
+            if door.from.is_neighbour_of(&door.to).is_none() {
+                violations.push(Violation::DoorNotAdjacent { door: *door });
+                continue;
+            }
+
+            match (cell_owners.get(&door.from), cell_owners.get(&door.to)) {
+                (Some(from), Some(to)) if from == to => {
+                    violations.push(Violation::DoorInsideRoom {
+                        door: *door,
+                        room: *from,
+                    });
+                }
+                (Some(_), Some(_)) => {}
+                _ => violations.push(Violation::DoorOutsideRooms { door: *door }),
+            }
+        }
+    }
+
+    for room_ref in iter_room_refs(&layout.regions) {
+        let room = &layout.regions[room_ref.region].rooms[room_ref.room];
+
+        if let Some(
+            modifier @ (RoomModifier::Save | RoomModifier::Navigation | RoomModifier::Warp),
+        ) = room.modifier
+        {
+            if room.cells.len() != 1 {
+                violations.push(Violation::InvalidModifier {
+                    room: room_ref,
+                    modifier,
+                });
+            }
+        }
+    }
+
+    let mut secret_doors = HashMap::<RoomRef, Vec<&Door>>::new();
+
+    for door in layout.regions.iter().flat_map(|region| region.doors.iter()) {
+        for cell in [door.from, door.to] {
+            if let Some(owner) = cell_owners.get(&cell) {
+                if layout.regions[owner.region].rooms[owner.room].modifier
+                    == Some(RoomModifier::Secret)
+                {
+                    secret_doors.entry(*owner).or_default().push(door);
+                }
+            }
+        }
+    }
+
+    for room_ref in iter_room_refs(&layout.regions) {
+        if layout.regions[room_ref.region].rooms[room_ref.room].modifier
+            != Some(RoomModifier::Secret)
+        {
+            continue;
+        }
+
+        let is_dead_end = match secret_doors.get(&room_ref).map(Vec::as_slice) {
+            Some([door]) => door.modifier == DoorModifier::Secret,
+            _ => false,
+        };
+
+        if !is_dead_end {
+            violations.push(Violation::InvalidModifier {
+                room: room_ref,
+                modifier: RoomModifier::Secret,
+            });
+        }
+    }
+
+    let (_, unmatched_rooms) = region_connections(&layout.regions);
+
+    violations.extend(unmatched_rooms.into_iter().map(|room| {
+        Violation::InvalidModifier {
+            room,
+            modifier: layout.regions[room.region].rooms[room.room]
+                .modifier
+                .unwrap(),
+        }
+    }));
+
+    let room_graph = RoomGraph::new(&layout.regions);
+
+    // A map made of a single room has nothing to connect its pinned room to
+    if room_graph.iter_rooms().nth(1).is_some() {
+        violations.extend(
+            room_graph
+                .iter_rooms()
+                .filter(|room| layout.regions[room.region].rooms[room.room].pinned)
+                .filter(|room| {
+                    room_graph
+                        .iter_all_links(*room)
+                        .all(|(_, link)| link == RoomLink::Warp)
+                })
+                .map(|room| Violation::DisconnectedPinnedRoom { room }),
+        );
+    }
+
+    if let Some(start) = start_room(&layout.regions) {
+        let distances = room_graph.distances_from(start, |link| link != RoomLink::Warp);
+
+        violations.extend(
+            room_graph
+                .iter_rooms()
+                .filter(|room| !distances.contains_key(room))
+                .map(|room| Violation::UnreachableRoom { room, from: start }),
+        );
+
+        let return_distances = room_graph.distances_to(start, |link| link != RoomLink::Warp);
+
+        violations.extend(
+            room_graph
+                .iter_rooms()
+                .filter(|room| distances.contains_key(room))
+                .filter(|room| !return_distances.contains_key(room))
+                .map(|room| Violation::NoWayBack { room, to: start }),
+        );
+    }
+
+    violations
+}
+
+/// Checks both castles of a dual castle with [`validate`], along with the
+/// invariants tying them together, returning all the violations found.
+///
+/// - The first castle can not be completed on its own, some of its gates need
+///   a pickup from the second castle.
+pub fn validate_dual_castle(dual_castle: &DualCastle) -> Vec<Violation> {
+    let mut violations = validate(&dual_castle.castle_a);
+    violations.extend(validate(&dual_castle.castle_b));
+
+    if solve_progression(&dual_castle.castle_a).is_some_and(|solution| solution.is_completable()) {
+        violations.push(Violation::SelfContainedCastle);
+    }
+
+    violations
+}
+
+fn is_four_connected(cells: &[Cell]) -> bool {
+    let cell_set = cells.iter().copied().collect::<BTreeSet<_>>();
+
+    let mut visited = BTreeSet::new();
+    let mut cells_to_visit = vec![cells[0]];
+
+    while let Some(cell) = cells_to_visit.pop() {
+        if !visited.insert(cell) {
+            continue;
+        }
+
+        cells_to_visit.extend(
+            cell_set
+                .iter()
+                .filter(|other| !visited.contains(*other) && cell.is_neighbour_of(other).is_some()),
+        );
+    }
+
+    visited.len() == cell_set.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{Direction, Map, Rect, Room};
+
+    fn single_region_layout(rooms: Vec<Room>, doors: Vec<Door>) -> MapLayout {
+        MapLayout {
+            columns: 4,
+            rows: 4,
+            seed: 0,
+            regions: vec![Map {
+                origin_rect: Rect::new(0, 0, 4, 4),
+                rooms,
+                doors,
+                themes: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_validate_accepts_connected_layout() {
+        let mut save_room = Room::new_from_rect(Rect::new(2, 0, 1, 1));
+        save_room.modifier = Some(RoomModifier::Save);
+
+        let layout = single_region_layout(
+            vec![Room::new_from_rect(Rect::new(0, 0, 2, 1)), save_room],
+            vec![Door::new(Cell::new(1, 0), Cell::new(2, 0))],
+        );
+
+        assert_eq!(validate(&layout), vec![]);
+    }
+
+    #[test]
+    fn test_validate_reports_broken_rooms_and_doors() {
+        let mut save_room = Room::new_from_rect(Rect::new(0, 0, 2, 1));
+        save_room.modifier = Some(RoomModifier::Save);
+
+        let split_room = Room {
+            cells: vec![Cell::new(0, 2), Cell::new(2, 2)],
+            modifier: None,
+            region: 0,
+            door_anchors: Vec::new(),
+            pinned: false,
+        };
+        let overlapping_room = Room::new_from_rect(Rect::new(1, 0, 1, 2));
+
+        let layout = single_region_layout(
+            vec![save_room, split_room, overlapping_room],
+            vec![
+                Door::new(Cell::new(0, 0), Cell::new(1, 0)),
+                Door::new(Cell::new(0, 0), Cell::new(3, 3)),
+                Door::new(Cell::new(1, 1), Cell::new(1, 2)),
+            ],
+        );
+
+        let violations = validate(&layout);
+
+        let room = |room| RoomRef { region: 0, room };
+        assert!(violations.contains(&Violation::SharedCell {
+            cell: Cell::new(1, 0),
+            first: room(0),
+            second: room(2),
+        }));
+        assert!(violations.contains(&Violation::DisconnectedRoom { room: room(1) }));
+        assert!(violations.contains(&Violation::DoorInsideRoom {
+            door: Door::new(Cell::new(0, 0), Cell::new(1, 0)),
+            room: room(0),
+        }));
+        assert!(violations.contains(&Violation::DoorNotAdjacent {
+            door: Door::new(Cell::new(0, 0), Cell::new(3, 3)),
+        }));
+        assert!(violations.contains(&Violation::DoorOutsideRooms {
+            door: Door::new(Cell::new(1, 1), Cell::new(1, 2)),
+        }));
+        assert!(violations.contains(&Violation::InvalidModifier {
+            room: room(0),
+            modifier: RoomModifier::Save,
+        }));
+        assert!(violations.contains(&Violation::UnreachableRoom {
+            room: room(1),
+            from: room(0),
+        }));
+    }
+
+    #[test]
+    fn test_validate_reports_one_way_dead_ends() {
+        let mut one_way_door = Door::new(Cell::new(1, 0), Cell::new(2, 0));
+        one_way_door.one_way = true;
+
+        let mut layout = single_region_layout(
+            (0..3)
+                .map(|col| Room::new_from_rect(Rect::new(col, 0, 1, 1)))
+                .collect(),
+            vec![Door::new(Cell::new(0, 0), Cell::new(1, 0)), one_way_door],
+        );
+
+        assert_eq!(
+            validate(&layout),
+            vec![Violation::NoWayBack {
+                room: RoomRef { region: 0, room: 2 },
+                to: RoomRef { region: 0, room: 0 },
+            }]
+        );
+
+        // A second way back out of the last room fixes it
+        layout.regions[0].rooms[1].cells.push(Cell::new(1, 1));
+        layout.regions[0].rooms[2].cells.push(Cell::new(2, 1));
+        layout.regions[0]
+            .doors
+            .push(Door::new(Cell::new(2, 1), Cell::new(1, 1)));
+
+        assert_eq!(validate(&layout), vec![]);
+    }
+
+    #[test]
+    fn test_validate_reports_disconnected_pinned_rooms() {
+        let mut pinned_room = Room::new_from_rect(Rect::new(0, 2, 2, 1));
+        pinned_room.pinned = true;
+
+        let mut layout = single_region_layout(
+            vec![Room::new_from_rect(Rect::new(0, 0, 2, 1)), pinned_room],
+            vec![],
+        );
+
+        let pinned_ref = RoomRef { region: 0, room: 1 };
+
+        assert_eq!(
+            validate(&layout),
+            vec![
+                Violation::DisconnectedPinnedRoom { room: pinned_ref },
+                Violation::UnreachableRoom {
+                    room: pinned_ref,
+                    from: RoomRef { region: 0, room: 0 },
+                },
+            ]
+        );
+
+        layout.regions[0].rooms[0].cells.push(Cell::new(0, 1));
+        layout.regions[0]
+            .doors
+            .push(Door::new(Cell::new(0, 1), Cell::new(0, 2)));
+
+        assert_eq!(validate(&layout), vec![]);
+    }
+
+    #[test]
+    fn test_validate_reports_secret_rooms_with_many_doors() {
+        let mut secret_room = Room::new_from_rect(Rect::new(1, 0, 1, 1));
+        secret_room.modifier = Some(RoomModifier::Secret);
+
+        let mut secret_door = Door::new(Cell::new(0, 0), Cell::new(1, 0));
+        secret_door.modifier = DoorModifier::Secret;
+
+        let mut layout = single_region_layout(
+            vec![Room::new_from_rect(Rect::new(0, 0, 1, 2)), secret_room],
+            vec![secret_door],
+        );
+
+        assert_eq!(validate(&layout), vec![]);
+
+        layout.regions[0].rooms[0].cells.push(Cell::new(1, 1));
+        layout.regions[0]
+            .doors
+            .push(Door::new(Cell::new(1, 1), Cell::new(1, 0)));
+
+        assert_eq!(
+            validate(&layout),
+            vec![Violation::InvalidModifier {
+                room: RoomRef { region: 0, room: 1 },
+                modifier: RoomModifier::Secret,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_follows_region_connections() {
+        let mut west_room = Room::new_from_rect(Rect::new(1, 0, 1, 1));
+        west_room.modifier = Some(RoomModifier::RegionConnection(Direction::East));
+        let mut east_room = Room::new_from_rect(Rect::new(2, 0, 1, 1));
+        east_room.modifier = Some(RoomModifier::RegionConnection(Direction::West));
+
+        let mut layout = MapLayout {
+            columns: 4,
+            rows: 1,
+            seed: 0,
+            regions: vec![
+                Map {
+                    origin_rect: Rect::new(0, 0, 2, 1),
+                    rooms: vec![west_room],
+                    doors: vec![],
+                    themes: Vec::new(),
+                },
+                Map {
+                    origin_rect: Rect::new(2, 0, 2, 1),
+                    rooms: vec![east_room],
+                    doors: vec![],
+                    themes: Vec::new(),
+                },
+            ],
+        };
+
+        assert_eq!(validate(&layout), vec![]);
+
+        layout.regions[1].rooms[0].modifier = None;
+
+        assert_eq!(
+            validate(&layout),
+            vec![
+                Violation::InvalidModifier {
+                    room: RoomRef { region: 0, room: 0 },
+                    modifier: RoomModifier::RegionConnection(Direction::East),
+                },
+                Violation::UnreachableRoom {
+                    room: RoomRef { region: 1, room: 0 },
+                    from: RoomRef { region: 0, room: 0 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_dual_castle_needs_the_second_castle() {
+        let mut start_room = Room::new_from_rect(Rect::new(0, 0, 1, 1));
+        start_room.modifier = Some(RoomModifier::Start);
+
+        let dual_castle = DualCastle {
+            castle_a: single_region_layout(vec![start_room], vec![]),
+            castle_b: single_region_layout(
+                vec![Room::new_from_rect(Rect::new(0, 0, 1, 1))],
+                vec![],
+            ),
+        };
+
+        assert_eq!(
+            validate_dual_castle(&dual_castle),
+            vec![Violation::SelfContainedCastle]
+        );
+    }
+}
@@ -1,19 +1,27 @@
-use super::{MapBuilder, MapBuilderConfig};
+use super::{LoopTargetScope, MapBuilder, MapBuilderConfig, room_decorator::DoorGraph};
 use crate::{
     algos::RngHandler,
-    types::{Door, DoorModifier, MapRegion, RoomId},
+    types::{Door, DoorModifier, MapRegion, Room, RoomId},
 };
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap, HashSet},
+};
 
-use rand::Rng;
+use rand::{Rng, seq::SliceRandom};
 
 impl MapBuilder {
     pub(super) fn generate_doors_for(
         map_region: &MapRegion,
         config: &MapBuilderConfig,
+        rng_handler: RngHandler,
     ) -> Vec<Door> {
         let room_count = map_region.iter_active().count();
+        if room_count == 0 {
+            return Vec::new();
+        }
+
         let mut doors = Vec::with_capacity(room_count * 2);
 
         let mut visited_rooms = HashSet::new();
@@ -23,7 +31,13 @@ impl MapBuilder {
             connected_count.insert(room_id, 0);
         }
 
-        let mut rng = RngHandler::rng();
+        // With a target number of loops, they are all added afterwards
+        let loop_chance = match config.target_loops {
+            Some(_) => 0.0,
+            None => config.door_loop_connection_chance,
+        };
+
+        let mut rng = rng_handler.rng();
 
         let initial_room = {
             let idx = rng.random_range(0..room_count);
@@ -31,7 +45,7 @@ impl MapBuilder {
                 .iter_active()
                 .nth(idx)
                 .map(|(id, _)| id)
-                .expect("There should be at least one active room")
+                .expect("Room count should match the active rooms")
         };
 
         let mut room_queue = Vec::new();
@@ -47,39 +61,13 @@ impl MapBuilder {
                     continue;
                 }
 
-                if connected_count[&neighbour_id] >= 1
-                    && !rng.random_bool(config.door_loop_connection_chance)
-                {
+                if connected_count[&neighbour_id] >= 1 && !rng.random_bool(loop_chance) {
                     continue;
                 }
 
                 let neighbour_room = map_region.get_active(neighbour_id);
 
-                if let Some(neighbouring_cells) = room.get_neighbouring_cells_for(neighbour_room) {
-                    let priority_neighbouring_cells = neighbouring_cells
-                        .iter()
-                        .copied()
-                        .filter(|(_, _, direction)| direction.is_horizontal())
-                        .collect::<Vec<_>>();
-
-                    let neighbouring_cells_selection = if priority_neighbouring_cells.is_empty() {
-                        neighbouring_cells
-                    } else {
-                        priority_neighbouring_cells
-                    };
-
-                    let selected_cell = rng.random_range(0..neighbouring_cells_selection.len());
-                    let (from, to, _) = neighbouring_cells_selection[selected_cell];
-
-                    let mut door = Door::new(from, to);
-
-                    match rng.random_range(0..100_u32) {
-                        0 => door.modifier = DoorModifier::Locked,
-                        1..6 => door.modifier = DoorModifier::Secret,
-                        6..10 => door.modifier = DoorModifier::None,
-                        _ => door.modifier = DoorModifier::Open,
-                    }
-
+                if let Some(door) = door_between(room, neighbour_room, &mut rng) {
                     doors.push(door);
 
                     *connected_count.get_mut(&room_id).unwrap() += 1;
@@ -92,4 +80,650 @@ impl MapBuilder {
 
         doors
     }
+
+    // Adds doors between neighbouring active rooms until the region holds
+    // `target_loops` loops, returning how many doors were added.
+    //
+    // With `LoopTargetScope::PerRegion`, every region the rooms were generated
+    // in must hold the target on its own, counting only the doors between its
+    // own rooms, so merged maps get the target in each of their regions.
+    pub(super) fn add_target_loops(
+        map_region: &MapRegion,
+        doors: &mut Vec<Door>,
+        target_loops: Option<u32>,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        let Some(target_loops) = target_loops else {
+            return 0;
+        };
+
+        let mut rng = rng_handler.rng();
+
+        match config.loop_target_scope {
+            LoopTargetScope::WholeMap => add_loops_between(
+                map_region,
+                doors,
+                target_loops as usize,
+                |_| true,
+                config.loop_length_bias,
+                &mut rng,
+            ),
+            LoopTargetScope::PerRegion => map_region
+                .iter_active()
+                .map(|(_, room)| room.region)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|region| {
+                    add_loops_between(
+                        map_region,
+                        doors,
+                        target_loops as usize,
+                        |room| room.region == region,
+                        config.loop_length_bias,
+                        &mut rng,
+                    )
+                })
+                .sum(),
+        }
+    }
+
+    // Adds doors between neighbouring active rooms until no more than
+    // `config.max_dead_end_ratio` of them are dead ends, returning how many
+    // doors were added.
+    //
+    // Every pair of a dead end and a neighbour is ranked by the distance between
+    // them through the existing doors, and the dead ends get their door towards
+    // the furthest neighbours first, closing the longest loops. The distances
+    // that can go through every new door are made shorter before picking the
+    // next one.
+    pub(super) fn limit_dead_ends(
+        map_region: &MapRegion,
+        doors: &mut Vec<Door>,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        if config.max_dead_end_ratio >= 1.0 {
+            return 0;
+        }
+
+        let room_count = map_region.iter_active().count();
+        let max_dead_ends = (room_count as f64 * config.max_dead_end_ratio).floor() as usize;
+
+        let mut door_graph = DoorGraph::new(map_region, doors);
+
+        let mut dead_ends = door_graph.dead_ends();
+        if dead_ends.len() <= max_dead_ends {
+            return 0;
+        }
+
+        let mut rng = rng_handler.rng();
+
+        // Shuffled before the stable sort, so the ties between equally long
+        // loops are broken at random
+        dead_ends.shuffle(&mut rng);
+
+        let mut candidates = dead_ends
+            .iter()
+            .flat_map(|dead_end| {
+                let distances = door_graph.distances_from(*dead_end);
+
+                map_region
+                    .iter_active_neighbours(*dead_end)
+                    .filter(|neighbour_id| !door_graph.is_linked(*dead_end, *neighbour_id))
+                    .map(move |neighbour_id| {
+                        // Rooms out of reach join two separate groups, which is
+                        // as long as a loop can get
+                        let distance = distances.get(&neighbour_id).copied().unwrap_or(usize::MAX);
+
+                        (*dead_end, neighbour_id, distance)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        candidates.sort_by_key(|(_, _, distance)| Reverse(*distance));
+
+        let mut door_counts = map_region
+            .iter_active()
+            .map(|(room_id, _)| (room_id, door_graph.door_count(room_id)))
+            .collect::<HashMap<_, _>>();
+        let mut dead_end_count = dead_ends.len();
+
+        let mut added_doors = 0;
+
+        while dead_end_count > max_dead_ends && !candidates.is_empty() {
+            let (dead_end, neighbour_id, _) = candidates.remove(0);
+
+            // The dead end may already have got a door from an earlier candidate
+            if door_counts[&dead_end] != 1 {
+                continue;
+            }
+
+            let Some(door) = door_between(
+                map_region.get_active(dead_end),
+                map_region.get_active(neighbour_id),
+                &mut rng,
+            ) else {
+                continue;
+            };
+
+            doors.push(door);
+            added_doors += 1;
+
+            for room_id in [dead_end, neighbour_id] {
+                let door_count = door_counts.get_mut(&room_id).unwrap();
+                *door_count += 1;
+
+                match *door_count {
+                    1 => dead_end_count += 1,
+                    2 => dead_end_count -= 1,
+                    _ => {}
+                }
+            }
+
+            door_graph = DoorGraph::new(map_region, doors);
+            let dead_end_distances = door_graph.distances_from(dead_end);
+            let neighbour_distances = door_graph.distances_from(neighbour_id);
+
+            for (from, to, distance) in candidates.iter_mut() {
+                for (from_distances, to_distances) in [
+                    (&dead_end_distances, &neighbour_distances),
+                    (&neighbour_distances, &dead_end_distances),
+                ] {
+                    if let (Some(from_distance), Some(to_distance)) =
+                        (from_distances.get(from), to_distances.get(to))
+                    {
+                        *distance = (*distance).min(from_distance + to_distance + 1);
+                    }
+                }
+            }
+
+            candidates.sort_by_key(|(_, _, distance)| Reverse(*distance));
+        }
+
+        added_doors
+    }
+}
+
+// Adds doors between the neighbouring active rooms accepted by `keep` until
+// they hold `target_loops` loops among them, returning how many doors were added.
+//
+// Every door closes a loop as long as the doors already between both rooms,
+// plus one. The loops are sorted by length and `loop_length_bias` picks which
+// one is opened each time, from the shortest at 0.0 to the longest at 1.0. The
+// loops that can go through every new door are made shorter before picking the
+// next one.
+fn add_loops_between(
+    map_region: &MapRegion,
+    doors: &mut Vec<Door>,
+    target_loops: usize,
+    keep: impl Fn(&Room) -> bool + Copy,
+    loop_length_bias: f64,
+    rng: &mut impl Rng,
+) -> usize {
+    let mut door_graph = DoorGraph::new_for(map_region, doors, keep);
+
+    let missing_loops = target_loops.saturating_sub(door_graph.loop_count());
+    if missing_loops == 0 {
+        return 0;
+    }
+
+    let mut candidates = Vec::new();
+
+    for (room_id, _) in map_region.iter_active().filter(|(_, room)| keep(room)) {
+        let distances = door_graph.distances_from(room_id);
+
+        for neighbour_id in map_region.iter_active_neighbours(room_id) {
+            // Rooms out of reach would join two groups instead of closing a loop
+            if room_id < neighbour_id && !door_graph.is_linked(room_id, neighbour_id) {
+                if let Some(distance) = distances.get(&neighbour_id) {
+                    candidates.push((room_id, neighbour_id, distance + 1));
+                }
+            }
+        }
+    }
+
+    // Shuffled before the stable sort, so loops of the same length are picked at random
+    candidates.shuffle(rng);
+    candidates.sort_by_key(|(_, _, loop_length)| *loop_length);
+
+    let mut added_doors = 0;
+
+    while added_doors < missing_loops && !candidates.is_empty() {
+        let selected = ((candidates.len() - 1) as f64 * loop_length_bias).round();
+        let (room_id, neighbour_id, _) = candidates.remove(selected as usize);
+
+        let Some(door) = door_between(
+            map_region.get_active(room_id),
+            map_region.get_active(neighbour_id),
+            rng,
+        ) else {
+            continue;
+        };
+
+        doors.push(door);
+        added_doors += 1;
+
+        door_graph = DoorGraph::new_for(map_region, doors, keep);
+        let room_distances = door_graph.distances_from(room_id);
+        let neighbour_distances = door_graph.distances_from(neighbour_id);
+
+        for (from, to, loop_length) in candidates.iter_mut() {
+            for (from_distances, to_distances) in [
+                (&room_distances, &neighbour_distances),
+                (&neighbour_distances, &room_distances),
+            ] {
+                if let (Some(from_distance), Some(to_distance)) =
+                    (from_distances.get(from), to_distances.get(to))
+                {
+                    *loop_length = (*loop_length).min(from_distance + to_distance + 2);
+                }
+            }
+        }
+
+        candidates.sort_by_key(|(_, _, loop_length)| *loop_length);
+    }
+
+    added_doors
+}
+
+// Places a door somewhere along the wall shared by both rooms, preferring the
+// horizontal ones.
+fn door_between(room: &Room, neighbour_room: &Room, rng: &mut impl Rng) -> Option<Door> {
+    let neighbouring_cells = room.get_neighbouring_cells_for(neighbour_room)?;
+
+    // Rooms stamped from a template keep their doors at their anchors
+    let neighbouring_cells = neighbouring_cells
+        .into_iter()
+        .filter(|(from, to, _)| {
+            room.door_cells().contains(from) && neighbour_room.door_cells().contains(to)
+        })
+        .collect::<Vec<_>>();
+
+    if neighbouring_cells.is_empty() {
+        return None;
+    }
+
+    let priority_neighbouring_cells = neighbouring_cells
+        .iter()
+        .copied()
+        .filter(|(_, _, direction)| direction.is_horizontal())
+        .collect::<Vec<_>>();
+
+    let neighbouring_cells_selection = if priority_neighbouring_cells.is_empty() {
+        neighbouring_cells
+    } else {
+        priority_neighbouring_cells
+    };
+
+    let selected_cell = rng.random_range(0..neighbouring_cells_selection.len());
+    let (from, to, _) = neighbouring_cells_selection[selected_cell];
+
+    let mut door = Door::new(from, to);
+    door.area_transition = room.region != neighbour_room.region;
+
+    // Locked doors are placed later on, together with their keys,
+    // and secret doors only lead into secret rooms
+    match rng.random_range(0..100_u32) {
+        6..10 => door.modifier = DoorModifier::None,
+        _ => door.modifier = DoorModifier::Open,
+    }
+
+    Some(door)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        LayoutCanvas, MapStyle,
+        algos::{
+            BuildPipeline,
+            map_builder::{room_decorator::DISTANCE_SEARCHES, test_maps::grid_region},
+        },
+        constants::TEST_SEED,
+    };
+
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_add_target_loops_meets_the_target() {
+        let map_region = grid_region(6, 5);
+
+        for (target_loops, loop_length_bias) in [(0, 0.5), (3, 0.0), (8, 1.0)] {
+            let config = MapBuilderConfig::builder()
+                .door_loop_connection_chance(1.0)
+                .target_loops(Some(target_loops))
+                .loop_length_bias(loop_length_bias)
+                .build()
+                .unwrap();
+
+            let mut doors =
+                MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+
+            // The spanning tree of the doors holds no loops at all
+            assert_eq!(DoorGraph::new(&map_region, &doors).loop_count(), 0);
+
+            let added_doors = MapBuilder::add_target_loops(
+                &map_region,
+                &mut doors,
+                config.target_loops,
+                &config,
+                RngHandler::new(TEST_SEED),
+            );
+
+            assert_eq!(added_doors, target_loops as usize);
+            assert_eq!(
+                DoorGraph::new(&map_region, &doors).loop_count(),
+                target_loops as usize
+            );
+        }
+    }
+
+    #[test]
+    fn test_add_target_loops_meets_the_target_in_every_scope() {
+        // Two regions side by side, linked through the rooms along their border
+        let mut map_region = grid_region(6, 5);
+        let room_ids = map_region
+            .iter_active()
+            .map(|(room_id, _)| room_id)
+            .collect::<Vec<_>>();
+
+        for room_id in room_ids {
+            let room = map_region.get_mut_room(room_id);
+            room.region = (room.cells[0].col >= 3) as usize;
+        }
+
+        for loop_target_scope in [LoopTargetScope::PerRegion, LoopTargetScope::WholeMap] {
+            let config = MapBuilderConfig::builder()
+                .target_loops(Some(3))
+                .loop_target_scope(loop_target_scope)
+                .build()
+                .unwrap();
+
+            let mut doors =
+                MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+
+            MapBuilder::add_target_loops(
+                &map_region,
+                &mut doors,
+                config.target_loops,
+                &config,
+                RngHandler::new(TEST_SEED),
+            );
+
+            let region_loops = |region| {
+                DoorGraph::new_for(&map_region, &doors, |room| room.region == region).loop_count()
+            };
+
+            match loop_target_scope {
+                LoopTargetScope::PerRegion => {
+                    assert_eq!(region_loops(0), 3);
+                    assert_eq!(region_loops(1), 3);
+                }
+                LoopTargetScope::WholeMap => {
+                    assert_eq!(DoorGraph::new(&map_region, &doors).loop_count(), 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_meet_the_target_in_every_scope() {
+        for style in [MapStyle::CastlevaniaSOTN, MapStyle::MetroidZM] {
+            for loop_target_scope in [LoopTargetScope::PerRegion, LoopTargetScope::WholeMap] {
+                let config = MapBuilderConfig::from_style(style)
+                    .to_builder()
+                    .target_loops(Some(4))
+                    .loop_target_scope(loop_target_scope)
+                    .build()
+                    .unwrap();
+
+                let (layout, _) = crate::generate_layout_with_report(
+                    &LayoutCanvas::new(48, 32),
+                    style,
+                    &config,
+                    &BuildPipeline::default(),
+                    TEST_SEED,
+                )
+                .unwrap();
+
+                let mut region_loops = BTreeMap::<(usize, usize), usize>::new();
+                let mut map_loops = 0;
+
+                for (map_idx, map) in layout.regions.into_iter().enumerate() {
+                    let (map_region, doors) = MapRegion::from_map(map);
+                    map_loops += DoorGraph::new(&map_region, &doors).loop_count();
+
+                    for region in map_region.iter_active().map(|(_, room)| room.region) {
+                        region_loops.entry((map_idx, region)).or_insert_with(|| {
+                            DoorGraph::new_for(&map_region, &doors, |room| room.region == region)
+                                .loop_count()
+                        });
+                    }
+                }
+
+                // The regions that are not merged are linked without any loop
+                // between them, so the loops of their maps add up
+                match loop_target_scope {
+                    LoopTargetScope::PerRegion => {
+                        assert!(region_loops.values().all(|loops| *loops == 4), "{style:?}");
+                    }
+                    LoopTargetScope::WholeMap => assert_eq!(map_loops, 4, "{style:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_target_loops_count_the_dead_end_doors() {
+        let map_region = grid_region(6, 5);
+
+        let config = MapBuilderConfig::builder()
+            .target_loops(Some(14))
+            .max_dead_end_ratio(0.0)
+            .build()
+            .unwrap();
+
+        let mut doors =
+            MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+
+        let dead_end_doors = MapBuilder::limit_dead_ends(
+            &map_region,
+            &mut doors,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+        let loop_doors = MapBuilder::add_target_loops(
+            &map_region,
+            &mut doors,
+            config.target_loops,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+
+        let door_graph = DoorGraph::new(&map_region, &doors);
+
+        assert!(dead_end_doors > 0 && loop_doors > 0);
+        assert_eq!(dead_end_doors + loop_doors, 14);
+        assert_eq!(door_graph.loop_count(), 14);
+        assert!(door_graph.dead_ends().is_empty());
+    }
+
+    #[test]
+    fn test_limit_dead_ends_adds_loop_doors() {
+        let map_region = grid_region(6, 5);
+
+        let config = MapBuilderConfig::builder()
+            .door_loop_connection_chance(0.0)
+            .max_dead_end_ratio(0.0)
+            .build()
+            .unwrap();
+
+        let mut doors =
+            MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+        let tree_doors = doors.len();
+        let tree_dead_ends = DoorGraph::new(&map_region, &doors).dead_ends().len();
+
+        let added_doors = MapBuilder::limit_dead_ends(
+            &map_region,
+            &mut doors,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+
+        assert!(added_doors > 0);
+        assert_eq!(doors.len(), tree_doors + added_doors);
+
+        let door_graph = DoorGraph::new(&map_region, &doors);
+        assert!(door_graph.dead_ends().len() < tree_dead_ends);
+
+        // The dead ends left have no neighbour to open a new door into
+        for dead_end in door_graph.dead_ends() {
+            assert!(
+                map_region
+                    .iter_active_neighbours(dead_end)
+                    .all(|neighbour_id| door_graph.is_linked(dead_end, neighbour_id))
+            );
+        }
+    }
+
+    #[test]
+    fn test_limit_dead_ends_searches_the_candidates_once() {
+        let map_region = grid_region(24, 18);
+
+        let config = MapBuilderConfig::builder()
+            .door_loop_connection_chance(0.0)
+            .max_dead_end_ratio(0.0)
+            .build()
+            .unwrap();
+
+        let mut doors =
+            MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+        let dead_ends = DoorGraph::new(&map_region, &doors).dead_ends().len();
+
+        DISTANCE_SEARCHES.with(|searches| searches.set(0));
+
+        let added_doors = MapBuilder::limit_dead_ends(
+            &map_region,
+            &mut doors,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+
+        // One search per dead end to rank the candidates, then one from each
+        // room of every new door to shorten their distances
+        assert!(added_doors > 0);
+        assert_eq!(
+            DISTANCE_SEARCHES.with(|searches| searches.get()),
+            dead_ends + 2 * added_doors
+        );
+    }
+
+    #[test]
+    fn test_limit_dead_ends_opens_the_longest_loops_first() {
+        let map_region = grid_region(6, 5);
+
+        let config = MapBuilderConfig::builder()
+            .door_loop_connection_chance(0.0)
+            .max_dead_end_ratio(0.0)
+            .build()
+            .unwrap();
+
+        let mut doors =
+            MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+        let tree_doors = doors.len();
+
+        MapBuilder::limit_dead_ends(&map_region, &mut doors, &config, RngHandler::new(TEST_SEED));
+
+        let room_at = |cell| {
+            map_region
+                .iter_active()
+                .find(|(_, room)| room.cells.contains(&cell))
+                .map(|(room_id, _)| room_id)
+                .unwrap()
+        };
+
+        // Every door leads from a dead end to the furthest neighbour of any dead
+        // end left once the previous doors are in place
+        for added_door in tree_doors..doors.len() {
+            let door_graph = &DoorGraph::new(&map_region, &doors[..added_door]);
+            let distance =
+                |room_id, neighbour_id| door_graph.distances_from(room_id)[&neighbour_id];
+
+            let longest_distance = door_graph
+                .dead_ends()
+                .into_iter()
+                .flat_map(|dead_end| {
+                    map_region
+                        .iter_active_neighbours(dead_end)
+                        .filter(move |neighbour_id| !door_graph.is_linked(dead_end, *neighbour_id))
+                        .map(move |neighbour_id| distance(dead_end, neighbour_id))
+                })
+                .max()
+                .unwrap();
+
+            let door = doors[added_door];
+            assert_eq!(
+                distance(room_at(door.from), room_at(door.to)),
+                longest_distance
+            );
+        }
+    }
+
+    #[test]
+    fn test_add_target_loops_opens_the_longest_loops_first() {
+        let map_region = grid_region(6, 5);
+
+        let config = MapBuilderConfig::builder()
+            .target_loops(Some(6))
+            .loop_length_bias(1.0)
+            .build()
+            .unwrap();
+
+        let mut doors =
+            MapBuilder::generate_doors_for(&map_region, &config, RngHandler::new(TEST_SEED));
+        let tree_doors = doors.len();
+
+        MapBuilder::add_target_loops(
+            &map_region,
+            &mut doors,
+            config.target_loops,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+
+        let room_at = |cell| {
+            map_region
+                .iter_active()
+                .find(|(_, room)| room.cells.contains(&cell))
+                .map(|(room_id, _)| room_id)
+                .unwrap()
+        };
+
+        // Every door closes the longest loop left once the previous doors are in place
+        for added_door in tree_doors..doors.len() {
+            let door_graph = &DoorGraph::new(&map_region, &doors[..added_door]);
+            let loop_length =
+                |room_id, neighbour_id| door_graph.distances_from(room_id)[&neighbour_id] + 1;
+
+            let longest_loop = map_region
+                .iter_active()
+                .flat_map(|(room_id, _)| {
+                    map_region
+                        .iter_active_neighbours(room_id)
+                        .filter(move |neighbour_id| !door_graph.is_linked(room_id, *neighbour_id))
+                        .map(move |neighbour_id| loop_length(room_id, neighbour_id))
+                })
+                .max()
+                .unwrap();
+
+            let door = doors[added_door];
+            assert_eq!(
+                loop_length(room_at(door.from), room_at(door.to)),
+                longest_loop
+            );
+        }
+    }
 }
@@ -0,0 +1,208 @@
+use super::{MapBuilder, MapBuilderConfig};
+use crate::{
+    algos::{RngHandler, room_graph::RoomGraph},
+    types::{Map, RoomModifier, RoomRef},
+};
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+
+impl MapBuilder {
+    // Marks the room the map is entered from and the rooms holding its bosses,
+    // returning the number of boss rooms of the map.
+    //
+    // The start room sits at the edge of the map, and every boss as far as
+    // possible from the start and from the other bosses, preferring big rooms
+    // with a save room one door away. Start and boss rooms pinned by hand are
+    // kept, and only the missing ones are placed.
+    pub(super) fn add_landmarks(
+        maps: &mut [Map],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        let room_graph = RoomGraph::new(maps);
+
+        let pinned_start = Self::rooms_with(maps, &room_graph, RoomModifier::Start)
+            .first()
+            .copied();
+        let pinned_bosses = Self::rooms_with(maps, &room_graph, RoomModifier::Boss);
+
+        let free_rooms = room_graph
+            .iter_rooms()
+            .filter(|room| Self::is_free(maps, *room))
+            .collect::<Vec<_>>();
+
+        if free_rooms.is_empty() && pinned_start.is_none() {
+            return pinned_bosses.len();
+        }
+
+        let mut rng = rng_handler.rng();
+
+        let start = if let Some(start) = pinned_start {
+            start
+        } else {
+            // The rooms furthest away from any room are at the edge of the map
+            let probe = free_rooms[rng.random_range(0..free_rooms.len())];
+            let probe_distances = room_graph.distances_from(probe, |_| true);
+
+            let start_rooms = Self::furthest_rooms(maps, &probe_distances, 9, 10);
+            let start = start_rooms[rng.random_range(0..start_rooms.len())];
+
+            maps[start.region].rooms[start.room].modifier = Some(RoomModifier::Start);
+
+            start
+        };
+
+        let mut landmark_distances = std::iter::once(start)
+            .chain(pinned_bosses.iter().copied())
+            .map(|landmark| room_graph.distances_from(landmark, |_| true))
+            .collect::<Vec<_>>();
+        let mut boss_count = pinned_bosses.len();
+
+        for _ in pinned_bosses.len()..config.boss_rooms as usize {
+            // Every room is scored by its distance to the closest landmark
+            let scores = landmark_distances[0]
+                .keys()
+                .filter_map(|room| {
+                    landmark_distances
+                        .iter()
+                        .map(|distances| distances.get(room).copied())
+                        .min()
+                        .flatten()
+                        .map(|score| (*room, score))
+                })
+                .filter(|(_, score)| *score > 0)
+                .collect::<BTreeMap<_, _>>();
+
+            let boss_rooms = Self::furthest_rooms(maps, &scores, 3, 4);
+
+            let Some((boss, save_room)) = boss_rooms
+                .into_iter()
+                .map(|room| (room, Self::find_save_room(maps, &room_graph, room)))
+                .max_by_key(|(room, save_room)| {
+                    (
+                        save_room.is_some(),
+                        maps[room.region].rooms[room.room].cells.len(),
+                        scores[room],
+                    )
+                })
+            else {
+                break;
+            };
+
+            maps[boss.region].rooms[boss.room].modifier = Some(RoomModifier::Boss);
+
+            if let Some(save_room) = save_room {
+                maps[save_room.region].rooms[save_room.room].modifier = Some(RoomModifier::Save);
+            }
+
+            landmark_distances.push(room_graph.distances_from(boss, |_| true));
+            boss_count += 1;
+        }
+
+        boss_count
+    }
+
+    fn rooms_with(maps: &[Map], room_graph: &RoomGraph, modifier: RoomModifier) -> Vec<RoomRef> {
+        room_graph
+            .iter_rooms()
+            .filter(|room| maps[room.region].rooms[room.room].modifier == Some(modifier))
+            .collect()
+    }
+
+    fn is_free(maps: &[Map], room: RoomRef) -> bool {
+        maps[room.region].rooms[room.room].modifier.is_none()
+    }
+
+    // Returns the free rooms whose distance is at least `numerator / denominator`
+    // of the distance of the furthest free room.
+    fn furthest_rooms(
+        maps: &[Map],
+        distances: &BTreeMap<RoomRef, usize>,
+        numerator: usize,
+        denominator: usize,
+    ) -> Vec<RoomRef> {
+        let free_distances = distances
+            .iter()
+            .filter(|(room, _)| Self::is_free(maps, **room))
+            .collect::<Vec<_>>();
+
+        let Some(max_distance) = free_distances.iter().map(|(_, distance)| **distance).max() else {
+            return Vec::new();
+        };
+
+        free_distances
+            .into_iter()
+            .filter(|(_, distance)| **distance * denominator >= max_distance * numerator)
+            .map(|(room, _)| *room)
+            .collect()
+    }
+
+    // Looks for a save room one door away from the given room, either an existing
+    // one or a free single cell room that can become one.
+    fn find_save_room(maps: &[Map], room_graph: &RoomGraph, room: RoomRef) -> Option<RoomRef> {
+        let neighbours = room_graph
+            .iter_links(room)
+            .map(|(neighbour, _)| neighbour)
+            .collect::<Vec<_>>();
+
+        neighbours
+            .iter()
+            .find(|neighbour| {
+                maps[neighbour.region].rooms[neighbour.room].modifier == Some(RoomModifier::Save)
+            })
+            .or_else(|| {
+                neighbours.iter().find(|neighbour| {
+                    Self::is_free(maps, **neighbour)
+                        && maps[neighbour.region].rooms[neighbour.room].cells.len() == 1
+                })
+            })
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        algos::map_builder::test_maps::corridor_maps,
+        constants::TEST_SEED,
+        types::{Cell, Door, Rect, Room},
+    };
+
+    #[test]
+    fn test_add_landmarks_places_boss_away_from_start() {
+        // A corridor ending in a big room
+        let mut maps = corridor_maps(8, 0);
+        maps[0].origin_rect = Rect::new(0, 0, 10, 2);
+        maps[0]
+            .rooms
+            .push(Room::new_from_rect(Rect::new(8, 0, 2, 2)));
+        maps[0]
+            .doors
+            .push(Door::new(Cell::new(7, 0), Cell::new(8, 0)));
+
+        let config = MapBuilderConfig::builder().boss_rooms(1).build().unwrap();
+
+        let boss_count = MapBuilder::add_landmarks(&mut maps, &config, RngHandler::new(TEST_SEED));
+
+        assert_eq!(boss_count, 1);
+
+        let modifiers = maps[0]
+            .rooms
+            .iter()
+            .map(|room| room.modifier)
+            .collect::<Vec<_>>();
+
+        // The start ends up in one end of the corridor and the boss in the other
+        if modifiers[0] == Some(RoomModifier::Start) {
+            assert_eq!(modifiers[8], Some(RoomModifier::Boss));
+            assert_eq!(modifiers[7], Some(RoomModifier::Save));
+        } else {
+            assert_eq!(modifiers[8], Some(RoomModifier::Start));
+            assert_eq!(modifiers[0], Some(RoomModifier::Boss));
+            assert_eq!(modifiers[1], Some(RoomModifier::Save));
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use super::{MapBuilder, MapBuilderConfig};
+use crate::{
+    algos::{
+        RngHandler,
+        room_graph::{RoomGraph, RoomLink, cell_owners},
+    },
+    types::{DoorModifier, Map},
+};
+
+use rand::{Rng, seq::SliceRandom};
+
+impl MapBuilder {
+    // Turns some doors into one-way doors, returning how many were turned.
+    //
+    // A door only becomes one-way when the room it leads into has another way
+    // back to the room it leads out of, so every room can still be reached from
+    // every other room. Vertical doors become drops, leading downwards.
+    pub(super) fn add_one_way_doors(
+        maps: &mut [Map],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        if config.one_way_doors == 0 {
+            return 0;
+        }
+
+        let mut rng = rng_handler.rng();
+
+        let mut candidates = maps
+            .iter()
+            .enumerate()
+            .flat_map(|(region_idx, map)| {
+                map.doors
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, door)| {
+                        matches!(door.modifier, DoorModifier::Open | DoorModifier::None)
+                            && door.requirement.is_none()
+                            && !door.one_way
+                    })
+                    .map(move |(door_idx, _)| (region_idx, door_idx))
+            })
+            .collect::<Vec<_>>();
+
+        candidates.shuffle(&mut rng);
+
+        let cell_owners = cell_owners(maps);
+        let mut room_graph = RoomGraph::new(maps);
+        let mut one_way_count = 0;
+
+        for (region_idx, door_idx) in candidates {
+            let mut door = maps[region_idx].doors[door_idx];
+
+            let should_flip = if door.is_vertical() {
+                door.from.row > door.to.row
+            } else {
+                rng.random_bool(0.5)
+            };
+
+            if should_flip {
+                std::mem::swap(&mut door.from, &mut door.to);
+            }
+
+            let (Some(from_room), Some(to_room)) =
+                (cell_owners.get(&door.from), cell_owners.get(&door.to))
+            else {
+                continue;
+            };
+
+            if from_room == to_room {
+                continue;
+            }
+
+            let door_link = RoomLink::Door {
+                region: region_idx,
+                door: door_idx,
+            };
+
+            let has_way_back = room_graph
+                .distances_from(*to_room, |link| link != door_link && link != RoomLink::Warp)
+                .contains_key(from_room);
+
+            if !has_way_back {
+                continue;
+            }
+
+            door.one_way = true;
+            maps[region_idx].doors[door_idx] = door;
+
+            one_way_count += 1;
+            if one_way_count == config.one_way_doors as usize {
+                break;
+            }
+
+            room_graph = RoomGraph::new(maps);
+        }
+
+        one_way_count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algos::map_builder::test_maps::ring_maps, constants::TEST_SEED};
+
+    #[test]
+    fn test_add_one_way_doors_keeps_rooms_reachable() {
+        let mut maps = ring_maps();
+
+        let config = MapBuilderConfig::builder()
+            .one_way_doors(5)
+            .build()
+            .unwrap();
+
+        let one_way_count =
+            MapBuilder::add_one_way_doors(&mut maps, &config, RngHandler::new(TEST_SEED));
+
+        // The door into the dead end has to stay two-way
+        assert!(one_way_count > 0);
+        assert!(one_way_count < 5);
+        assert!(!maps[0].doors[4].one_way);
+
+        let room_graph = RoomGraph::new(&maps);
+        for room in room_graph.iter_rooms() {
+            assert_eq!(room_graph.distances_from(room, |_| true).len(), 5);
+        }
+
+        // Drops always lead downwards
+        assert!(
+            maps[0]
+                .doors
+                .iter()
+                .filter(|door| door.one_way && door.is_vertical())
+                .all(|door| door.from.row < door.to.row)
+        );
+    }
+}
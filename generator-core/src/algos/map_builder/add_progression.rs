@@ -0,0 +1,370 @@
+use super::{MapBuilder, MapBuilderConfig};
+use crate::{
+    algos::{
+        RngHandler,
+        progression::{Inventory, solve_regions},
+        room_graph::{RoomGraph, RoomLink, start_room},
+    },
+    constants::MIN_GATED_AREA_ROOMS,
+    types::{Ability, DoorModifier, Map, RoomModifier, RoomRef},
+};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::{Rng, seq::SliceRandom};
+
+// Something that has to be picked up to get past some doors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gate {
+    Ability(Ability),
+    Lock(u8),
+}
+
+impl Gate {
+    fn pickup(&self) -> RoomModifier {
+        match self {
+            Gate::Ability(ability) => RoomModifier::Item(Some(*ability)),
+            Gate::Lock(tier) => RoomModifier::Key(*tier),
+        }
+    }
+
+    fn add_to(&self, inventory: &mut Inventory) {
+        match self {
+            Gate::Ability(ability) => inventory.abilities.insert(*ability),
+            Gate::Lock(tier) => {
+                inventory.keys.insert(*tier);
+            }
+        }
+    }
+}
+
+impl MapBuilder {
+    // Returns the number of doors gated by an ability and the number of locked doors.
+    //
+    // Every gate closes the doors around a branch of the map reachable from the
+    // start room, and places its pickup somewhere that can be reached with the
+    // pickups of the previous gates, so the map can always be completed.
+    pub(super) fn add_progression(
+        maps: &mut [Map],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> (usize, usize) {
+        Self::add_progression_with(maps, config, rng_handler, |_, _| true)
+    }
+
+    // Like `add_progression`, but the first gate placed must close an area and
+    // use a pickup room accepted by `first_gate`.
+    pub(super) fn add_progression_with(
+        maps: &mut [Map],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+        first_gate: impl Fn(&BTreeSet<RoomRef>, RoomRef) -> bool,
+    ) -> (usize, usize) {
+        let Some(start) = start_room(maps) else {
+            return (0, 0);
+        };
+
+        let Some(baseline) = solve_regions(maps) else {
+            return (0, 0);
+        };
+
+        let room_graph = RoomGraph::new(maps);
+
+        let mut rng = rng_handler.rng();
+
+        let gates = config
+            .abilities
+            .iter()
+            .map(Gate::Ability)
+            .chain((0..config.lock_tiers).map(Gate::Lock));
+
+        let mut inventory = Inventory::default();
+
+        let mut ability_doors = 0;
+        let mut locked_doors = 0;
+        let mut placed_gates = 0;
+
+        for gate in gates {
+            let is_valid_gate = |gated_area: &BTreeSet<RoomRef>, pickup_room: RoomRef| {
+                placed_gates > 0 || first_gate(gated_area, pickup_room)
+            };
+
+            let (distances, parents) =
+                room_graph.search_from(start, |link| inventory.can_cross(maps, link));
+
+            let Some((gated_area, pickup_room)) = Self::select_gated_area(
+                maps,
+                &room_graph,
+                gate,
+                start,
+                &distances,
+                &parents,
+                &is_valid_gate,
+                &mut rng,
+            ) else {
+                continue;
+            };
+
+            let boundary_doors = gated_area
+                .iter()
+                .flat_map(|room| room_graph.iter_all_links(*room))
+                .filter(|(other, _)| !gated_area.contains(other))
+                .filter_map(|(_, link)| match link {
+                    RoomLink::Door { region, door } => Some((region, door)),
+                    RoomLink::Region | RoomLink::Warp | RoomLink::Castle => None,
+                })
+                .collect::<BTreeSet<_>>();
+
+            let previous_doors = boundary_doors
+                .iter()
+                .map(|(region, door)| maps[*region].doors[*door])
+                .collect::<Vec<_>>();
+            let previous_pickup_modifier =
+                maps[pickup_room.region].rooms[pickup_room.room].modifier;
+
+            for (region, door) in boundary_doors.iter() {
+                let door = &mut maps[*region].doors[*door];
+
+                match gate {
+                    Gate::Ability(ability) => door.requirement = Some(ability),
+                    Gate::Lock(tier) => door.modifier = DoorModifier::Locked(tier),
+                }
+            }
+            maps[pickup_room.region].rooms[pickup_room.room].modifier = Some(gate.pickup());
+
+            // We keep the gate only if the rooms that could be reached before still can
+            let is_solvable = solve_regions(maps)
+                .is_some_and(|solution| solution.unreachable == baseline.unreachable);
+
+            if is_solvable {
+                match gate {
+                    Gate::Ability(_) => ability_doors += boundary_doors.len(),
+                    Gate::Lock(_) => locked_doors += boundary_doors.len(),
+                }
+
+                gate.add_to(&mut inventory);
+                placed_gates += 1;
+            } else {
+                for ((region, door), previous_door) in boundary_doors.iter().zip(previous_doors) {
+                    maps[*region].doors[*door] = previous_door;
+                }
+                maps[pickup_room.region].rooms[pickup_room.room].modifier =
+                    previous_pickup_modifier;
+            }
+        }
+
+        (ability_doors, locked_doors)
+    }
+
+    // Picks a branch of the search tree to close with the gate, together with
+    // the room for its pickup.
+    #[allow(clippy::too_many_arguments)]
+    fn select_gated_area(
+        maps: &[Map],
+        room_graph: &RoomGraph,
+        gate: Gate,
+        start: RoomRef,
+        distances: &BTreeMap<RoomRef, usize>,
+        parents: &BTreeMap<RoomRef, RoomRef>,
+        is_valid_gate: &impl Fn(&BTreeSet<RoomRef>, RoomRef) -> bool,
+        rng: &mut impl Rng,
+    ) -> Option<(BTreeSet<RoomRef>, RoomRef)> {
+        let mut children = BTreeMap::<RoomRef, Vec<RoomRef>>::new();
+        for (child, parent) in parents.iter() {
+            children.entry(*parent).or_default().push(*child);
+        }
+
+        let max_area_rooms = distances.len() / 3;
+
+        let mut candidates = distances
+            .keys()
+            .copied()
+            .filter(|room| *room != start)
+            .map(|room| Self::collect_branch(room, &children))
+            .filter(|branch| (MIN_GATED_AREA_ROOMS..=max_area_rooms).contains(&branch.len()))
+            .collect::<Vec<_>>();
+
+        candidates.shuffle(rng);
+
+        let only_vertical_doors = match gate {
+            Gate::Ability(ability) => ability.gates_vertical_doors(),
+            Gate::Lock(_) => false,
+        };
+
+        for branch in candidates {
+            let holds_a_pickup = branch.iter().any(|room| {
+                matches!(
+                    maps[room.region].rooms[room.room].modifier,
+                    Some(RoomModifier::Key(_) | RoomModifier::Item(Some(_)))
+                )
+            });
+
+            if holds_a_pickup {
+                continue;
+            }
+
+            // The branch must only be entered or left through doors that are not gated yet
+            let has_valid_boundary = branch
+                .iter()
+                .flat_map(|room| room_graph.iter_all_links(*room))
+                .filter(|(other, _)| !branch.contains(other))
+                .all(|(_, link)| match link {
+                    RoomLink::Door { region, door } => {
+                        let door = &maps[region].doors[door];
+
+                        !matches!(door.modifier, DoorModifier::Locked(_))
+                            && door.requirement.is_none()
+                            && (!only_vertical_doors || door.is_vertical())
+                    }
+                    RoomLink::Region | RoomLink::Castle => false,
+                    RoomLink::Warp => true,
+                });
+
+            if !has_valid_boundary {
+                continue;
+            }
+
+            let pickup_rooms = distances
+                .iter()
+                .filter(|(room, _)| {
+                    **room != start
+                        && !branch.contains(*room)
+                        && Self::can_hold_pickup(maps, **room, gate)
+                        && is_valid_gate(&branch, **room)
+                })
+                .collect::<Vec<_>>();
+
+            let Some(max_distance) = pickup_rooms.iter().map(|(_, distance)| **distance).max()
+            else {
+                continue;
+            };
+
+            // Pickups are kept away from the start room so they take some exploring
+            let far_pickup_rooms = pickup_rooms
+                .into_iter()
+                .filter(|(_, distance)| **distance * 2 >= max_distance)
+                .map(|(room, _)| *room)
+                .collect::<Vec<_>>();
+
+            // Abilities go into the item rooms placed by the decorators when possible
+            let item_rooms = far_pickup_rooms
+                .iter()
+                .copied()
+                .filter(|room| {
+                    maps[room.region].rooms[room.room].modifier == Some(RoomModifier::Item(None))
+                })
+                .collect::<Vec<_>>();
+
+            let selection = if item_rooms.is_empty() {
+                far_pickup_rooms
+            } else {
+                item_rooms
+            };
+
+            let pickup_room = selection[rng.random_range(0..selection.len())];
+
+            return Some((branch, pickup_room));
+        }
+
+        None
+    }
+
+    fn can_hold_pickup(maps: &[Map], room: RoomRef, gate: Gate) -> bool {
+        matches!(
+            (maps[room.region].rooms[room.room].modifier, gate),
+            (None, _) | (Some(RoomModifier::Item(None)), Gate::Ability(_))
+        )
+    }
+
+    fn collect_branch(
+        root: RoomRef,
+        children: &BTreeMap<RoomRef, Vec<RoomRef>>,
+    ) -> BTreeSet<RoomRef> {
+        let mut branch = BTreeSet::new();
+        let mut rooms_to_visit = vec![root];
+
+        while let Some(room) = rooms_to_visit.pop() {
+            branch.insert(room);
+            rooms_to_visit.extend(children.get(&room).into_iter().flatten());
+        }
+
+        branch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algos::map_builder::test_maps::comb_maps, constants::TEST_SEED};
+
+    #[test]
+    fn test_add_progression_keeps_map_solvable() {
+        let mut maps = comb_maps(6, 1);
+
+        let config = MapBuilderConfig::builder().lock_tiers(2).build().unwrap();
+
+        let (ability_doors, locked_doors) =
+            MapBuilder::add_progression(&mut maps, &config, RngHandler::new(TEST_SEED));
+
+        assert_eq!(ability_doors, 0);
+        assert!(locked_doors > 0);
+        assert_eq!(
+            maps[0]
+                .doors
+                .iter()
+                .filter(|door| matches!(door.modifier, DoorModifier::Locked(_)))
+                .count(),
+            locked_doors
+        );
+        assert!(
+            maps[0]
+                .rooms
+                .iter()
+                .any(|room| room.modifier == Some(RoomModifier::Key(0)))
+        );
+
+        let solution = solve_regions(&maps).unwrap();
+        assert!(solution.is_completable());
+        assert!(solution.spheres.len() > 1);
+    }
+
+    #[test]
+    fn test_add_progression_places_every_ability() {
+        let mut maps = comb_maps(8, 3);
+        maps[0].rooms[21].modifier = Some(RoomModifier::Item(None));
+
+        let config = MapBuilderConfig::builder()
+            .lock_tiers(0)
+            .abilities([Ability::MorphBall, Ability::HighJump])
+            .build()
+            .unwrap();
+
+        let (ability_doors, locked_doors) =
+            MapBuilder::add_progression(&mut maps, &config, RngHandler::new(TEST_SEED));
+
+        assert!(ability_doors > 0);
+        assert_eq!(locked_doors, 0);
+
+        // The high jump only gates the vertical doors into the side rooms
+        assert!(
+            maps[0]
+                .doors
+                .iter()
+                .filter(|door| door.requirement == Some(Ability::HighJump))
+                .all(|door| door.is_vertical())
+        );
+
+        let solution = solve_regions(&maps).unwrap();
+        assert!(solution.is_completable());
+
+        let found_abilities = solution
+            .spheres
+            .iter()
+            .flat_map(|sphere| sphere.abilities.iter().copied())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            found_abilities,
+            BTreeSet::from([Ability::MorphBall, Ability::HighJump])
+        );
+    }
+}
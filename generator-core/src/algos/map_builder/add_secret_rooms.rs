@@ -0,0 +1,142 @@
+use super::{MapBuilder, MapBuilderConfig};
+use crate::{
+    algos::RngHandler,
+    types::{Door, DoorModifier, MapRegion, RoomModifier},
+};
+
+use rand::{Rng, seq::SliceRandom};
+
+impl MapBuilder {
+    // Brings some of the removed rooms of the region back as secret rooms,
+    // returning how many were brought back.
+    //
+    // Every secret room gets a single secret door into one of the active rooms
+    // next to it, so it is always a dead end, and secret rooms are never
+    // entered through each other.
+    pub(super) fn add_secret_rooms(
+        map_region: &mut MapRegion,
+        doors: &mut Vec<Door>,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        if config.secret_rooms == 0 {
+            return 0;
+        }
+
+        let mut rng = rng_handler.rng();
+
+        let mut candidates = map_region
+            .iter_removed()
+            .map(|(room_id, _)| room_id)
+            .collect::<Vec<_>>();
+
+        candidates.shuffle(&mut rng);
+
+        let mut secret_count = 0;
+
+        for room_id in candidates {
+            let room = map_region.get_removed(room_id);
+
+            let entrances = map_region
+                .iter_active_neighbours(room_id)
+                .filter(|neighbour_id| {
+                    map_region.get_active(*neighbour_id).modifier != Some(RoomModifier::Secret)
+                })
+                .flat_map(|neighbour_id| {
+                    let neighbour = map_region.get_active(neighbour_id);
+
+                    room.get_neighbouring_cells_for(neighbour)
+                        .unwrap_or_default()
+                        .into_iter()
+                        // Rooms stamped from a template keep their doors at their anchors
+                        .filter(|(cell, other_cell, _)| {
+                            room.door_cells().contains(cell)
+                                && neighbour.door_cells().contains(other_cell)
+                        })
+                        .map(|(cell, other_cell, direction)| {
+                            (cell, other_cell, direction, neighbour.region)
+                        })
+                })
+                .collect::<Vec<_>>();
+
+            // Like any other door, secret doors are horizontal whenever possible
+            let horizontal_entrances = entrances
+                .iter()
+                .copied()
+                .filter(|(_, _, direction, _)| direction.is_horizontal())
+                .collect::<Vec<_>>();
+
+            let entrances = if horizontal_entrances.is_empty() {
+                entrances
+            } else {
+                horizontal_entrances
+            };
+
+            if entrances.is_empty() {
+                continue;
+            }
+
+            let (secret_cell, entrance_cell, _, entrance_region) =
+                entrances[rng.random_range(0..entrances.len())];
+
+            let mut door = Door::new(entrance_cell, secret_cell);
+            door.modifier = DoorModifier::Secret;
+            door.area_transition = room.region != entrance_region;
+            doors.push(door);
+
+            map_region.mark_active(room_id);
+            map_region.get_mut_room(room_id).modifier = Some(RoomModifier::Secret);
+
+            secret_count += 1;
+            if secret_count == config.secret_rooms as usize {
+                break;
+            }
+        }
+
+        secret_count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::TEST_SEED;
+
+    #[test]
+    fn test_add_secret_rooms_are_dead_ends() {
+        let mut map_region = MapRegion::new_test_region();
+        let mut doors = Vec::new();
+
+        let config = MapBuilderConfig::builder().secret_rooms(8).build().unwrap();
+
+        let secret_count = MapBuilder::add_secret_rooms(
+            &mut map_region,
+            &mut doors,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+
+        assert!(secret_count > 0);
+        assert_eq!(doors.len(), secret_count);
+
+        let secret_rooms = map_region
+            .iter_active()
+            .filter(|(_, room)| room.modifier == Some(RoomModifier::Secret))
+            .map(|(_, room)| room)
+            .collect::<Vec<_>>();
+
+        assert_eq!(secret_rooms.len(), secret_count);
+
+        for room in secret_rooms {
+            let room_doors = doors
+                .iter()
+                .filter(|door| room.cells.contains(&door.from) || room.cells.contains(&door.to))
+                .collect::<Vec<_>>();
+
+            // The only door of a secret room leads into it from a room that is not secret
+            assert_eq!(room_doors.len(), 1);
+            assert_eq!(room_doors[0].modifier, DoorModifier::Secret);
+            assert!(room.cells.contains(&room_doors[0].to));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use super::{MapBuilder, MapBuilderConfig};
+use crate::{
+    algos::{
+        RngHandler,
+        room_graph::{RoomGraph, RoomLink},
+    },
+    types::{Map, RoomModifier, RoomRef},
+};
+
+use std::collections::BTreeMap;
+
+use rand::seq::SliceRandom;
+
+impl MapBuilder {
+    // Turns some single cell rooms into warp rooms, returning how many were placed.
+    //
+    // Going through the warp network between two rooms takes the doors to the
+    // warp room closest to each of them, so every warp room is picked to
+    // minimize the total distance from the rooms of the map to their closest
+    // warp room.
+    pub(super) fn add_warp_rooms(
+        maps: &mut [Map],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        if config.warp_rooms == 0 {
+            return 0;
+        }
+
+        let room_graph = RoomGraph::new(maps);
+
+        let mut candidates = room_graph
+            .iter_rooms()
+            .filter(|room| {
+                let room = &maps[room.region].rooms[room.room];
+                room.modifier.is_none() && room.cells.len() == 1
+            })
+            .map(|room| {
+                let distances = room_graph.distances_from(room, |link| link != RoomLink::Warp);
+                (room, distances)
+            })
+            .collect::<Vec<_>>();
+
+        // Shuffled so that ties do not always go to the first rooms
+        candidates.shuffle(&mut rng_handler.rng());
+
+        let mut warp_distances = room_graph
+            .iter_rooms()
+            .map(|room| (room, usize::MAX))
+            .collect::<BTreeMap<_, _>>();
+        let mut warp_count = 0;
+
+        while warp_count < config.warp_rooms as usize {
+            let best_candidate = candidates
+                .iter()
+                .filter(|(room, _)| maps[room.region].rooms[room.room].modifier.is_none())
+                .min_by_key(|(_, distances)| Self::total_warp_distance(&warp_distances, distances));
+
+            let Some((warp_room, distances)) = best_candidate else {
+                break;
+            };
+
+            maps[warp_room.region].rooms[warp_room.room].modifier = Some(RoomModifier::Warp);
+            warp_count += 1;
+
+            for (room, distance) in distances.iter() {
+                let warp_distance = warp_distances.get_mut(room).unwrap();
+                *warp_distance = (*warp_distance).min(*distance);
+            }
+        }
+
+        warp_count
+    }
+
+    // The sum of the distances from every room to its closest warp room, after
+    // adding the warp room with the given distances.
+    fn total_warp_distance(
+        warp_distances: &BTreeMap<RoomRef, usize>,
+        distances: &BTreeMap<RoomRef, usize>,
+    ) -> usize {
+        warp_distances
+            .iter()
+            .map(|(room, warp_distance)| {
+                let distance = distances.get(room).copied().unwrap_or(usize::MAX);
+                // Rooms that cannot reach any warp room count as far as the map is big
+                (*warp_distance).min(distance).min(warp_distances.len())
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        algos::{map_builder::test_maps::corridor_maps, warp_links},
+        constants::TEST_SEED,
+    };
+
+    // The average number of links between every pair of rooms
+    fn average_travel_distance(maps: &[Map]) -> f64 {
+        let room_graph = RoomGraph::new(maps);
+
+        let rooms = room_graph.iter_rooms().collect::<Vec<_>>();
+        let total_distance = rooms
+            .iter()
+            .flat_map(|room| room_graph.distances_from(*room, |_| true).into_values())
+            .sum::<usize>();
+
+        total_distance as f64 / (rooms.len() * rooms.len()) as f64
+    }
+
+    #[test]
+    fn test_add_warp_rooms_shortens_travel() {
+        let mut maps = corridor_maps(30, 0);
+        let travel_without_warps = average_travel_distance(&maps);
+
+        let config = MapBuilderConfig::builder().warp_rooms(3).build().unwrap();
+
+        let warp_count = MapBuilder::add_warp_rooms(&mut maps, &config, RngHandler::new(TEST_SEED));
+
+        assert_eq!(warp_count, 3);
+        assert_eq!(warp_links(&maps).len(), 3);
+
+        // The warp rooms are spread along the corridor
+        let warp_rooms = maps[0]
+            .rooms
+            .iter()
+            .enumerate()
+            .filter(|(_, room)| room.modifier == Some(RoomModifier::Warp))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        assert!(warp_rooms.windows(2).all(|pair| pair[1] - pair[0] >= 5));
+
+        assert!(average_travel_distance(&maps) < travel_without_warps / 2.0);
+    }
+}
@@ -2,19 +2,24 @@ use super::MapBuilder;
 use crate::{
     algos::RngHandler,
     constants::MIN_BISECT_SIZE,
-    types::{MapRegion, NeighbourSet, Room, RoomId},
+    types::{Cell, MapRegion, NeighbourSet, Room, RoomId},
 };
 
 use rand::Rng;
 
 impl MapBuilder {
-    pub(super) fn bisect_long_horizontal_rooms(map_region: &mut MapRegion, bisect_chance: f64) {
+    // Returns the number of bisected rooms.
+    pub(super) fn bisect_long_horizontal_rooms(
+        map_region: &mut MapRegion,
+        bisect_chance: f64,
+        rng_handler: RngHandler,
+    ) -> usize {
         let mut target_rooms = Vec::new();
 
-        let mut rng = RngHandler::rng();
+        let mut rng = rng_handler.rng();
 
         for (idx, room) in map_region.iter_active() {
-            if room.cells.len() < MIN_BISECT_SIZE {
+            if room.pinned || room.cells.len() < MIN_BISECT_SIZE {
                 continue;
             }
 
@@ -30,6 +35,8 @@ impl MapBuilder {
             target_rooms.push(idx);
         }
 
+        let mut bisected_rooms = 0;
+
         for room_id in target_rooms.into_iter() {
             let should_bisect = rng.random_bool(bisect_chance);
 
@@ -37,14 +44,28 @@ impl MapBuilder {
                 continue;
             }
 
+            bisected_rooms += 1;
+
             let mut room = map_region.take_active(room_id);
-            room.cells.sort_by(|a, b| a.col.cmp(&b.col));
+            room.cells.sort_by_key(|cell| cell.col);
 
             let bisect_cell = rng.random_range(1..room.cells.len() - 1);
 
+            // Every part keeps the door anchors that fall inside of it
+            let door_anchors_in = |cells: &[Cell]| {
+                room.door_anchors
+                    .iter()
+                    .filter(|anchor| cells.contains(anchor))
+                    .copied()
+                    .collect::<Vec<_>>()
+            };
+
             let room_a = Room {
                 cells: room.cells[0..bisect_cell].to_vec(),
                 modifier: room.modifier,
+                region: room.region,
+                door_anchors: door_anchors_in(&room.cells[0..bisect_cell]),
+                pinned: room.pinned,
             };
             let room_a_id = map_region.insert_room(room_a);
             Self::recompute_neighbours_for(room_id, map_region, room_a_id);
@@ -52,6 +73,9 @@ impl MapBuilder {
             let room_b = Room {
                 cells: vec![room.cells[bisect_cell]],
                 modifier: room.modifier,
+                region: room.region,
+                door_anchors: door_anchors_in(&room.cells[bisect_cell..=bisect_cell]),
+                pinned: room.pinned,
             };
             let room_b_id = map_region.insert_room(room_b);
             Self::recompute_neighbours_for(room_id, map_region, room_b_id);
@@ -59,6 +83,9 @@ impl MapBuilder {
             let room_c = Room {
                 cells: room.cells[(bisect_cell + 1)..].to_vec(),
                 modifier: room.modifier,
+                region: room.region,
+                door_anchors: door_anchors_in(&room.cells[(bisect_cell + 1)..]),
+                pinned: room.pinned,
             };
             let room_c_id = map_region.insert_room(room_c);
             Self::recompute_neighbours_for(room_id, map_region, room_c_id);
@@ -72,6 +99,8 @@ impl MapBuilder {
         }
 
         map_region.shrink_buffers();
+
+        bisected_rooms
     }
 
     fn recompute_neighbours_for(room_id: RoomId, map_region: &mut MapRegion, new_room_id: RoomId) {
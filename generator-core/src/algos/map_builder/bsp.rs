@@ -2,7 +2,7 @@ use super::BinarySpacePartitioningConfig;
 use crate::{
     algos::RngHandler,
     constants::{MIN_RECT_HEIGHT, MIN_RECT_WIDTH},
-    types::{NeighbourSet, Rect, RectModifier, RectRegion, SplitAxis},
+    types::{CellMask, NeighbourSet, Rect, RectModifier, RectRegion, SplitAxis},
 };
 
 use std::collections::{HashMap, VecDeque};
@@ -15,13 +15,22 @@ pub(crate) type RectTable = HashMap<usize, Rect>;
 pub(crate) type RemovedRectTable = HashMap<usize, Rect>;
 pub(crate) type NeighbourTable = HashMap<usize, NeighbourSet>;
 
+// Stream identifiers for the random streams used by each step
+const REGION_STREAM: u64 = 0;
+const PARTITION_STREAM: u64 = 1;
+const TRIM_STREAM: u64 = 2;
+
 pub(crate) struct BinarySpacePartitioning;
 
 impl BinarySpacePartitioning {
+    // Partitions the canvas into regions of rects, leaving out the cells outside
+    // of the mask, if any.
     pub fn generate_and_trim_partitions(
         width: u32,
         height: u32,
+        mask: Option<&CellMask>,
         config: BinarySpacePartitioningConfig,
+        rng_handler: RngHandler,
     ) -> Vec<(Rect, RectTable, RemovedRectTable, NeighbourTable)> {
         if width <= MIN_RECT_WIDTH || height <= MIN_RECT_HEIGHT {
             event!(
@@ -42,7 +51,8 @@ impl BinarySpacePartitioning {
             initial_rect
         );
 
-        let regions = Self::generate_regions(initial_rect, &config);
+        let regions =
+            Self::generate_regions(initial_rect, &config, rng_handler.derive(REGION_STREAM));
 
         let avg_region_area =
             regions.iter().map(|r| r.rect.area()).sum::<u32>() / regions.len() as u32;
@@ -56,19 +66,35 @@ impl BinarySpacePartitioning {
             max_region_area - min_region_area
         );
 
+        let partition_rng = rng_handler.derive(PARTITION_STREAM);
+
         regions
             .into_par_iter()
-            .map(|region| {
+            .enumerate()
+            .map(|(region_idx, region)| {
                 let origin_rect = region.rect;
+                // Each region gets its own stream, so the result does not depend
+                // on the order in which the regions are processed
+                let region_rng = partition_rng.derive(region_idx as u64);
 
                 let (mut region_rects, mut removed_rects, mut neighbours) =
-                    Self::generate_partitions(region, &config);
+                    Self::generate_partitions(region, &config, mask, region_rng);
+
+                if let Some(mask) = mask {
+                    Self::drop_masked_rects(
+                        &mut region_rects,
+                        &mut removed_rects,
+                        &mut neighbours,
+                        mask,
+                    );
+                }
 
                 Self::trim_connected_rects(
                     &mut region_rects,
                     &mut removed_rects,
                     &mut neighbours,
                     &config,
+                    region_rng.derive(TRIM_STREAM),
                 );
 
                 Self::trim_orphaned_rects(&mut region_rects, &mut removed_rects, &mut neighbours);
@@ -85,6 +111,7 @@ impl BinarySpacePartitioning {
     fn generate_regions(
         initial_rect: Rect,
         config: &BinarySpacePartitioningConfig,
+        rng_handler: RngHandler,
     ) -> Vec<RectRegion> {
         let mut rect_queue = VecDeque::new();
         rect_queue.push_back(initial_rect);
@@ -92,7 +119,7 @@ impl BinarySpacePartitioning {
         let mut built_rects =
             Vec::with_capacity(initial_rect.area() as usize / config.region_split_factor as usize);
 
-        let mut rng = rand::rng();
+        let mut rng = rng_handler.rng();
 
         while let Some(rect) = rect_queue.pop_front() {
             let rect_split_factor =
@@ -103,7 +130,7 @@ impl BinarySpacePartitioning {
                 continue;
             }
 
-            let (rect_a, maybe_rect_b) = Self::split_rect(rect, 1.0, 1.0, 0.5, 0.0);
+            let (rect_a, maybe_rect_b) = Self::split_rect(rect, 1.0, 1.0, 0.5, 0.0, &mut rng);
             if rect_a.area() < config.region_split_factor {
                 rect_queue.push_back(rect_a);
             } else {
@@ -119,8 +146,6 @@ impl BinarySpacePartitioning {
             }
         }
 
-        let mut rng = RngHandler::rng();
-
         built_rects
             .into_iter()
             .map(|rect| {
@@ -148,8 +173,10 @@ impl BinarySpacePartitioning {
     fn generate_partitions(
         region: RectRegion,
         config: &BinarySpacePartitioningConfig,
+        mask: Option<&CellMask>,
+        rng_handler: RngHandler,
     ) -> (RectTable, RemovedRectTable, NeighbourTable) {
-        let mut rng = RngHandler::rng();
+        let mut rng = rng_handler.rng();
 
         let min_area = config.rect_area_cutoff;
         let max_area = config.big_rect_area_cutoff;
@@ -203,8 +230,19 @@ impl BinarySpacePartitioning {
             let rect = rect_table.remove(&idx).unwrap();
             let rect_area = rect.area();
 
-            if rect_area > min_area {
-                if rect_area <= max_area && rng.random_bool(config.big_rect_survival_prob) {
+            // Rects crossing the boundary of the mask are split until they fall
+            // either inside or outside of it
+            let is_cut_by_mask = mask.is_some_and(|mask| {
+                let cells_inside = mask.cells_inside(&rect);
+
+                cells_inside > 0 && cells_inside < rect_area as usize
+            });
+
+            if rect_area > min_area || is_cut_by_mask {
+                if !is_cut_by_mask
+                    && rect_area <= max_area
+                    && rng.random_bool(config.big_rect_survival_prob)
+                {
                     // The rectangle survived, so we put it back into the table
                     rect_table.insert(idx, rect);
                 } else {
@@ -214,6 +252,7 @@ impl BinarySpacePartitioning {
                         width_factor_cutoff,
                         horizontal_split_prob,
                         1.0,
+                        &mut rng,
                     );
 
                     rect_idx += 1;
@@ -288,12 +327,11 @@ impl BinarySpacePartitioning {
         width_cutoff: f32,
         horizontal_split_prob: f64,
         chaos_factor: f32,
+        rng: &mut impl Rng,
     ) -> (Rect, Option<Rect>) {
         let height_factor = rect.height as f32 / rect.width as f32;
         let width_factor = rect.width as f32 / rect.height as f32;
 
-        let mut rng = RngHandler::rng();
-
         let split_axis = {
             if height_factor > height_cutoff {
                 SplitAxis::Horizontal
@@ -346,11 +384,39 @@ impl BinarySpacePartitioning {
         }
     }
 
+    // Drops the rects outside of the mask, since they can not hold any room,
+    // not even a secret one.
+    fn drop_masked_rects(
+        rects: &mut HashMap<usize, Rect>,
+        removed: &mut HashMap<usize, Rect>,
+        neighbour_map: &mut HashMap<usize, NeighbourSet>,
+        mask: &CellMask,
+    ) {
+        let masked_rects = rects
+            .iter()
+            .chain(removed.iter())
+            .filter(|(_, rect)| mask.cells_inside(rect) == 0)
+            .map(|(idx, _)| *idx)
+            .collect::<Vec<_>>();
+
+        for rect_idx in masked_rects {
+            rects.remove(&rect_idx);
+            removed.remove(&rect_idx);
+
+            for neighbour in neighbour_map.remove(&rect_idx).unwrap_or_default().iter() {
+                if let Some(neighbour_set) = neighbour_map.get_mut(&neighbour) {
+                    neighbour_set.remove(rect_idx);
+                }
+            }
+        }
+    }
+
     fn trim_connected_rects(
         rects: &mut HashMap<usize, Rect>,
         removed: &mut HashMap<usize, Rect>,
         neighbour_map: &mut HashMap<usize, NeighbourSet>,
         config: &BinarySpacePartitioningConfig,
+        rng_handler: RngHandler,
     ) {
         let rects_to_remove = rects
             .par_iter()
@@ -362,7 +428,7 @@ impl BinarySpacePartitioning {
                         .count()
                 });
 
-                let mut rng = RngHandler::rng();
+                let mut rng = rng_handler.derive(*idx as u64).rng();
 
                 let should_remove = match neighbour_count {
                     8.. => rng.random_bool(config.trim_fully_connected_rect_prob),
@@ -416,17 +482,26 @@ impl BinarySpacePartitioning {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::constants::TEST_SEED;
 
     #[test]
     fn test_generate_and_trim_partitions() {
         let width = 20;
         let height = 20;
 
-        let mut config = BinarySpacePartitioningConfig::default();
         // Force only two regions to be generated
-        config.region_split_factor = width * height;
+        let config = BinarySpacePartitioningConfig {
+            region_split_factor: width * height,
+            ..Default::default()
+        };
 
-        let results = BinarySpacePartitioning::generate_and_trim_partitions(width, height, config);
+        let results = BinarySpacePartitioning::generate_and_trim_partitions(
+            width,
+            height,
+            None,
+            config,
+            RngHandler::new(TEST_SEED),
+        );
 
         for (origin_rect, rect_table, removed_rects, neighbours) in results {
             // Check origin rect dimensions
@@ -477,4 +552,41 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_generate_and_trim_partitions_respects_mask() {
+        let width = 24;
+        let height = 20;
+
+        let mask = CellMask::from_polygon(
+            width,
+            height,
+            &[(12.0, 0.0), (24.0, 10.0), (12.0, 20.0), (0.0, 10.0)],
+        )
+        .unwrap();
+
+        let results = BinarySpacePartitioning::generate_and_trim_partitions(
+            width,
+            height,
+            Some(&mask),
+            BinarySpacePartitioningConfig::default(),
+            RngHandler::new(TEST_SEED),
+        );
+
+        assert!(!results.is_empty());
+
+        for (_, rect_table, removed_rects, neighbours) in results {
+            assert_eq!(rect_table.len() + removed_rects.len(), neighbours.len());
+
+            for rect in rect_table.values().chain(removed_rects.values()) {
+                assert!(rect.get_cells().into_iter().all(|cell| mask.contains(cell)));
+            }
+
+            for neighbour_set in neighbours.values() {
+                for neighbour_idx in neighbour_set.iter() {
+                    assert!(neighbours.contains_key(&neighbour_idx));
+                }
+            }
+        }
+    }
 }
@@ -1,7 +1,21 @@
-use crate::{MapStyle, constants::REGION_SPLIT_FACTOR};
+use crate::{
+    MapStyle,
+    constants::{
+        MAX_BOSS_ROOMS, MAX_CASTLE_LINKS, MAX_FLOORS, MAX_LOCK_TIERS, MAX_WARP_ROOMS,
+        REGION_SPLIT_FACTOR,
+    },
+    types::{Ability, AbilitySet},
+};
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) struct BinarySpacePartitioningConfig {
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Parameters of the binary space partitioning used to lay out the rooms.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BinarySpacePartitioningConfig {
     pub region_split_factor: u32,
     // The proportion of regions that are going to be PreferHorizontal
     // over PreferVertical. The Standard and Chaotic modifiers are
@@ -49,8 +63,13 @@ impl Default for BinarySpacePartitioningConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) struct MapBuilderConfig {
+/// Parameters controlling how the rooms and doors of a map are generated.
+///
+/// Use [`MapBuilderConfig::from_style`] to start from the preset of a style,
+/// and [`MapBuilderConfig::to_builder`] to tweak it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapBuilderConfig {
     pub bsp_config: BinarySpacePartitioningConfig,
     // Should we merge the regions after generating their rooms?
     pub merge_regions: bool,
@@ -61,8 +80,56 @@ pub(crate) struct MapBuilderConfig {
     // Probability of opening a connection between rooms that will
     // cause a navigation loop in the map.
     pub door_loop_connection_chance: f64,
+    // Highest share of the active rooms that can be dead ends, more doors are
+    // added between neighbouring rooms until it is met. These doors count
+    // towards target_loops.
+    pub max_dead_end_ratio: f64,
+    // Number of loops, the doors beyond the ones needed to reach every room,
+    // counted as set by loop_target_scope. When set, it replaces
+    // door_loop_connection_chance. The doors added for max_dead_end_ratio come
+    // first, so they can take the map beyond the target.
+    pub target_loops: Option<u32>,
+    // Whether target_loops is met by every region or by the whole map.
+    pub loop_target_scope: LoopTargetScope,
+    // Length of the loops opened to meet target_loops, from the shortest ones
+    // at 0.0 to the longest ones at 1.0.
+    pub loop_length_bias: f64,
+    // Probability of merging small neighbouring rooms together.
     pub repeat_small_room_merge_prob: f64,
+    // Probability of splitting a long horizontal room in two.
     pub bisect_room_prob: f64,
+    // Probability of stamping a room template into a region, tried again after
+    // every stamped template.
+    pub room_template_prob: f64,
+    // Number of locked areas, each one opened by the key of its tier.
+    pub lock_tiers: u8,
+    // Abilities gating areas of the map, placed before the locks. The set keeps
+    // no order, the gates are placed in the declaration order of the abilities.
+    pub abilities: AbilitySet,
+    // Number of boss rooms, placed far away from the start room and from each other.
+    pub boss_rooms: u8,
+    // Maximum number of doors between any room and its closest save room.
+    pub max_save_distance: u32,
+    // Number of navigation rooms in every region the map was partitioned into,
+    // also when the regions are merged.
+    pub navigation_rooms: u32,
+    // Number of warp rooms, spread out to shorten the travel across the map.
+    pub warp_rooms: u8,
+    // Number of doors that can only be gone through in one direction.
+    pub one_way_doors: u32,
+    // Number of removed rooms brought back as secret rooms in every decorated
+    // region, or in the whole map when the regions are merged.
+    pub secret_rooms: u32,
+    // Number of floors generated by the layered mode, each one a full layout.
+    pub floors: u8,
+    // Number of stairs or elevators between every pair of consecutive floors.
+    pub floor_links: u32,
+    // Whether the map also gets an inverted castle, the same castle turned upside down.
+    pub inverted_castle: bool,
+    // Whether the map is made of two different castles linked by warp rooms.
+    pub dual_castle: bool,
+    // Number of warp rooms shared by both castles of a dual castle.
+    pub castle_links: u8,
 }
 
 impl Default for MapBuilderConfig {
@@ -73,13 +140,502 @@ impl Default for MapBuilderConfig {
             random_room_merge_prob: 0.05,
             group_loop_connection_chance: 0.17,
             door_loop_connection_chance: 0.2,
+            max_dead_end_ratio: 1.0,
+            target_loops: None,
+            loop_target_scope: LoopTargetScope::WholeMap,
+            loop_length_bias: 0.5,
             repeat_small_room_merge_prob: 0.2,
             bisect_room_prob: 0.1,
+            room_template_prob: 0.0,
+            lock_tiers: 0,
+            abilities: AbilitySet::empty(),
+            boss_rooms: 0,
+            max_save_distance: 6,
+            navigation_rooms: 1,
+            warp_rooms: 0,
+            one_way_doors: 0,
+            secret_rooms: 0,
+            floors: 1,
+            floor_links: 2,
+            inverted_castle: false,
+            dual_castle: false,
+            castle_links: 3,
+        }
+    }
+}
+
+/// Where the loops of [`MapBuilderConfig::target_loops`] are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopTargetScope {
+    /// Every region the map was partitioned into holds the target, counting
+    /// only the doors between its own rooms.
+    PerRegion,
+    /// The whole map holds the target, shared between the regions by their
+    /// number of rooms when they are not merged.
+    WholeMap,
+}
+
+/// The format of a configuration overrides document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Guesses the format from the extension of the given path.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(ConfigFormat::Json),
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Ok(ConfigFormat::Toml),
+            _ => Err(anyhow::anyhow!(
+                "Unknown config format for file '{}', expected a .json or .toml file",
+                path.display()
+            )),
         }
     }
 }
 
+fn check_probability(name: &str, value: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(anyhow::anyhow!(
+            "{} must be between 0.0 and 1.0, got {}",
+            name,
+            value
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_factor_cutoff(name: &str, value: f32) -> Result<()> {
+    if !(1.0..=5.0).contains(&value) {
+        return Err(anyhow::anyhow!(
+            "{} must be between 1.0 and 5.0, got {}",
+            name,
+            value
+        ));
+    }
+
+    Ok(())
+}
+
+// Recursively replaces the values of base with the ones found in overrides,
+// keeping the values that are not overriden.
+fn merge_values(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+impl BinarySpacePartitioningConfig {
+    /// Returns a builder initialized with the values of this config.
+    pub fn to_builder(self) -> BinarySpacePartitioningConfigBuilder {
+        BinarySpacePartitioningConfigBuilder { config: self }
+    }
+
+    /// Checks that every parameter is within its valid range.
+    pub fn validate(&self) -> Result<()> {
+        if self.region_split_factor == 0 {
+            return Err(anyhow::anyhow!(
+                "region_split_factor must be greater than zero"
+            ));
+        }
+        if self.rect_area_cutoff == 0 {
+            return Err(anyhow::anyhow!(
+                "rect_area_cutoff must be greater than zero"
+            ));
+        }
+        if self.big_rect_area_cutoff < self.rect_area_cutoff {
+            return Err(anyhow::anyhow!(
+                "big_rect_area_cutoff ({}) must not be smaller than rect_area_cutoff ({})",
+                self.big_rect_area_cutoff,
+                self.rect_area_cutoff
+            ));
+        }
+
+        check_factor_cutoff("height_factor_cutoff", self.height_factor_cutoff)?;
+        check_factor_cutoff("width_factor_cutoff", self.width_factor_cutoff)?;
+
+        check_probability("horizontal_region_prob", self.horizontal_region_prob)?;
+        check_probability("big_rect_survival_prob", self.big_rect_survival_prob)?;
+        check_probability("horizontal_split_prob", self.horizontal_split_prob)?;
+        check_probability("rect_survival_prob", self.rect_survival_prob)?;
+        check_probability(
+            "trim_highly_connected_rect_prob",
+            self.trim_highly_connected_rect_prob,
+        )?;
+        check_probability(
+            "trim_fully_connected_rect_prob",
+            self.trim_fully_connected_rect_prob,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Builder for [`BinarySpacePartitioningConfig`], validating the values on [`build`].
+///
+/// [`build`]: BinarySpacePartitioningConfigBuilder::build
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BinarySpacePartitioningConfigBuilder {
+    config: BinarySpacePartitioningConfig,
+}
+
+impl BinarySpacePartitioningConfigBuilder {
+    pub fn region_split_factor(mut self, value: u32) -> Self {
+        self.config.region_split_factor = value;
+        self
+    }
+
+    pub fn horizontal_region_prob(mut self, value: f64) -> Self {
+        self.config.horizontal_region_prob = value;
+        self
+    }
+
+    pub fn rect_area_cutoff(mut self, value: u32) -> Self {
+        self.config.rect_area_cutoff = value;
+        self
+    }
+
+    pub fn big_rect_area_cutoff(mut self, value: u32) -> Self {
+        self.config.big_rect_area_cutoff = value;
+        self
+    }
+
+    pub fn big_rect_survival_prob(mut self, value: f64) -> Self {
+        self.config.big_rect_survival_prob = value;
+        self
+    }
+
+    pub fn horizontal_split_prob(mut self, value: f64) -> Self {
+        self.config.horizontal_split_prob = value;
+        self
+    }
+
+    pub fn height_factor_cutoff(mut self, value: f32) -> Self {
+        self.config.height_factor_cutoff = value;
+        self
+    }
+
+    pub fn width_factor_cutoff(mut self, value: f32) -> Self {
+        self.config.width_factor_cutoff = value;
+        self
+    }
+
+    pub fn rect_survival_prob(mut self, value: f64) -> Self {
+        self.config.rect_survival_prob = value;
+        self
+    }
+
+    pub fn trim_highly_connected_rect_prob(mut self, value: f64) -> Self {
+        self.config.trim_highly_connected_rect_prob = value;
+        self
+    }
+
+    pub fn trim_fully_connected_rect_prob(mut self, value: f64) -> Self {
+        self.config.trim_fully_connected_rect_prob = value;
+        self
+    }
+
+    pub fn build(self) -> Result<BinarySpacePartitioningConfig> {
+        self.config.validate()?;
+
+        Ok(self.config)
+    }
+}
+
+/// Builder for [`MapBuilderConfig`], validating the values on [`build`].
+///
+/// [`build`]: MapBuilderConfigBuilder::build
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapBuilderConfigBuilder {
+    config: MapBuilderConfig,
+}
+
+impl MapBuilderConfigBuilder {
+    /// Tweaks the partitioning parameters, starting from the current ones.
+    pub fn bsp_config(
+        mut self,
+        f: impl FnOnce(BinarySpacePartitioningConfigBuilder) -> BinarySpacePartitioningConfigBuilder,
+    ) -> Self {
+        self.config.bsp_config = f(self.config.bsp_config.to_builder()).config;
+        self
+    }
+
+    pub fn merge_regions(mut self, value: bool) -> Self {
+        self.config.merge_regions = value;
+        self
+    }
+
+    pub fn random_room_merge_prob(mut self, value: f64) -> Self {
+        self.config.random_room_merge_prob = value;
+        self
+    }
+
+    pub fn group_loop_connection_chance(mut self, value: f64) -> Self {
+        self.config.group_loop_connection_chance = value;
+        self
+    }
+
+    pub fn door_loop_connection_chance(mut self, value: f64) -> Self {
+        self.config.door_loop_connection_chance = value;
+        self
+    }
+
+    pub fn max_dead_end_ratio(mut self, value: f64) -> Self {
+        self.config.max_dead_end_ratio = value;
+        self
+    }
+
+    pub fn target_loops(mut self, value: Option<u32>) -> Self {
+        self.config.target_loops = value;
+        self
+    }
+
+    pub fn loop_target_scope(mut self, value: LoopTargetScope) -> Self {
+        self.config.loop_target_scope = value;
+        self
+    }
+
+    pub fn loop_length_bias(mut self, value: f64) -> Self {
+        self.config.loop_length_bias = value;
+        self
+    }
+
+    pub fn repeat_small_room_merge_prob(mut self, value: f64) -> Self {
+        self.config.repeat_small_room_merge_prob = value;
+        self
+    }
+
+    pub fn bisect_room_prob(mut self, value: f64) -> Self {
+        self.config.bisect_room_prob = value;
+        self
+    }
+
+    pub fn room_template_prob(mut self, value: f64) -> Self {
+        self.config.room_template_prob = value;
+        self
+    }
+
+    pub fn lock_tiers(mut self, value: u8) -> Self {
+        self.config.lock_tiers = value;
+        self
+    }
+
+    pub fn abilities(mut self, value: impl IntoIterator<Item = Ability>) -> Self {
+        self.config.abilities = value.into_iter().collect();
+        self
+    }
+
+    pub fn boss_rooms(mut self, value: u8) -> Self {
+        self.config.boss_rooms = value;
+        self
+    }
+
+    pub fn max_save_distance(mut self, value: u32) -> Self {
+        self.config.max_save_distance = value;
+        self
+    }
+
+    pub fn navigation_rooms(mut self, value: u32) -> Self {
+        self.config.navigation_rooms = value;
+        self
+    }
+
+    pub fn warp_rooms(mut self, value: u8) -> Self {
+        self.config.warp_rooms = value;
+        self
+    }
+
+    pub fn one_way_doors(mut self, value: u32) -> Self {
+        self.config.one_way_doors = value;
+        self
+    }
+
+    pub fn secret_rooms(mut self, value: u32) -> Self {
+        self.config.secret_rooms = value;
+        self
+    }
+
+    pub fn floors(mut self, value: u8) -> Self {
+        self.config.floors = value;
+        self
+    }
+
+    pub fn floor_links(mut self, value: u32) -> Self {
+        self.config.floor_links = value;
+        self
+    }
+
+    pub fn inverted_castle(mut self, value: bool) -> Self {
+        self.config.inverted_castle = value;
+        self
+    }
+
+    pub fn dual_castle(mut self, value: bool) -> Self {
+        self.config.dual_castle = value;
+        self
+    }
+
+    pub fn castle_links(mut self, value: u8) -> Self {
+        self.config.castle_links = value;
+        self
+    }
+
+    pub fn build(self) -> Result<MapBuilderConfig> {
+        self.config.validate()?;
+
+        Ok(self.config)
+    }
+}
+
 impl MapBuilderConfig {
+    /// Returns a builder initialized with the default values.
+    pub fn builder() -> MapBuilderConfigBuilder {
+        MapBuilderConfigBuilder::default()
+    }
+
+    /// Returns a builder initialized with the values of this config.
+    pub fn to_builder(self) -> MapBuilderConfigBuilder {
+        MapBuilderConfigBuilder { config: self }
+    }
+
+    /// Checks that every parameter is within its valid range.
+    pub fn validate(&self) -> Result<()> {
+        self.bsp_config.validate()?;
+
+        check_probability("random_room_merge_prob", self.random_room_merge_prob)?;
+        check_probability(
+            "group_loop_connection_chance",
+            self.group_loop_connection_chance,
+        )?;
+        check_probability(
+            "door_loop_connection_chance",
+            self.door_loop_connection_chance,
+        )?;
+        check_probability("max_dead_end_ratio", self.max_dead_end_ratio)?;
+        check_probability("loop_length_bias", self.loop_length_bias)?;
+        check_probability(
+            "repeat_small_room_merge_prob",
+            self.repeat_small_room_merge_prob,
+        )?;
+        check_probability("bisect_room_prob", self.bisect_room_prob)?;
+        check_probability("room_template_prob", self.room_template_prob)?;
+
+        if self.lock_tiers > MAX_LOCK_TIERS {
+            return Err(anyhow::anyhow!(
+                "lock_tiers ({}) must not be greater than {}",
+                self.lock_tiers,
+                MAX_LOCK_TIERS
+            ));
+        }
+
+        if self.boss_rooms > MAX_BOSS_ROOMS {
+            return Err(anyhow::anyhow!(
+                "boss_rooms ({}) must not be greater than {}",
+                self.boss_rooms,
+                MAX_BOSS_ROOMS
+            ));
+        }
+
+        if self.warp_rooms > MAX_WARP_ROOMS {
+            return Err(anyhow::anyhow!(
+                "warp_rooms ({}) must not be greater than {}",
+                self.warp_rooms,
+                MAX_WARP_ROOMS
+            ));
+        }
+
+        if self.floors == 0 || self.floors > MAX_FLOORS {
+            return Err(anyhow::anyhow!(
+                "floors ({}) must be between 1 and {}",
+                self.floors,
+                MAX_FLOORS
+            ));
+        }
+
+        if self.inverted_castle && self.floors > 1 {
+            return Err(anyhow::anyhow!(
+                "inverted_castle cannot be combined with more than one floor"
+            ));
+        }
+
+        if self.dual_castle {
+            if self.inverted_castle || self.floors > 1 {
+                return Err(anyhow::anyhow!(
+                    "dual_castle cannot be combined with inverted_castle or more than one floor"
+                ));
+            }
+
+            if self.castle_links == 0 || self.castle_links > MAX_CASTLE_LINKS {
+                return Err(anyhow::anyhow!(
+                    "castle_links ({}) must be between 1 and {}",
+                    self.castle_links,
+                    MAX_CASTLE_LINKS
+                ));
+            }
+
+            // The castles are tied together through the pickups of the gates
+            if self.lock_tiers == 0 && self.abilities.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "dual_castle needs at least one lock tier or ability"
+                ));
+            }
+        }
+
+        if self.max_save_distance == 0 {
+            return Err(anyhow::anyhow!(
+                "max_save_distance must be greater than zero"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Layers the values found in the overrides document on top of this config.
+    ///
+    /// Only the given keys are replaced, e.g. `bisect_room_prob = 0.3` or
+    /// `[bsp_config]` followed by `rect_survival_prob = 0.2` in TOML.
+    pub fn with_overrides(self, overrides: &str, format: ConfigFormat) -> Result<Self> {
+        let overrides: serde_json::Value = match format {
+            ConfigFormat::Json => serde_json::from_str(overrides)?,
+            ConfigFormat::Toml => toml::from_str(overrides)?,
+        };
+
+        let mut merged = serde_json::to_value(self)?;
+        merge_values(&mut merged, overrides);
+
+        let config: MapBuilderConfig = serde_json::from_value(merged)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Loads the preset of the given style and layers the overrides file on top of it.
+    ///
+    /// The format of the file is taken from its extension, `.json` or `.toml`.
+    pub fn from_style_with_overrides(style: MapStyle, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)?;
+        let overrides = std::fs::read_to_string(path)?;
+
+        Self::from_style(style).with_overrides(&overrides, format)
+    }
+
     pub fn from_style(style: MapStyle) -> Self {
         let mut base = Self::default();
 
@@ -100,6 +656,13 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.22;
                 base.repeat_small_room_merge_prob = 0.51;
                 base.bisect_room_prob = 0.17;
+                base.room_template_prob = 0.35;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([Ability::DoubleJump, Ability::HighJump]);
+                base.boss_rooms = 1;
+                base.warp_rooms = 5;
+                base.one_way_doors = 2;
+                base.secret_rooms = 2;
             }
             MapStyle::CastlevaniaAOS => {
                 base.bsp_config.horizontal_region_prob = 0.0;
@@ -117,6 +680,13 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.24;
                 base.repeat_small_room_merge_prob = 0.45;
                 base.bisect_room_prob = 0.15;
+                base.room_template_prob = 0.3;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([Ability::DoubleJump, Ability::HighJump]);
+                base.boss_rooms = 1;
+                base.warp_rooms = 4;
+                base.one_way_doors = 2;
+                base.secret_rooms = 2;
             }
             MapStyle::CastlevaniaCOTM => {
                 base.bsp_config.horizontal_region_prob = 0.1;
@@ -134,6 +704,12 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.14;
                 base.repeat_small_room_merge_prob = 0.85;
                 base.bisect_room_prob = 0.29;
+                base.room_template_prob = 0.2;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([Ability::DoubleJump, Ability::HighJump]);
+                base.boss_rooms = 1;
+                base.one_way_doors = 2;
+                base.secret_rooms = 2;
             }
             MapStyle::CastlevaniaHOD => {
                 base.bsp_config.horizontal_region_prob = 0.75;
@@ -151,6 +727,13 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.22;
                 base.repeat_small_room_merge_prob = 0.81;
                 base.bisect_room_prob = 0.17;
+                base.room_template_prob = 0.3;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([Ability::DoubleJump, Ability::HighJump]);
+                base.boss_rooms = 1;
+                base.warp_rooms = 4;
+                base.one_way_doors = 2;
+                base.secret_rooms = 2;
             }
             MapStyle::MetroidZM => {
                 base.bsp_config.region_split_factor =
@@ -172,6 +755,12 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.22;
                 base.repeat_small_room_merge_prob = 0.51;
                 base.bisect_room_prob = 0.17;
+                base.room_template_prob = 0.4;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([Ability::MorphBall, Ability::HighJump]);
+                base.boss_rooms = 2;
+                base.one_way_doors = 4;
+                base.secret_rooms = 2;
             }
             MapStyle::MetroidFS => {
                 base.bsp_config.region_split_factor =
@@ -193,6 +782,16 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.22;
                 base.repeat_small_room_merge_prob = 0.51;
                 base.bisect_room_prob = 0.17;
+                base.room_template_prob = 0.4;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([
+                    Ability::MorphBall,
+                    Ability::HighJump,
+                    Ability::WaterSuit,
+                ]);
+                base.boss_rooms = 1;
+                base.one_way_doors = 4;
+                base.secret_rooms = 2;
             }
             MapStyle::MetroidSP => {
                 base.bsp_config.region_split_factor =
@@ -214,9 +813,164 @@ impl MapBuilderConfig {
                 base.door_loop_connection_chance = 0.22;
                 base.repeat_small_room_merge_prob = 0.51;
                 base.bisect_room_prob = 0.17;
+                base.room_template_prob = 0.5;
+                base.lock_tiers = 2;
+                base.abilities = AbilitySet::from_iter([
+                    Ability::MorphBall,
+                    Ability::HighJump,
+                    Ability::WaterSuit,
+                    Ability::GrappleBeam,
+                ]);
+                base.boss_rooms = 4;
+                base.one_way_doors = 4;
+                base.secret_rooms = 2;
             }
         }
 
         base
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_style_presets_are_valid() {
+        for style in [
+            MapStyle::CastlevaniaSOTN,
+            MapStyle::CastlevaniaAOS,
+            MapStyle::CastlevaniaCOTM,
+            MapStyle::CastlevaniaHOD,
+            MapStyle::MetroidZM,
+            MapStyle::MetroidFS,
+            MapStyle::MetroidSP,
+        ] {
+            assert!(MapBuilderConfig::from_style(style).validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_builder_validates_ranges() {
+        let config = MapBuilderConfig::from_style(MapStyle::CastlevaniaSOTN)
+            .to_builder()
+            .door_loop_connection_chance(0.5)
+            .bsp_config(|bsp| bsp.rect_survival_prob(0.25))
+            .build()
+            .unwrap();
+
+        assert_eq!(config.door_loop_connection_chance, 0.5);
+        assert_eq!(config.bsp_config.rect_survival_prob, 0.25);
+
+        assert!(
+            MapBuilderConfig::builder()
+                .bisect_room_prob(1.5)
+                .build()
+                .is_err()
+        );
+        assert!(
+            MapBuilderConfig::builder()
+                .max_dead_end_ratio(-0.5)
+                .build()
+                .is_err()
+        );
+        assert!(
+            MapBuilderConfig::builder()
+                .target_loops(Some(4))
+                .loop_length_bias(2.0)
+                .build()
+                .is_err()
+        );
+        assert!(
+            MapBuilderConfig::builder()
+                .bsp_config(|bsp| bsp.rect_survival_prob(-0.1))
+                .build()
+                .is_err()
+        );
+        assert!(
+            MapBuilderConfig::builder()
+                .bsp_config(|bsp| bsp.rect_area_cutoff(10).big_rect_area_cutoff(5))
+                .build()
+                .is_err()
+        );
+        assert!(
+            BinarySpacePartitioningConfig::default()
+                .to_builder()
+                .height_factor_cutoff(0.5)
+                .build()
+                .is_err()
+        );
+        assert!(MapBuilderConfig::builder().floors(0).build().is_err());
+        assert!(
+            MapBuilderConfig::builder()
+                .floors(2)
+                .inverted_castle(true)
+                .build()
+                .is_err()
+        );
+        assert!(
+            MapBuilderConfig::builder()
+                .dual_castle(true)
+                .lock_tiers(0)
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_overrides_are_layered_on_preset() {
+        let preset = MapBuilderConfig::from_style(MapStyle::MetroidZM);
+
+        let toml_config = preset
+            .with_overrides(
+                "bisect_room_prob = 0.3\n[bsp_config]\nrect_survival_prob = 0.2\n",
+                ConfigFormat::Toml,
+            )
+            .unwrap();
+        let json_config = preset
+            .with_overrides(
+                r#"{"bisect_room_prob": 0.3, "bsp_config": {"rect_survival_prob": 0.2}}"#,
+                ConfigFormat::Json,
+            )
+            .unwrap();
+
+        assert_eq!(toml_config, json_config);
+        assert_eq!(toml_config.bisect_room_prob, 0.3);
+        assert_eq!(toml_config.bsp_config.rect_survival_prob, 0.2);
+        assert_eq!(
+            toml_config.door_loop_connection_chance,
+            preset.door_loop_connection_chance
+        );
+        assert_eq!(
+            toml_config.bsp_config.region_split_factor,
+            preset.bsp_config.region_split_factor
+        );
+        assert!(!toml_config.merge_regions);
+    }
+
+    #[test]
+    fn test_overrides_are_rejected_when_invalid() {
+        let preset = MapBuilderConfig::default();
+
+        assert!(
+            preset
+                .with_overrides("bisect_room_porb = 0.3", ConfigFormat::Toml)
+                .is_err()
+        );
+        assert!(
+            preset
+                .with_overrides(r#"{"bisect_room_prob": 2.0}"#, ConfigFormat::Json)
+                .is_err()
+        );
+        assert!(
+            preset
+                .with_overrides("lock_tiers = 9", ConfigFormat::Toml)
+                .is_err()
+        );
+        assert!(ConfigFormat::from_path("config.yaml").is_err());
+        assert_eq!(
+            ConfigFormat::from_path("config.TOML").unwrap(),
+            ConfigFormat::Toml
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use super::MapBuilder;
-use crate::types::{Map, RoomModifier};
+use crate::types::{Map, Room, RoomModifier};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 
 impl MapBuilder {
     pub(super) fn connect_regions(regions: &mut [Map]) {
-        let mut region_map = regions.iter_mut().enumerate().collect::<HashMap<_, _>>();
+        let mut region_map = regions.iter_mut().enumerate().collect::<BTreeMap<_, _>>();
 
-        let mut regions_to_visit = vec![region_map.keys().cloned().next().unwrap()];
+        let Some(first_region) = region_map.keys().next().copied() else {
+            return;
+        };
+
+        let mut regions_to_visit = vec![first_region];
 
         let mut expanded_regions = HashSet::new();
         expanded_regions.insert(regions_to_visit[0]);
@@ -44,7 +48,7 @@ impl MapBuilder {
     fn link_closest_rooms(
         from_region_id: usize,
         to_region_id: usize,
-        region_map: &mut HashMap<usize, &mut Map>,
+        region_map: &mut BTreeMap<usize, &mut Map>,
     ) {
         let from_room_map = region_map
             .get(&from_region_id)
@@ -52,7 +56,7 @@ impl MapBuilder {
             .rooms
             .iter()
             .enumerate()
-            .collect::<HashMap<_, _>>();
+            .collect::<BTreeMap<_, _>>();
 
         let to_room_map = region_map
             .get(&to_region_id)
@@ -60,10 +64,11 @@ impl MapBuilder {
             .rooms
             .iter()
             .enumerate()
-            .collect::<HashMap<_, _>>();
+            .collect::<BTreeMap<_, _>>();
 
-        let mut closest_distance = f32::MAX;
-        let mut closest_rooms = (0_usize, 0_usize);
+        // Rooms that were already decorated are only used when there is no other choice
+        let mut closest_candidate = (true, f32::MAX);
+        let mut maybe_closest_rooms = None;
 
         let from_axis = {
             let from_region = &region_map[&from_region_id];
@@ -75,7 +80,20 @@ impl MapBuilder {
         };
         let to_axis = from_axis.reverse();
 
+        // Rooms already linked to another region keep their connection, secret
+        // rooms must stay dead ends and pinned rooms keep their modifier
+        let cannot_be_linked = |room: &Room| {
+            matches!(
+                room.modifier,
+                Some(RoomModifier::RegionConnection(_) | RoomModifier::Secret)
+            ) || (room.pinned && room.modifier.is_some())
+        };
+
         for (from_room_id, from_room) in from_room_map.iter() {
+            if cannot_be_linked(from_room) {
+                continue;
+            }
+
             if from_room_map
                 .values()
                 .any(|room| match from_room.get_neighbouring_cells_for(room) {
@@ -90,6 +108,10 @@ impl MapBuilder {
 
             let from_room_center = from_room.get_center();
             for (to_room_id, to_room) in to_room_map.iter() {
+                if cannot_be_linked(to_room) {
+                    continue;
+                }
+
                 if to_room_map
                     .values()
                     .any(|room| match to_room.get_neighbouring_cells_for(room) {
@@ -104,14 +126,19 @@ impl MapBuilder {
 
                 let to_room_center = to_room.get_center();
                 let distance = from_room_center.distance(&to_room_center);
+                let is_decorated = from_room.modifier.is_some() || to_room.modifier.is_some();
 
-                if distance < closest_distance {
-                    closest_distance = distance;
-                    closest_rooms = (*from_room_id, *to_room_id);
+                if (is_decorated, distance) < closest_candidate {
+                    closest_candidate = (is_decorated, distance);
+                    maybe_closest_rooms = Some((*from_room_id, *to_room_id));
                 }
             }
         }
 
+        let Some(closest_rooms) = maybe_closest_rooms else {
+            return;
+        };
+
         region_map
             .get_mut(&from_region_id)
             .unwrap()
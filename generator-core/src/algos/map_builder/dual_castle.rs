@@ -0,0 +1,312 @@
+use super::{
+    BuildPipeline, CASTLE_LINKS_STREAM, DUAL_CASTLE_STREAM, GenerationReport, MapBuilder,
+    MapBuilderConfig, PROGRESSION_STREAM,
+};
+use crate::{
+    GenerateError, MapStyle,
+    algos::{RngHandler, room_graph::cell_owners},
+    constants::MAX_DUAL_CASTLE_ATTEMPTS,
+    types::{Cell, Map, Rect, RoomModifier},
+};
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, seq::SliceRandom};
+
+impl MapBuilder {
+    // Builds two different castles over the same canvas, returning the maps of both.
+    //
+    // The castles share warp rooms at the same cells, which are the only way into
+    // the second castle. Progression is placed over both castles at once, and the
+    // first gate always closes part of the first castle with its pickup inside
+    // the second one, so the map can not be completed without going there. The
+    // second castle is built again from another stream when no such gate fits.
+    pub fn build_dual_castle(
+        &self,
+        config: &MapBuilderConfig,
+        pipeline: &BuildPipeline,
+        style: MapStyle,
+        seed: u64,
+    ) -> Result<([Vec<Map>; 2], GenerationReport), GenerateError> {
+        let build_start = std::time::Instant::now();
+
+        let rng_handler = RngHandler::new(seed);
+        let dual_castle_rng = rng_handler.derive(DUAL_CASTLE_STREAM);
+
+        let (mut castle_a, mut report) = self.build(config, pipeline, style, seed)?;
+        castle_a.iter_mut().for_each(Self::clear_progression);
+
+        for attempt in 0..MAX_DUAL_CASTLE_ATTEMPTS {
+            let attempt_start = std::time::Instant::now();
+            let attempt_rng = dual_castle_rng.derive(attempt);
+
+            let (castle_b, castle_b_report) =
+                self.build(config, pipeline, style, attempt_rng.rng().random())?;
+
+            let dual_castle_start = std::time::Instant::now();
+            let linked_castles = self.link_castles(castle_a.clone(), castle_b, config, attempt_rng);
+
+            // The time spent on the second castles that are thrown away is kept apart
+            let Some((castles, castle_links, (ability_doors, locked_doors))) = linked_castles
+            else {
+                report.record_duration("discarded_castle_attempts", attempt_start.elapsed());
+                continue;
+            };
+
+            report.record_duration("add_dual_castle", dual_castle_start.elapsed());
+
+            report.merge_from(castle_b_report);
+            report.castle_links = castle_links;
+            report.ability_doors = ability_doors;
+            report.locked_doors = locked_doors;
+
+            report.total_duration = build_start.elapsed();
+
+            return Ok((castles, report));
+        }
+
+        Err(GenerateError::UnlinkedCastles)
+    }
+
+    // Links both castles and places the progression over them, returning the maps
+    // of both castles along with the number of castle links and of gated doors.
+    //
+    // Returns `None` when no gate of the first castle could get its pickup
+    // inside the second one.
+    #[allow(clippy::type_complexity)]
+    fn link_castles(
+        &self,
+        mut castle_a: Vec<Map>,
+        mut castle_b: Vec<Map>,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> Option<([Vec<Map>; 2], usize, (usize, usize))> {
+        castle_b.iter_mut().for_each(Self::clear_progression);
+
+        for room in castle_b.iter_mut().flat_map(|map| map.rooms.iter_mut()) {
+            if room.modifier == Some(RoomModifier::Start) {
+                room.modifier = None;
+            }
+        }
+
+        let castle_links = Self::add_castle_links(
+            &mut castle_a,
+            &mut castle_b,
+            config,
+            rng_handler.derive(CASTLE_LINKS_STREAM),
+        );
+
+        // The second castle is moved away from the first one while placing the
+        // progression, so the cells of both castles do not overlap
+        let col_offset = self.cols as i64 + 1;
+        let castle_a_maps = castle_a.len();
+
+        let mut maps = castle_a;
+        maps.extend(castle_b.into_iter().map(|map| shift_map(map, col_offset)));
+
+        let gated_doors = Self::add_progression_with(
+            &mut maps,
+            config,
+            rng_handler.derive(PROGRESSION_STREAM),
+            |gated_area, pickup_room| {
+                gated_area.iter().all(|room| room.region < castle_a_maps)
+                    && pickup_room.region >= castle_a_maps
+            },
+        );
+
+        // No gate is placed at all when the first one does not fit
+        if gated_doors == (0, 0) {
+            return None;
+        }
+
+        let castle_b = maps
+            .split_off(castle_a_maps)
+            .into_iter()
+            .map(|map| shift_map(map, -col_offset))
+            .collect();
+
+        Some(([maps, castle_b], castle_links, gated_doors))
+    }
+
+    // Turns up to `config.castle_links` pairs of rooms, one holding the same cell
+    // in each castle, into castle links, returning how many were placed.
+    //
+    // Single cell rooms are preferred, and every link is placed as far as possible
+    // from the other ones.
+    fn add_castle_links(
+        castle_a: &mut [Map],
+        castle_b: &mut [Map],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        let mut rng = rng_handler.rng();
+
+        // Sorted by cell, so the candidates do not depend on the hashing order
+        let castle_b_owners = cell_owners(castle_b);
+        let shared_cells = cell_owners(castle_a)
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .filter_map(|(cell, room_a)| {
+                castle_b_owners
+                    .get(&cell)
+                    .map(|room_b| (cell, room_a, *room_b))
+            })
+            .collect::<Vec<_>>();
+
+        let (mut single_cell, mut multi_cell): (Vec<_>, Vec<_>) =
+            shared_cells.into_iter().partition(|(_, room_a, room_b)| {
+                castle_a[room_a.region].rooms[room_a.room].cells.len() == 1
+                    && castle_b[room_b.region].rooms[room_b.room].cells.len() == 1
+            });
+
+        single_cell.shuffle(&mut rng);
+        multi_cell.shuffle(&mut rng);
+
+        let mut linked_cells: Vec<Cell> = Vec::new();
+
+        for candidates in [single_cell, multi_cell] {
+            while linked_cells.len() < config.castle_links as usize {
+                let best_candidate = candidates
+                    .iter()
+                    .copied()
+                    .filter(|(_, room_a, room_b)| {
+                        castle_a[room_a.region].rooms[room_a.room]
+                            .modifier
+                            .is_none()
+                            && castle_b[room_b.region].rooms[room_b.room]
+                                .modifier
+                                .is_none()
+                    })
+                    .max_by_key(|(cell, _, _)| {
+                        linked_cells
+                            .iter()
+                            .map(|linked_cell| cell.distance(linked_cell))
+                            .min()
+                            .unwrap_or(0)
+                    });
+
+                let Some((cell, room_a, room_b)) = best_candidate else {
+                    break;
+                };
+
+                let link = Some(RoomModifier::CastleLink(linked_cells.len() as u8));
+                castle_a[room_a.region].rooms[room_a.room].modifier = link;
+                castle_b[room_b.region].rooms[room_b.room].modifier = link;
+
+                linked_cells.push(cell);
+            }
+        }
+
+        linked_cells.len()
+    }
+}
+
+// Moves every cell of the map by the given number of columns.
+fn shift_map(mut map: Map, col_offset: i64) -> Map {
+    let shift_cell = |cell: Cell| Cell::new((cell.col as i64 + col_offset) as u32, cell.row);
+    let shift_rect = |rect: Rect| Rect {
+        origin: shift_cell(rect.origin),
+        ..rect
+    };
+
+    map.origin_rect = shift_rect(map.origin_rect);
+
+    for cell in map.rooms.iter_mut().flat_map(|room| room.cells.iter_mut()) {
+        *cell = shift_cell(*cell);
+    }
+
+    for door in map.doors.iter_mut() {
+        door.from = shift_cell(door.from);
+        door.to = shift_cell(door.to);
+    }
+
+    for theme in map.themes.iter_mut() {
+        theme.rect = shift_rect(theme.rect);
+    }
+
+    map
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algos::progression::solve_regions, constants::TEST_SEED};
+
+    #[test]
+    fn test_dual_castle_needs_both_castles() {
+        let map_builder = MapBuilder::new(48, 32).unwrap();
+        let config = MapBuilderConfig::builder()
+            .dual_castle(true)
+            .castle_links(3)
+            .lock_tiers(2)
+            .build()
+            .unwrap();
+
+        let ([castle_a, castle_b], report) = map_builder
+            .build_dual_castle(
+                &config,
+                &BuildPipeline::default(),
+                MapStyle::CastlevaniaHOD,
+                TEST_SEED,
+            )
+            .unwrap();
+
+        assert_eq!(report.castle_links, 3);
+
+        let link_rooms = |maps: &[Map]| {
+            maps.iter()
+                .flat_map(|map| map.rooms.iter())
+                .filter_map(|room| match room.modifier {
+                    Some(RoomModifier::CastleLink(link)) => Some((link, room.cells.clone())),
+                    _ => None,
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let castle_b_links = link_rooms(&castle_b);
+
+        // Both rooms of every link hold the same cell
+        for (link, cells) in link_rooms(&castle_a) {
+            assert!(
+                cells
+                    .iter()
+                    .any(|cell| castle_b_links[&link].contains(cell))
+            );
+        }
+
+        // The first castle misses some pickups when played on its own
+        let solution = solve_regions(&castle_a).unwrap();
+        assert!(!solution.is_completable());
+
+        let mut maps = castle_a;
+        maps.extend(castle_b.into_iter().map(|map| shift_map(map, 49)));
+
+        let solution = solve_regions(&maps).unwrap();
+        assert!(solution.is_completable());
+    }
+
+    #[test]
+    fn test_dual_castle_builds_the_second_castle_again() {
+        // The first second castles of this seed leave no room for a gate tying
+        // both castles together
+        let map_builder = MapBuilder::new(16, 12).unwrap();
+        let config = MapBuilderConfig::from_style(MapStyle::CastlevaniaHOD)
+            .to_builder()
+            .dual_castle(true)
+            .build()
+            .unwrap();
+
+        let ([castle_a, _], report) = map_builder
+            .build_dual_castle(
+                &config,
+                &BuildPipeline::default(),
+                MapStyle::CastlevaniaHOD,
+                2,
+            )
+            .unwrap();
+
+        assert!(report.ability_doors + report.locked_doors > 0);
+        assert!(!solve_regions(&castle_a).unwrap().is_completable());
+    }
+}
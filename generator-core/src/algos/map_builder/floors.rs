@@ -0,0 +1,248 @@
+use super::{
+    BuildPipeline, FLOOR_LINKS_STREAM, FLOORS_STREAM, GenerationReport, MapBuilder,
+    MapBuilderConfig,
+};
+use crate::{
+    GenerateError, MapStyle,
+    algos::{RngHandler, room_graph::cell_owners},
+    types::{Cell, FloorLink, FloorLinkKind, Map, Room, RoomModifier, RoomRef},
+};
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, seq::SliceRandom};
+
+impl FloorLinkKind {
+    fn modifier(&self) -> RoomModifier {
+        match self {
+            FloorLinkKind::Stairs => RoomModifier::Stairs,
+            FloorLinkKind::Elevator => RoomModifier::Elevator,
+        }
+    }
+}
+
+impl MapBuilder {
+    // Builds `config.floors` layouts over the same grid, from the lowest floor up,
+    // and links every pair of consecutive floors through stairs or elevators.
+    //
+    // The lowest floor is built with `seed` itself, so it is the same layout
+    // a single floor map gets for that seed.
+    #[allow(clippy::type_complexity)]
+    pub fn build_floors(
+        &self,
+        config: &MapBuilderConfig,
+        pipeline: &BuildPipeline,
+        style: MapStyle,
+        seed: u64,
+    ) -> Result<(Vec<(u64, Vec<Map>)>, Vec<FloorLink>, GenerationReport), GenerateError> {
+        let build_start = std::time::Instant::now();
+
+        let rng_handler = RngHandler::new(seed);
+        let floors_rng = rng_handler.derive(FLOORS_STREAM);
+
+        let mut report = GenerationReport::new(seed);
+
+        let mut floors = Vec::with_capacity(config.floors as usize);
+
+        for floor in 0..config.floors as u64 {
+            let floor_seed = match floor {
+                0 => seed,
+                _ => floors_rng.derive(floor).rng().random(),
+            };
+
+            let (maps, floor_report) = self.build(config, pipeline, style, floor_seed)?;
+            report.merge_from(floor_report);
+
+            floors.push((floor_seed, maps));
+        }
+
+        let links_start = std::time::Instant::now();
+
+        let mut floor_maps = floors.iter_mut().map(|(_, maps)| maps).collect::<Vec<_>>();
+        let links = Self::add_floor_links(
+            &mut floor_maps,
+            config,
+            rng_handler.derive(FLOOR_LINKS_STREAM),
+        );
+        report.floor_links = links.len();
+
+        report.record_duration("add_floor_links", links_start.elapsed());
+        report.total_duration = build_start.elapsed();
+
+        Ok((floors, links, report))
+    }
+
+    // Places up to `config.floor_links` stairs or elevators between every pair of
+    // consecutive floors, in cells taken by a room on both of them.
+    //
+    // Single cell rooms are preferred, and every link is placed as far as possible
+    // from the other ones between the same floors. An elevator reaching a floor
+    // can keep going up from it, as long as the room above is free.
+    fn add_floor_links(
+        floors: &mut [&mut Vec<Map>],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> Vec<FloorLink> {
+        let mut rng = rng_handler.rng();
+
+        let mut links: Vec<FloorLink> = Vec::new();
+
+        for upper_floor in 1..floors.len() {
+            let lower_floor = upper_floor - 1;
+
+            // Sorted by cell, so the candidates do not depend on the hashing order
+            let lower_owners = cell_owners(floors[lower_floor])
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            let upper_owners = cell_owners(floors[upper_floor]);
+
+            let shared_cells = lower_owners
+                .into_iter()
+                .filter_map(|(cell, lower_ref)| {
+                    upper_owners
+                        .get(&cell)
+                        .map(|upper_ref| (cell, lower_ref, *upper_ref))
+                })
+                .collect::<Vec<_>>();
+
+            let (mut single_cell, mut multi_cell): (Vec<_>, Vec<_>) = shared_cells
+                .into_iter()
+                .partition(|(_, lower_ref, upper_ref)| {
+                    room_at(floors[lower_floor], *lower_ref).cells.len() == 1
+                        && room_at(floors[upper_floor], *upper_ref).cells.len() == 1
+                });
+
+            single_cell.shuffle(&mut rng);
+            multi_cell.shuffle(&mut rng);
+
+            let mut linked_cells: Vec<Cell> = Vec::new();
+
+            for candidates in [single_cell, multi_cell] {
+                while linked_cells.len() < config.floor_links as usize {
+                    let best_candidate = candidates
+                        .iter()
+                        .copied()
+                        .filter(|(cell, lower_ref, upper_ref)| {
+                            let is_lower_free =
+                                match room_at(floors[lower_floor], *lower_ref).modifier {
+                                    None => true,
+                                    // Only the shaft of the elevator can go further up
+                                    Some(RoomModifier::Elevator) => links.iter().any(|link| {
+                                        link.kind == FloorLinkKind::Elevator
+                                            && link.cell == *cell
+                                            && link.upper_floor == lower_floor
+                                    }),
+                                    _ => false,
+                                };
+
+                            is_lower_free
+                                && room_at(floors[upper_floor], *upper_ref).modifier.is_none()
+                        })
+                        .max_by_key(|(cell, _, _)| {
+                            linked_cells
+                                .iter()
+                                .map(|linked_cell| cell.distance(linked_cell))
+                                .min()
+                                .unwrap_or(0)
+                        });
+
+                    let Some((cell, lower_ref, upper_ref)) = best_candidate else {
+                        break;
+                    };
+
+                    let extended_link = links.iter_mut().find(|link| {
+                        link.kind == FloorLinkKind::Elevator
+                            && link.cell == cell
+                            && link.upper_floor == lower_floor
+                    });
+
+                    let kind = match extended_link {
+                        Some(link) => {
+                            link.upper_floor = upper_floor;
+
+                            FloorLinkKind::Elevator
+                        }
+                        None => {
+                            let kind = if rng.random_bool(0.5) {
+                                FloorLinkKind::Stairs
+                            } else {
+                                FloorLinkKind::Elevator
+                            };
+
+                            links.push(FloorLink {
+                                kind,
+                                cell,
+                                lower_floor,
+                                upper_floor,
+                            });
+
+                            kind
+                        }
+                    };
+
+                    floors[lower_floor][lower_ref.region].rooms[lower_ref.room].modifier =
+                        Some(kind.modifier());
+                    floors[upper_floor][upper_ref.region].rooms[upper_ref.room].modifier =
+                        Some(kind.modifier());
+
+                    linked_cells.push(cell);
+                }
+            }
+        }
+
+        links
+    }
+}
+
+fn room_at(maps: &[Map], room_ref: RoomRef) -> &Room {
+    &maps[room_ref.region].rooms[room_ref.room]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algos::map_builder::test_maps::single_cell_maps, constants::TEST_SEED};
+
+    #[test]
+    fn test_add_floor_links_line_up() {
+        let cells = [Cell::new(0, 0), Cell::new(3, 3), Cell::new(1, 2)];
+
+        let mut floors = (0..3).map(|_| single_cell_maps(&cells)).collect::<Vec<_>>();
+        let mut floor_maps = floors.iter_mut().collect::<Vec<_>>();
+
+        let config = MapBuilderConfig::builder()
+            .floors(3)
+            .floor_links(2)
+            .build()
+            .unwrap();
+
+        let links =
+            MapBuilder::add_floor_links(&mut floor_maps, &config, RngHandler::new(TEST_SEED));
+
+        assert!(!links.is_empty());
+
+        for link in links.iter() {
+            assert!(link.lower_floor < link.upper_floor);
+
+            for floor in floors[link.lower_floor..=link.upper_floor].iter() {
+                let room = floor
+                    .iter()
+                    .flat_map(|map| map.rooms.iter())
+                    .find(|room| room.cells.contains(&link.cell))
+                    .unwrap();
+
+                assert_eq!(room.modifier, Some(link.kind.modifier()));
+            }
+        }
+
+        // The first two links are placed in the farthest apart cells
+        let first_links = links
+            .iter()
+            .filter(|link| link.lower_floor == 0)
+            .map(|link| link.cell)
+            .collect::<Vec<_>>();
+
+        assert_eq!(first_links.len(), 2);
+        assert!(first_links.contains(&Cell::new(0, 0)) || first_links.contains(&Cell::new(3, 3)));
+    }
+}
@@ -0,0 +1,211 @@
+use super::{
+    BuildPipeline, DECORATOR_STREAM, GenerationReport, INVERTED_CASTLE_STREAM, MapBuilder,
+    MapBuilderConfig, ONE_WAY_DOORS_STREAM, PROGRESSION_STREAM, room_decorator,
+};
+use crate::{
+    GenerateError, MapStyle,
+    algos::{
+        RngHandler,
+        room_graph::{RoomGraph, start_room},
+    },
+    types::{Cell, DoorModifier, Map, MapRegion, Rect, RoomModifier, RoomRef},
+};
+
+impl MapBuilder {
+    // Builds a castle along with its inverted counterpart, the same castle turned
+    // 180 degrees, returning the maps of both.
+    //
+    // The inverted castle keeps the rooms, doors and landmarks of the castle, but
+    // its save, navigation and item rooms are placed again. Its one-way doors are
+    // placed again too, so its drops still lead downwards. The room of the castle
+    // furthest from its start links both castles together.
+    #[allow(clippy::type_complexity)]
+    pub fn build_inverted_castle(
+        &self,
+        config: &MapBuilderConfig,
+        pipeline: &BuildPipeline,
+        style: MapStyle,
+        seed: u64,
+    ) -> Result<(Vec<Map>, Vec<Map>, GenerationReport), GenerateError> {
+        let (mut castle, mut report) = self.build(config, pipeline, style, seed)?;
+
+        let invert_start = std::time::Instant::now();
+
+        let rng_handler = RngHandler::new(seed).derive(INVERTED_CASTLE_STREAM);
+
+        if Self::add_castle_link(&mut castle).is_some() {
+            report.castle_links = 1;
+        }
+
+        let mut inverted = castle
+            .iter()
+            .enumerate()
+            .map(|(map_idx, map)| {
+                let mut map = self.rotate_map(map);
+                Self::clear_decorations(&mut map);
+
+                let (mut map_region, doors) = MapRegion::from_map(map);
+
+                room_decorator::RoomDecoratorFactory::decorator_for(style).decorate(
+                    &mut map_region,
+                    &doors,
+                    config,
+                    rng_handler.derive(map_idx as u64).derive(DECORATOR_STREAM),
+                );
+
+                map_region.into_map(doors)
+            })
+            .collect::<Vec<_>>();
+
+        // The counters cover the doors placed again in the inverted castle too
+        report.one_way_doors += Self::add_one_way_doors(
+            &mut inverted,
+            config,
+            rng_handler.derive(ONE_WAY_DOORS_STREAM),
+        );
+
+        let (ability_doors, locked_doors) = Self::add_progression(
+            &mut inverted,
+            config,
+            rng_handler.derive(PROGRESSION_STREAM),
+        );
+        report.ability_doors += ability_doors;
+        report.locked_doors += locked_doors;
+
+        let invert_duration = invert_start.elapsed();
+        report.record_duration("invert_castle", invert_duration);
+        report.total_duration += invert_duration;
+
+        Ok((castle, inverted, report))
+    }
+
+    // Marks the free room furthest from the start as the link to the other castle,
+    // preferring single cell rooms.
+    fn add_castle_link(maps: &mut [Map]) -> Option<RoomRef> {
+        let start = start_room(maps)?;
+
+        let (link, _) = RoomGraph::new(maps)
+            .distances_from(start, |_| true)
+            .into_iter()
+            .filter(|(room, _)| maps[room.region].rooms[room.room].modifier.is_none())
+            .max_by_key(|(room, distance)| {
+                (
+                    maps[room.region].rooms[room.room].cells.len() == 1,
+                    *distance,
+                )
+            })?;
+
+        maps[link.region].rooms[link.room].modifier = Some(RoomModifier::CastleLink(0));
+
+        Some(link)
+    }
+
+    // Removes the save, navigation and item rooms of a map, along with the locks
+    // and ability requirements of its doors.
+    fn clear_decorations(map: &mut Map) {
+        for room in map.rooms.iter_mut() {
+            if matches!(
+                room.modifier,
+                Some(RoomModifier::Save | RoomModifier::Navigation | RoomModifier::Item(_))
+            ) {
+                room.modifier = None;
+            }
+        }
+
+        Self::clear_progression(map);
+    }
+
+    // Removes the keys and abilities of a map, along with the doors they open.
+    pub(super) fn clear_progression(map: &mut Map) {
+        for room in map.rooms.iter_mut() {
+            if matches!(
+                room.modifier,
+                Some(RoomModifier::Key(_) | RoomModifier::Item(Some(_)))
+            ) {
+                room.modifier = None;
+            }
+        }
+
+        for door in map.doors.iter_mut() {
+            door.requirement = None;
+
+            if let DoorModifier::Locked(_) = door.modifier {
+                door.modifier = DoorModifier::Open;
+            }
+        }
+    }
+
+    fn rotate_cell(&self, cell: Cell) -> Cell {
+        Cell::new(self.cols - 1 - cell.col, self.rows - 1 - cell.row)
+    }
+
+    fn rotate_rect(&self, rect: Rect) -> Rect {
+        Rect::new(
+            self.cols - rect.origin.col - rect.width,
+            self.rows - rect.origin.row - rect.height,
+            rect.width,
+            rect.height,
+        )
+    }
+
+    // Turns the map 180 degrees around the center of the canvas.
+    fn rotate_map(&self, map: &Map) -> Map {
+        let mut rotated = map.clone();
+
+        rotated.origin_rect = self.rotate_rect(map.origin_rect);
+
+        for room in rotated.rooms.iter_mut() {
+            for cell in room.cells.iter_mut() {
+                *cell = self.rotate_cell(*cell);
+            }
+
+            if let Some(RoomModifier::RegionConnection(direction)) = room.modifier {
+                room.modifier = Some(RoomModifier::RegionConnection(direction.reverse()));
+            }
+        }
+
+        // The drops of one-way doors would lead upwards once turned
+        for door in rotated.doors.iter_mut() {
+            door.from = self.rotate_cell(door.from);
+            door.to = self.rotate_cell(door.to);
+            door.one_way = false;
+        }
+
+        for theme in rotated.themes.iter_mut() {
+            theme.rect = self.rotate_rect(theme.rect);
+        }
+
+        rotated
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algos::map_builder::test_maps::corridor_maps;
+
+    #[test]
+    fn test_rotate_map_turns_around_the_canvas() {
+        let map_builder = MapBuilder {
+            cols: 8,
+            rows: 6,
+            mask: None,
+            pinned_rooms: Vec::new(),
+        };
+
+        let mut maps = corridor_maps(8, 1);
+        maps[0].rooms[0].modifier = Some(RoomModifier::Start);
+
+        let link = MapBuilder::add_castle_link(&mut maps).unwrap();
+        assert_eq!(maps[0].rooms[link.room].cells, vec![Cell::new(7, 1)]);
+
+        let rotated = map_builder.rotate_map(&maps[0]);
+
+        assert_eq!(rotated.origin_rect, Rect::new(0, 4, 8, 2));
+        assert_eq!(rotated.rooms[0].cells, vec![Cell::new(7, 4)]);
+        assert_eq!(rotated.rooms[link.room].cells, vec![Cell::new(0, 4)]);
+        assert_eq!(rotated.doors[0].from, Cell::new(7, 4));
+
+        assert_eq!(map_builder.rotate_map(&rotated), maps[0]);
+    }
+}
@@ -101,20 +101,23 @@ impl MapBuilder {
         let room_centers = map_region
             .iter_rooms()
             .map(|(idx, room)| (idx, room.get_center()))
-            .collect::<HashMap<_, _>>();
+            .collect::<Vec<_>>();
 
-        let center_vec = room_centers
-            .values()
-            .fold(Vector2::ZERO, |mut center, other_vector| {
-                center.x += other_vector.x;
-                center.y += other_vector.y;
+        // We add the centers in room order, so the floating point result
+        // is the same between runs
+        let center_vec =
+            room_centers
+                .iter()
+                .fold(Vector2::ZERO, |mut center, (_, other_vector)| {
+                    center.x += other_vector.x;
+                    center.y += other_vector.y;
 
-                center
-            });
+                    center
+                });
 
         (
             center_vec.divide_by(room_centers.len() as f32),
-            room_centers,
+            room_centers.into_iter().collect(),
         )
     }
 
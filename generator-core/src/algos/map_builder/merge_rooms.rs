@@ -1,16 +1,21 @@
 use super::{MapBuilder, MapBuilderConfig};
 use crate::{algos::RngHandler, types::MapRegion};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use rand::Rng;
 
 impl MapBuilder {
-    pub(super) fn merge_random_rooms(map_region: &mut MapRegion, config: &MapBuilderConfig) {
+    // Returns the number of merged rooms.
+    pub(super) fn merge_random_rooms(
+        map_region: &mut MapRegion,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
         let mut rooms_to_merge = HashSet::with_capacity(map_region.room_slots() / 4);
         let mut merge_groups = Vec::with_capacity(rooms_to_merge.capacity() / 2);
 
-        let mut rng = RngHandler::rng();
+        let mut rng = rng_handler.rng();
 
         // We will use a buffer to collect filterd neighbours
         // to avoid excessive allocations
@@ -18,18 +23,18 @@ impl MapBuilder {
 
         // First, we build a set of rooms that are going to be merged
         for (room_id, room) in map_region.iter_active() {
-            if rooms_to_merge.contains(&room_id) {
+            // Pinned rooms keep the shape they were given
+            if room.pinned || rooms_to_merge.contains(&room_id) {
                 continue;
             }
 
             // We only want to merge room_id with a neighbour that is not already in the rooms_to_merge set
             // and that is a valid room in the map_region
-            neighbour_buffer.extend(
-                map_region
-                    .get_neighbours(room_id)
-                    .iter()
-                    .filter(|n| map_region.is_active(*n) && !rooms_to_merge.contains(n)),
-            );
+            neighbour_buffer.extend(map_region.get_neighbours(room_id).iter().filter(|n| {
+                map_region.is_active(*n)
+                    && !map_region.get_active(*n).pinned
+                    && !rooms_to_merge.contains(n)
+            }));
 
             let neighbour_count = neighbour_buffer.len();
 
@@ -56,33 +61,41 @@ impl MapBuilder {
             neighbour_buffer.clear();
         }
 
+        let merged_rooms = merge_groups.len();
+
         // Now we merge the rooms in the merge_groups
         for (room_a_idx, room_b_idx) in merge_groups.into_iter() {
             map_region
                 .merge_active_rooms(room_a_idx, room_b_idx)
                 .expect("Should merge rooms");
         }
+
+        merged_rooms
     }
 
+    // Returns the number of merged rooms.
     pub(super) fn merge_repeated_simple_rooms(
         map_region: &mut MapRegion,
         max_size: usize,
         merge_prob: f64,
-    ) {
-        let mut merge_candidates = HashSet::new();
+        rng_handler: RngHandler,
+    ) -> usize {
+        // Candidates are kept sorted so the random rolls are consumed
+        // in the same order on every run
+        let mut merge_candidates = BTreeSet::new();
 
         for (i, room) in map_region.iter_active() {
             let room_cells = room.cells.len();
 
-            if room_cells <= max_size {
+            if room_cells <= max_size && !room.pinned {
                 merge_candidates.insert(i);
             }
         }
 
         let mut visited_rooms = HashSet::new();
-        let mut merge_pairs = HashMap::new();
+        let mut merge_pairs = BTreeMap::new();
 
-        let mut rng = RngHandler::rng();
+        let mut rng = rng_handler.rng();
 
         for &room_id in merge_candidates.iter() {
             if visited_rooms.contains(&room_id) {
@@ -99,7 +112,7 @@ impl MapBuilder {
                 let room = map_region.get_active(room_id);
                 let neighbour_room = map_region.get_active(neighbour_id);
 
-                if neighbour_room.cells.len() > max_size {
+                if neighbour_room.cells.len() > max_size || neighbour_room.pinned {
                     continue;
                 }
 
@@ -126,17 +139,24 @@ impl MapBuilder {
             }
         }
 
+        let merged_rooms = merge_pairs.len();
+
         for (from, to) in merge_pairs.into_iter() {
             map_region
                 .merge_active_rooms(from, to)
                 .expect("Should merge rooms");
         }
+
+        merged_rooms
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::algos::map_builder::{BinarySpacePartitioningConfig, bsp::BinarySpacePartitioning};
+    use crate::{
+        algos::map_builder::{BinarySpacePartitioningConfig, bsp::BinarySpacePartitioning},
+        constants::TEST_SEED,
+    };
 
     use super::*;
 
@@ -144,11 +164,19 @@ mod test {
     fn test_merge_random_rooms() {
         let width = 20;
         let height = 20;
-        let mut config = BinarySpacePartitioningConfig::default();
         // Force only two regions to be generated
-        config.region_split_factor = width * height;
-
-        let results = BinarySpacePartitioning::generate_and_trim_partitions(width, height, config);
+        let config = BinarySpacePartitioningConfig {
+            region_split_factor: width * height,
+            ..Default::default()
+        };
+
+        let results = BinarySpacePartitioning::generate_and_trim_partitions(
+            width,
+            height,
+            None,
+            config,
+            RngHandler::new(TEST_SEED),
+        );
 
         // We take only the first region for simplicity
         let (origin_rect, rect_table, removed_rects, neighbours) = results[0].clone();
@@ -163,10 +191,12 @@ mod test {
 
         let config = MapBuilderConfig::default();
 
-        MapBuilder::merge_random_rooms(&mut map_region, &config);
+        let merged_rooms =
+            MapBuilder::merge_random_rooms(&mut map_region, &config, RngHandler::new(TEST_SEED));
 
         // Check that the number of rooms, removed rooms, and neighbours has not increased
         let new_room_count = map_region.iter_active().count();
+        assert_eq!(new_room_count, room_count - merged_rooms);
         assert!(
             new_room_count <= room_count,
             "The number of rooms may have decreased after merging"
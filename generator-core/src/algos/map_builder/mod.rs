@@ -1,91 +1,216 @@
 use crate::{
-    MapStyle,
-    types::{Map, Rect},
+    GenerateError, MapStyle,
+    algos::RngHandler,
+    constants::{MAX_MAP_COLUMNS, MAX_MAP_ROWS, MIN_RECT_HEIGHT, MIN_RECT_WIDTH},
+    types::{CellMask, Map, PinnedRoom, Rect},
 };
 
-use anyhow::Result;
 use rayon::prelude::*;
 use tracing::event;
 
 mod add_doors;
+mod add_landmarks;
+mod add_one_way_doors;
+mod add_progression;
+mod add_secret_rooms;
+mod add_warps;
 mod bisect_rooms;
 mod bsp;
 mod builder_config;
 mod connect_regions;
+mod dual_castle;
+mod floors;
 mod gen_rooms;
+mod inverted_castle;
 mod merge_regions;
 mod merge_rooms;
+mod pinned_rooms;
+mod pipeline;
 mod reconnect_rooms;
+mod region_themes;
+mod report;
 mod room_decorator;
+mod room_templates;
+#[cfg(test)]
+mod test_maps;
 
-use builder_config::BinarySpacePartitioningConfig;
-pub(crate) use builder_config::MapBuilderConfig;
+pub use builder_config::{
+    BinarySpacePartitioningConfig, BinarySpacePartitioningConfigBuilder, ConfigFormat,
+    LoopTargetScope, MapBuilderConfig, MapBuilderConfigBuilder,
+};
+pub use pipeline::{BuildPass, BuildPipeline, PassContext};
+pub use report::{GenerationReport, PassDuration};
+pub use room_templates::RoomTemplateLibrary;
+
+// Stream identifiers used to derive the random stream of each pass.
+// Changing them will change the maps generated for a given seed.
+const BSP_STREAM: u64 = 0;
+const REGION_STREAM: u64 = 1;
+const MERGE_REGIONS_STREAM: u64 = 2;
+const PIPELINE_STREAM: u64 = 3;
+const RECONNECT_ROOMS_STREAM: u64 = 4;
+const DOORS_STREAM: u64 = 5;
+const DECORATOR_STREAM: u64 = 6;
+const PROGRESSION_STREAM: u64 = 7;
+const LANDMARKS_STREAM: u64 = 8;
+const WARPS_STREAM: u64 = 9;
+const ONE_WAY_DOORS_STREAM: u64 = 10;
+const SECRET_ROOMS_STREAM: u64 = 11;
+const REGION_THEMES_STREAM: u64 = 12;
+const FLOORS_STREAM: u64 = 13;
+const FLOOR_LINKS_STREAM: u64 = 14;
+const INVERTED_CASTLE_STREAM: u64 = 15;
+const DUAL_CASTLE_STREAM: u64 = 16;
+const CASTLE_LINKS_STREAM: u64 = 17;
+const DEAD_ENDS_STREAM: u64 = 18;
+const LOOPS_STREAM: u64 = 19;
 
 pub(crate) struct MapBuilder {
     pub cols: u32,
     pub rows: u32,
+    pub mask: Option<CellMask>,
+    pub pinned_rooms: Vec<PinnedRoom>,
 }
 
 impl MapBuilder {
-    pub fn new(cols: u32, rows: u32) -> Result<Self> {
-        if cols == 0 || rows == 0 {
-            return Err(anyhow::anyhow!(
-                "Columns and rows must be greater than zero"
-            ));
+    pub fn new(cols: u32, rows: u32) -> Result<Self, GenerateError> {
+        // The partitioning needs at least one rect bigger than the minimum size
+        if cols <= MIN_RECT_WIDTH || rows <= MIN_RECT_HEIGHT {
+            return Err(GenerateError::DimensionsTooSmall {
+                columns: cols,
+                rows,
+                min_columns: MIN_RECT_WIDTH + 1,
+                min_rows: MIN_RECT_HEIGHT + 1,
+            });
+        }
+
+        if cols > MAX_MAP_COLUMNS || rows > MAX_MAP_ROWS {
+            return Err(GenerateError::DimensionsTooLarge {
+                columns: cols,
+                rows,
+                max_columns: MAX_MAP_COLUMNS,
+                max_rows: MAX_MAP_ROWS,
+            });
         }
 
-        Ok(MapBuilder { cols, rows })
+        Ok(MapBuilder {
+            cols,
+            rows,
+            mask: None,
+            pinned_rooms: Vec::new(),
+        })
+    }
+
+    // Creates a builder for a canvas the size of the mask, whose rooms are only
+    // placed inside of it.
+    pub fn new_masked(mask: CellMask) -> Result<Self, GenerateError> {
+        let mut map_builder = Self::new(mask.columns(), mask.rows())?;
+        map_builder.mask = Some(mask);
+
+        Ok(map_builder)
     }
 
-    pub fn build(&self, config: &MapBuilderConfig, style: MapStyle) -> Vec<Map> {
+    pub fn build(
+        &self,
+        config: &MapBuilderConfig,
+        pipeline: &BuildPipeline,
+        style: MapStyle,
+        seed: u64,
+    ) -> Result<(Vec<Map>, GenerationReport), GenerateError> {
         let build_start = std::time::Instant::now();
 
+        let rng_handler = RngHandler::new(seed);
+
+        let mut report = GenerationReport::new(seed);
+
+        event!(tracing::Level::DEBUG, "Building map with seed {}", seed);
+
         let rect_groups = bsp::BinarySpacePartitioning::generate_and_trim_partitions(
             self.cols,
             self.rows,
+            self.reserved_mask().as_ref(),
             config.bsp_config,
+            rng_handler.derive(BSP_STREAM),
         );
 
+        let pinned_rooms = self.place_pinned_rooms(&rect_groups);
+
         let rect_groups_time = std::time::Instant::now();
         event!(
             tracing::Level::DEBUG,
             "Generated rectangle groups in {:.2}ms",
             rect_groups_time.duration_since(build_start).as_millis()
         );
+        report.record_duration("partition", rect_groups_time.duration_since(build_start));
+
+        let region_rng = rng_handler.derive(REGION_STREAM);
+        let room_templates = pipeline.room_templates_for(style);
 
         let map_regions = rect_groups
             .into_par_iter()
+            .enumerate()
             .by_uniform_blocks(30)
-            .map(|(origin_rect, region_rects, removed_rects, neighbours)| {
-                let mut map_region =
-                    Self::generate_map_region(origin_rect, region_rects, removed_rects, neighbours);
+            .map(
+                |(region_idx, (origin_rect, region_rects, removed_rects, neighbours))| {
+                    let rng_handler = region_rng.derive(region_idx as u64);
 
-                map_region.compact_buffers();
+                    let mut region_report = GenerationReport::default();
 
-                Self::merge_random_rooms(&mut map_region, config);
+                    let mut map_region = Self::generate_map_region(
+                        origin_rect,
+                        region_rects,
+                        removed_rects,
+                        neighbours,
+                    );
 
-                Self::reconnect_room_groups(&mut map_region, config);
+                    Self::insert_pinned_rooms(
+                        &mut map_region,
+                        pinned_rooms
+                            .iter()
+                            .filter(|(pinned_region_idx, _)| *pinned_region_idx == region_idx)
+                            .map(|(_, room)| room.clone()),
+                    );
 
-                // We randomly merge some groups of 1 sized-rooms first
-                Self::merge_repeated_simple_rooms(
-                    &mut map_region,
-                    1,
-                    config.repeat_small_room_merge_prob,
-                );
-                // Then we merge rooms of size 2 or less
-                Self::merge_repeated_simple_rooms(
-                    &mut map_region,
-                    2,
-                    config.repeat_small_room_merge_prob / 2.0,
-                );
+                    map_region.compact_buffers();
 
-                // Finally we bisect long horizontal rooms randomly
-                Self::bisect_long_horizontal_rooms(&mut map_region, config.bisect_room_prob);
+                    pipeline.run(
+                        &mut map_region,
+                        config,
+                        style,
+                        &room_templates,
+                        rng_handler.derive(PIPELINE_STREAM),
+                        &mut region_report,
+                    );
+
+                    (map_region, region_report)
+                },
+            )
+            .collect::<Vec<_>>();
+
+        // Reports are merged in region order, so the passes are listed in the order they ran
+        let map_regions = map_regions
+            .into_iter()
+            .map(|(map_region, region_report)| {
+                report.merge_from(region_report);
 
                 map_region
             })
             .collect::<Vec<_>>();
 
+        // Regions can end up without rooms after trimming, we keep the original
+        // index of the remaining ones so their random streams do not change
+        let mut map_regions = map_regions
+            .into_iter()
+            .enumerate()
+            .filter(|(_, map_region)| map_region.iter_active().next().is_some())
+            .collect::<Vec<_>>();
+
+        if map_regions.is_empty() {
+            return Err(GenerateError::EmptyLayout);
+        }
+
+        report.region_count = map_regions.len();
+
         let map_regions_time = std::time::Instant::now();
         event!(
             tracing::Level::DEBUG,
@@ -95,44 +220,221 @@ impl MapBuilder {
                 .as_millis()
         );
 
-        let generated_maps = if config.merge_regions {
+        Self::add_region_themes(
+            map_regions.iter_mut().map(|(_, map_region)| map_region),
+            style,
+            rng_handler.derive(REGION_THEMES_STREAM),
+        );
+
+        report.record_duration("add_region_themes", map_regions_time.elapsed());
+
+        let mut generated_maps = if config.merge_regions {
             let origin_rect = Rect::new(0, 0, self.cols, self.rows);
 
+            let map_regions = map_regions
+                .into_iter()
+                .map(|(_, map_region)| map_region)
+                .collect();
+
+            let merge_start = std::time::Instant::now();
+
             let mut map_region = Self::merge_regions(origin_rect, map_regions);
+            Self::link_pinned_rooms(&mut map_region);
+
+            let rng_handler = rng_handler.derive(MERGE_REGIONS_STREAM);
 
             // We connect the rooms of the newly merged region together
-            Self::reconnect_room_groups(&mut map_region, config);
+            let (orphan_rooms, small_group_rooms) = Self::reconnect_room_groups(
+                &mut map_region,
+                config,
+                rng_handler.derive(RECONNECT_ROOMS_STREAM),
+            );
+            report.rooms_removed_as_orphans += orphan_rooms;
+            report.rooms_removed_in_small_groups += small_group_rooms;
+
+            let doors_start = std::time::Instant::now();
+            report.record_duration("merge_regions", doors_start.duration_since(merge_start));
 
-            let doors: Vec<crate::types::Door> = Self::generate_doors_for(&map_region, config);
+            let mut doors: Vec<crate::types::Door> =
+                Self::generate_doors_for(&map_region, config, rng_handler.derive(DOORS_STREAM));
+            // The doors of the dead ends count towards the target loops
+            report.dead_end_doors += Self::limit_dead_ends(
+                &map_region,
+                &mut doors,
+                config,
+                rng_handler.derive(DEAD_ENDS_STREAM),
+            );
+            report.loop_doors += Self::add_target_loops(
+                &map_region,
+                &mut doors,
+                config.target_loops,
+                config,
+                rng_handler.derive(LOOPS_STREAM),
+            );
+
+            let decorate_start = std::time::Instant::now();
+            report.record_duration("generate_doors", decorate_start.duration_since(doors_start));
 
             room_decorator::RoomDecoratorFactory::decorator_for(style).decorate(
                 &mut map_region,
                 &doors,
                 config,
+                rng_handler.derive(DECORATOR_STREAM),
+            );
+
+            let secret_rooms_start = std::time::Instant::now();
+            report.record_duration(
+                "decorate_rooms",
+                secret_rooms_start.duration_since(decorate_start),
             );
 
+            report.secret_rooms += Self::add_secret_rooms(
+                &mut map_region,
+                &mut doors,
+                config,
+                rng_handler.derive(SECRET_ROOMS_STREAM),
+            );
+
+            report.record_duration("add_secret_rooms", secret_rooms_start.elapsed());
+
             vec![map_region.into_map(doors)]
         } else {
+            // A target for the whole map is shared between the regions by their
+            // number of rooms, and the loops a region cannot hold move on to the
+            // next ones. The regions are linked without any loop between them.
+            let mut rooms_left = map_regions
+                .iter()
+                .map(|(_, map_region)| map_region.iter_active().count())
+                .sum::<usize>();
+            let mut loops_left = config.target_loops.unwrap_or(0) as usize;
+
             let mut maps = map_regions
                 .into_iter()
-                .map(|mut map_region| {
-                    let doors = Self::generate_doors_for(&map_region, config);
+                .map(|(region_idx, mut map_region)| {
+                    let rng_handler = region_rng.derive(region_idx as u64);
+
+                    let doors_start = std::time::Instant::now();
+
+                    let mut doors = Self::generate_doors_for(
+                        &map_region,
+                        config,
+                        rng_handler.derive(DOORS_STREAM),
+                    );
+                    report.dead_end_doors += Self::limit_dead_ends(
+                        &map_region,
+                        &mut doors,
+                        config,
+                        rng_handler.derive(DEAD_ENDS_STREAM),
+                    );
+
+                    let room_count = map_region.iter_active().count();
+                    let target_loops = match config.loop_target_scope {
+                        LoopTargetScope::PerRegion => config.target_loops,
+                        LoopTargetScope::WholeMap => config.target_loops.map(|_| {
+                            ((loops_left * room_count + rooms_left / 2) / rooms_left) as u32
+                        }),
+                    };
+
+                    report.loop_doors += Self::add_target_loops(
+                        &map_region,
+                        &mut doors,
+                        target_loops,
+                        config,
+                        rng_handler.derive(LOOPS_STREAM),
+                    );
+
+                    if config.target_loops.is_some() {
+                        let door_graph = room_decorator::DoorGraph::new(&map_region, &doors);
+
+                        loops_left = loops_left.saturating_sub(door_graph.loop_count());
+                        rooms_left -= room_count;
+                    }
+
+                    let decorate_start = std::time::Instant::now();
+                    report.record_duration(
+                        "generate_doors",
+                        decorate_start.duration_since(doors_start),
+                    );
 
                     room_decorator::RoomDecoratorFactory::decorator_for(style).decorate(
                         &mut map_region,
                         &doors,
                         config,
+                        rng_handler.derive(DECORATOR_STREAM),
                     );
 
+                    let secret_rooms_start = std::time::Instant::now();
+                    report.record_duration(
+                        "decorate_rooms",
+                        secret_rooms_start.duration_since(decorate_start),
+                    );
+
+                    report.secret_rooms += Self::add_secret_rooms(
+                        &mut map_region,
+                        &mut doors,
+                        config,
+                        rng_handler.derive(SECRET_ROOMS_STREAM),
+                    );
+
+                    report.record_duration("add_secret_rooms", secret_rooms_start.elapsed());
+
                     map_region.into_map(doors)
                 })
                 .collect::<Vec<_>>();
 
+            let connect_start = std::time::Instant::now();
+
             Self::connect_regions(&mut maps);
 
+            report.record_duration("connect_regions", connect_start.elapsed());
+
             maps
         };
 
+        self.check_pinned_rooms(&generated_maps)?;
+
+        let landmarks_start = std::time::Instant::now();
+
+        report.boss_rooms = Self::add_landmarks(
+            &mut generated_maps,
+            config,
+            rng_handler.derive(LANDMARKS_STREAM),
+        );
+
+        report.record_duration("add_landmarks", landmarks_start.elapsed());
+
+        let warps_start = std::time::Instant::now();
+
+        report.warp_rooms = Self::add_warp_rooms(
+            &mut generated_maps,
+            config,
+            rng_handler.derive(WARPS_STREAM),
+        );
+
+        report.record_duration("add_warp_rooms", warps_start.elapsed());
+
+        let one_way_start = std::time::Instant::now();
+
+        report.one_way_doors = Self::add_one_way_doors(
+            &mut generated_maps,
+            config,
+            rng_handler.derive(ONE_WAY_DOORS_STREAM),
+        );
+
+        report.record_duration("add_one_way_doors", one_way_start.elapsed());
+
+        let progression_start = std::time::Instant::now();
+
+        let (ability_doors, locked_doors) = Self::add_progression(
+            &mut generated_maps,
+            config,
+            rng_handler.derive(PROGRESSION_STREAM),
+        );
+        report.ability_doors = ability_doors;
+        report.locked_doors = locked_doors;
+
+        report.record_duration("add_progression", progression_start.elapsed());
+
         let generated_maps_time = std::time::Instant::now();
         event!(
             tracing::Level::DEBUG,
@@ -158,6 +460,10 @@ impl MapBuilder {
             generated_maps_time.duration_since(build_start).as_millis()
         );
 
-        generated_maps
+        report.room_count = built_rooms;
+        report.doors_created = built_doors;
+        report.total_duration = generated_maps_time.duration_since(build_start);
+
+        Ok((generated_maps, report))
     }
 }
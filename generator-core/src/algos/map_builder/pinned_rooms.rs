@@ -0,0 +1,209 @@
+use super::{
+    MapBuilder,
+    bsp::{NeighbourTable, RectTable, RemovedRectTable},
+};
+use crate::{
+    GenerateError,
+    types::{Cell, CellMask, Map, MapRegion, PinnedRoom, Rect, Room, RoomModifier},
+};
+
+use std::{cmp::Reverse, collections::HashMap};
+
+impl MapBuilder {
+    // Sets the rooms placed by hand, whose cells are left out of the partitioning
+    // so the rest of the map is generated around them.
+    pub fn with_pinned_rooms(
+        mut self,
+        pinned_rooms: Vec<PinnedRoom>,
+    ) -> Result<Self, GenerateError> {
+        let mut cell_owners = HashMap::<Cell, usize>::new();
+
+        for (index, pinned_room) in pinned_rooms.iter().enumerate() {
+            let invalid_room = |reason: String| GenerateError::InvalidPinnedRoom { index, reason };
+
+            pinned_room
+                .validate()
+                .map_err(|e| invalid_room(e.to_string()))?;
+
+            for cell in pinned_room.cells.iter() {
+                let is_inside = match &self.mask {
+                    Some(mask) => mask.contains(*cell),
+                    None => cell.col < self.cols && cell.row < self.rows,
+                };
+
+                if !is_inside {
+                    return Err(invalid_room(format!("cell {} is outside of the map", cell)));
+                }
+
+                if let Some(owner) = cell_owners.insert(*cell, index) {
+                    if owner != index {
+                        return Err(invalid_room(format!(
+                            "cell {} is also part of pinned room {}",
+                            cell, owner
+                        )));
+                    }
+                }
+            }
+        }
+
+        self.pinned_rooms = pinned_rooms;
+
+        Ok(self)
+    }
+
+    // Returns the mask the canvas is partitioned with, leaving out the cells
+    // of the pinned rooms.
+    pub(super) fn reserved_mask(&self) -> Option<CellMask> {
+        if self.pinned_rooms.is_empty() {
+            return self.mask.clone();
+        }
+
+        let mut mask = self
+            .mask
+            .clone()
+            .unwrap_or_else(|| CellMask::full(self.cols, self.rows));
+
+        for cell in self.pinned_rooms.iter().flat_map(|room| room.cells.iter()) {
+            mask.remove(*cell);
+        }
+
+        Some(mask)
+    }
+
+    // Returns the rooms of the pinned rooms, along with the index of the region
+    // each one is placed in.
+    //
+    // Every room goes to the region with the most rects next to it, or the one
+    // holding most of its cells when no rect is next to it.
+    #[allow(clippy::type_complexity)]
+    pub(super) fn place_pinned_rooms(
+        &self,
+        rect_groups: &[(Rect, RectTable, RemovedRectTable, NeighbourTable)],
+    ) -> Vec<(usize, Room)> {
+        self.pinned_rooms
+            .iter()
+            .filter_map(|pinned_room| {
+                let room = pinned_room.room();
+
+                let (region_idx, _) = rect_groups
+                    .iter()
+                    .enumerate()
+                    .map(|(region_idx, (origin_rect, rects, removed_rects, _))| {
+                        let next_rects = rects
+                            .values()
+                            .chain(removed_rects.values())
+                            .filter(|rect| Room::new_from_rect(**rect).is_neighbour_of(&room))
+                            .count();
+                        let cells_inside = room
+                            .cells
+                            .iter()
+                            .filter(|cell| origin_rect.contains(**cell))
+                            .count();
+
+                        (region_idx, (next_rects, cells_inside))
+                    })
+                    .max_by_key(|(region_idx, score)| (*score, Reverse(*region_idx)))?;
+
+                Some((region_idx, room))
+            })
+            .collect()
+    }
+
+    // Inserts the rooms as active rooms of the region, neighbouring every room
+    // next to them.
+    pub(super) fn insert_pinned_rooms(
+        map_region: &mut MapRegion,
+        rooms: impl Iterator<Item = Room>,
+    ) {
+        for room in rooms {
+            let room_id = map_region.insert_room(room);
+            map_region.link_adjacent_rooms(room_id);
+        }
+    }
+
+    // Links the pinned rooms of the region with every room next to them, since
+    // they can be next to rooms that were generated in other regions.
+    pub(super) fn link_pinned_rooms(map_region: &mut MapRegion) {
+        let pinned_rooms = map_region
+            .iter_active()
+            .filter(|(_, room)| room.pinned)
+            .map(|(room_id, _)| room_id)
+            .collect::<Vec<_>>();
+
+        for room_id in pinned_rooms {
+            map_region.link_adjacent_rooms(room_id);
+        }
+    }
+
+    // Fails when a pinned room ends up without any door or region connection,
+    // which happens when no generated room is placed next to it.
+    pub(super) fn check_pinned_rooms(&self, maps: &[Map]) -> Result<(), GenerateError> {
+        for (index, pinned_room) in self.pinned_rooms.iter().enumerate() {
+            let is_connected = maps.iter().any(|map| {
+                let has_door = map.doors.iter().any(|door| {
+                    pinned_room.cells.contains(&door.from) || pinned_room.cells.contains(&door.to)
+                });
+                let has_region_connection = map.rooms.iter().any(|room| {
+                    room.pinned
+                        && matches!(room.modifier, Some(RoomModifier::RegionConnection(..)))
+                        && room.cells.contains(&pinned_room.cells[0])
+                });
+
+                has_door || has_region_connection
+            });
+
+            if !is_connected {
+                return Err(GenerateError::InvalidPinnedRoom {
+                    index,
+                    reason: "it is not connected to any other room".to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_with_pinned_rooms_rejects_invalid_rooms() {
+        let map_builder = || MapBuilder::new(24, 16).unwrap();
+
+        let throne_room =
+            PinnedRoom::from_rect(Rect::new(8, 0, 8, 3)).with_modifier(RoomModifier::Boss);
+        let entrance_hall =
+            PinnedRoom::new(vec![Cell::new(0, 10)]).with_modifier(RoomModifier::Start);
+
+        let mask = map_builder()
+            .with_pinned_rooms(vec![throne_room.clone(), entrance_hall])
+            .unwrap()
+            .reserved_mask()
+            .unwrap();
+        assert!(!mask.contains(Cell::new(8, 0)));
+        assert!(!mask.contains(Cell::new(0, 10)));
+        assert!(mask.contains(Cell::new(8, 3)));
+
+        let overlapping_room = PinnedRoom::from_rect(Rect::new(15, 2, 2, 2));
+        assert!(matches!(
+            map_builder().with_pinned_rooms(vec![throne_room, overlapping_room]),
+            Err(GenerateError::InvalidPinnedRoom { index: 1, .. })
+        ));
+
+        let outside_room = PinnedRoom::from_rect(Rect::new(23, 15, 2, 1));
+        assert!(map_builder().with_pinned_rooms(vec![outside_room]).is_err());
+
+        let split_room = PinnedRoom::new(vec![Cell::new(0, 0), Cell::new(2, 0)]);
+        assert!(map_builder().with_pinned_rooms(vec![split_room]).is_err());
+
+        let big_save_room =
+            PinnedRoom::from_rect(Rect::new(0, 0, 2, 1)).with_modifier(RoomModifier::Save);
+        assert!(
+            map_builder()
+                .with_pinned_rooms(vec![big_save_room])
+                .is_err()
+        );
+    }
+}
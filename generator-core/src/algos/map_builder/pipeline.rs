@@ -0,0 +1,470 @@
+use super::{GenerationReport, MapBuilder, MapBuilderConfig, RoomTemplateLibrary};
+use crate::{MapStyle, algos::RngHandler, types::MapRegion};
+
+use std::{
+    borrow::Cow,
+    cell::{RefCell, RefMut},
+    fmt::{Debug, Formatter},
+};
+
+use anyhow::Result;
+use rand_chacha::ChaCha8Rng;
+
+/// A step of the generation that transforms the rooms of a region.
+///
+/// Passes run once per region, after the region has been partitioned into
+/// rooms and before the doors are generated.
+pub trait BuildPass: Send + Sync {
+    /// The name of the pass, which must be unique within a [`BuildPipeline`].
+    ///
+    /// The random stream of the pass is derived from it, so reordering or
+    /// removing other passes does not change the rolls of this one.
+    fn name(&self) -> &str;
+
+    fn run(&self, map_region: &mut MapRegion, context: &PassContext);
+}
+
+/// The data available to a [`BuildPass`] while it runs.
+pub struct PassContext<'a> {
+    config: &'a MapBuilderConfig,
+    style: MapStyle,
+    room_templates: &'a RoomTemplateLibrary,
+    rng_handler: RngHandler,
+    rng: RefCell<ChaCha8Rng>,
+    report: RefCell<GenerationReport>,
+}
+
+impl<'a> PassContext<'a> {
+    pub(crate) fn new(
+        config: &'a MapBuilderConfig,
+        style: MapStyle,
+        room_templates: &'a RoomTemplateLibrary,
+        rng_handler: RngHandler,
+    ) -> Self {
+        PassContext {
+            config,
+            style,
+            room_templates,
+            rng_handler,
+            rng: RefCell::new(rng_handler.rng()),
+            report: RefCell::new(GenerationReport::default()),
+        }
+    }
+
+    pub fn config(&self) -> &MapBuilderConfig {
+        self.config
+    }
+
+    pub fn style(&self) -> MapStyle {
+        self.style
+    }
+
+    /// Returns the room templates of the pipeline, or the built-in ones of the
+    /// style when the pipeline has none of its own.
+    pub fn room_templates(&self) -> &RoomTemplateLibrary {
+        self.room_templates
+    }
+
+    /// Returns the random generator of the pass, seeded from the map seed.
+    ///
+    /// The generator is shared by the whole pass, so every call continues the
+    /// sequence where the previous one left it.
+    /// Panics if the generator is still borrowed by a previous call.
+    pub fn rng(&self) -> RefMut<'_, impl rand::Rng + use<>> {
+        self.rng.borrow_mut()
+    }
+
+    pub(crate) fn rng_handler(&self) -> RngHandler {
+        self.rng_handler
+    }
+
+    // Updates the counters of the report with the changes made by the pass.
+    pub(crate) fn record(&self, f: impl FnOnce(&mut GenerationReport)) {
+        f(&mut self.report.borrow_mut());
+    }
+}
+
+/// The ordered list of passes run over every region of the map.
+///
+/// The default pipeline contains the built-in passes, which can be removed,
+/// reordered or extended with custom ones.
+pub struct BuildPipeline {
+    passes: Vec<Box<dyn BuildPass>>,
+    room_templates: Option<RoomTemplateLibrary>,
+}
+
+impl BuildPipeline {
+    pub const MERGE_RANDOM_ROOMS: &'static str = "merge_random_rooms";
+    pub const STAMP_ROOM_TEMPLATES: &'static str = "stamp_room_templates";
+    pub const RECONNECT_ROOM_GROUPS: &'static str = "reconnect_room_groups";
+    pub const MERGE_SINGLE_ROOMS: &'static str = "merge_single_rooms";
+    pub const MERGE_DOUBLE_ROOMS: &'static str = "merge_double_rooms";
+    pub const BISECT_ROOMS: &'static str = "bisect_rooms";
+
+    /// Creates a pipeline without any pass.
+    pub fn empty() -> Self {
+        BuildPipeline {
+            passes: Vec::new(),
+            room_templates: None,
+        }
+    }
+
+    /// Replaces the room templates stamped into the map, which are the built-in
+    /// ones of the map style by default.
+    pub fn set_room_templates(&mut self, room_templates: RoomTemplateLibrary) -> &mut Self {
+        self.room_templates = Some(room_templates);
+        self
+    }
+
+    /// Returns the names of the passes, in the order they run.
+    pub fn pass_names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Adds a pass at the end of the pipeline.
+    pub fn push(&mut self, pass: Box<dyn BuildPass>) -> Result<&mut Self> {
+        let at = self.passes.len();
+
+        self.insert_at(at, pass)
+    }
+
+    /// Adds a pass right before the pass named `target`.
+    pub fn insert_before(&mut self, target: &str, pass: Box<dyn BuildPass>) -> Result<&mut Self> {
+        let at = self.try_position(target)?;
+
+        self.insert_at(at, pass)
+    }
+
+    /// Adds a pass right after the pass named `target`.
+    pub fn insert_after(&mut self, target: &str, pass: Box<dyn BuildPass>) -> Result<&mut Self> {
+        let at = self.try_position(target)? + 1;
+
+        self.insert_at(at, pass)
+    }
+
+    /// Removes the pass named `name`, returning it so it can be inserted elsewhere.
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn BuildPass>> {
+        self.position(name).map(|idx| self.passes.remove(idx))
+    }
+
+    // Returns the room templates set on the pipeline, or the built-in ones of
+    // the style. Built once per map, since every region stamps the same ones.
+    pub(crate) fn room_templates_for(&self, style: MapStyle) -> Cow<'_, RoomTemplateLibrary> {
+        match &self.room_templates {
+            Some(room_templates) => Cow::Borrowed(room_templates),
+            None => Cow::Owned(RoomTemplateLibrary::for_style(style)),
+        }
+    }
+
+    pub(crate) fn run(
+        &self,
+        map_region: &mut MapRegion,
+        config: &MapBuilderConfig,
+        style: MapStyle,
+        room_templates: &RoomTemplateLibrary,
+        rng_handler: RngHandler,
+        report: &mut GenerationReport,
+    ) {
+        for pass in self.passes.iter() {
+            let pass_start = std::time::Instant::now();
+
+            let context = PassContext::new(
+                config,
+                style,
+                room_templates,
+                rng_handler.derive_named(pass.name()),
+            );
+
+            pass.run(map_region, &context);
+
+            report.merge_from(context.report.into_inner());
+            report.record_duration(pass.name(), pass_start.elapsed());
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|pass| pass.name() == name)
+    }
+
+    fn try_position(&self, name: &str) -> Result<usize> {
+        self.position(name)
+            .ok_or_else(|| anyhow::anyhow!("There is no pass named '{}'", name))
+    }
+
+    fn insert_at(&mut self, at: usize, pass: Box<dyn BuildPass>) -> Result<&mut Self> {
+        if self.contains(pass.name()) {
+            return Err(anyhow::anyhow!(
+                "A pass named '{}' is already in the pipeline",
+                pass.name()
+            ));
+        }
+
+        self.passes.insert(at, pass);
+
+        Ok(self)
+    }
+}
+
+impl Default for BuildPipeline {
+    fn default() -> Self {
+        BuildPipeline {
+            passes: vec![
+                Box::new(MergeRandomRoomsPass),
+                Box::new(StampRoomTemplatesPass),
+                Box::new(ReconnectRoomGroupsPass),
+                // We randomly merge some groups of 1 sized-rooms first
+                Box::new(MergeSimpleRoomsPass {
+                    name: Self::MERGE_SINGLE_ROOMS,
+                    max_size: 1,
+                    prob_divisor: 1.0,
+                }),
+                // Then we merge rooms of size 2 or less
+                Box::new(MergeSimpleRoomsPass {
+                    name: Self::MERGE_DOUBLE_ROOMS,
+                    max_size: 2,
+                    prob_divisor: 2.0,
+                }),
+                // Finally we bisect long horizontal rooms randomly
+                Box::new(BisectRoomsPass),
+            ],
+            room_templates: None,
+        }
+    }
+}
+
+impl Debug for BuildPipeline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.pass_names()).finish()
+    }
+}
+
+struct MergeRandomRoomsPass;
+
+impl BuildPass for MergeRandomRoomsPass {
+    fn name(&self) -> &str {
+        BuildPipeline::MERGE_RANDOM_ROOMS
+    }
+
+    fn run(&self, map_region: &mut MapRegion, context: &PassContext) {
+        let merged_rooms =
+            MapBuilder::merge_random_rooms(map_region, context.config(), context.rng_handler());
+
+        context.record(|report| report.rooms_merged += merged_rooms);
+    }
+}
+
+struct StampRoomTemplatesPass;
+
+impl BuildPass for StampRoomTemplatesPass {
+    fn name(&self) -> &str {
+        BuildPipeline::STAMP_ROOM_TEMPLATES
+    }
+
+    fn run(&self, map_region: &mut MapRegion, context: &PassContext) {
+        let template_rooms = MapBuilder::stamp_room_templates(
+            map_region,
+            context.room_templates(),
+            context.config(),
+            context.rng_handler(),
+        );
+
+        context.record(|report| report.template_rooms += template_rooms);
+    }
+}
+
+struct ReconnectRoomGroupsPass;
+
+impl BuildPass for ReconnectRoomGroupsPass {
+    fn name(&self) -> &str {
+        BuildPipeline::RECONNECT_ROOM_GROUPS
+    }
+
+    fn run(&self, map_region: &mut MapRegion, context: &PassContext) {
+        let (orphan_rooms, small_group_rooms) =
+            MapBuilder::reconnect_room_groups(map_region, context.config(), context.rng_handler());
+
+        context.record(|report| {
+            report.rooms_removed_as_orphans += orphan_rooms;
+            report.rooms_removed_in_small_groups += small_group_rooms;
+        });
+    }
+}
+
+struct MergeSimpleRoomsPass {
+    name: &'static str,
+    max_size: usize,
+    prob_divisor: f64,
+}
+
+impl BuildPass for MergeSimpleRoomsPass {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn run(&self, map_region: &mut MapRegion, context: &PassContext) {
+        let merged_rooms = MapBuilder::merge_repeated_simple_rooms(
+            map_region,
+            self.max_size,
+            context.config().repeat_small_room_merge_prob / self.prob_divisor,
+            context.rng_handler(),
+        );
+
+        context.record(|report| report.rooms_merged += merged_rooms);
+    }
+}
+
+struct BisectRoomsPass;
+
+impl BuildPass for BisectRoomsPass {
+    fn name(&self) -> &str {
+        BuildPipeline::BISECT_ROOMS
+    }
+
+    fn run(&self, map_region: &mut MapRegion, context: &PassContext) {
+        let bisected_rooms = MapBuilder::bisect_long_horizontal_rooms(
+            map_region,
+            context.config().bisect_room_prob,
+            context.rng_handler(),
+        );
+
+        context.record(|report| report.rooms_bisected += bisected_rooms);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constants::TEST_SEED,
+        types::{Rect, Room},
+    };
+
+    use rand::Rng;
+
+    struct NamedPass(&'static str);
+
+    impl BuildPass for NamedPass {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn run(&self, _: &mut MapRegion, _: &PassContext) {}
+    }
+
+    // Moves the last cell of the first room with many cells into a room of its own
+    struct CarveCellPass;
+
+    impl BuildPass for CarveCellPass {
+        fn name(&self) -> &str {
+            "carve_cell"
+        }
+
+        fn run(&self, map_region: &mut MapRegion, _: &PassContext) {
+            let room_id = map_region
+                .iter_active()
+                .find(|(_, room)| room.cells.len() > 1)
+                .map(|(room_id, _)| room_id)
+                .unwrap();
+
+            let cell = map_region.get_mut_room(room_id).cells.pop().unwrap();
+
+            for neighbour_id in map_region.iter_neighbours(room_id).collect::<Vec<_>>() {
+                map_region.remove_neighbours(room_id, neighbour_id);
+            }
+
+            let carved_id =
+                map_region.insert_room(Room::new_from_rect(Rect::new(cell.col, cell.row, 1, 1)));
+
+            map_region.link_adjacent_rooms(room_id);
+            map_region.link_adjacent_rooms(carved_id);
+        }
+    }
+
+    #[test]
+    fn test_custom_pass_can_rewire_neighbours() {
+        let mut map_region = MapRegion::new_test_region();
+        let config = MapBuilderConfig::default();
+        let room_templates = RoomTemplateLibrary::empty();
+        let context = PassContext::new(
+            &config,
+            MapStyle::CastlevaniaSOTN,
+            &room_templates,
+            RngHandler::new(TEST_SEED),
+        );
+
+        CarveCellPass.run(&mut map_region, &context);
+
+        for (room_id, room) in map_region.iter_rooms() {
+            for (other_id, other_room) in map_region.iter_rooms() {
+                assert_eq!(
+                    room_id != other_id && room.is_neighbour_of(other_room),
+                    map_region.iter_neighbours(room_id).any(|id| id == other_id)
+                );
+            }
+        }
+
+        // The generator of the pass keeps going between calls
+        let first_roll = context.rng().random::<u64>();
+        let second_roll = context.rng().random::<u64>();
+        assert_ne!(first_roll, second_roll);
+    }
+
+    #[test]
+    fn test_default_pipeline_order() {
+        let pipeline = BuildPipeline::default();
+
+        assert_eq!(
+            pipeline.pass_names().collect::<Vec<_>>(),
+            vec![
+                BuildPipeline::MERGE_RANDOM_ROOMS,
+                BuildPipeline::STAMP_ROOM_TEMPLATES,
+                BuildPipeline::RECONNECT_ROOM_GROUPS,
+                BuildPipeline::MERGE_SINGLE_ROOMS,
+                BuildPipeline::MERGE_DOUBLE_ROOMS,
+                BuildPipeline::BISECT_ROOMS,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pipeline_can_be_reordered_and_extended() {
+        let mut pipeline = BuildPipeline::default();
+
+        let bisect = pipeline.remove(BuildPipeline::BISECT_ROOMS).unwrap();
+        assert!(!pipeline.contains(BuildPipeline::BISECT_ROOMS));
+
+        pipeline
+            .insert_before(BuildPipeline::MERGE_RANDOM_ROOMS, bisect)
+            .unwrap()
+            .insert_after(BuildPipeline::MERGE_RANDOM_ROOMS, Box::new(NamedPass("a")))
+            .unwrap()
+            .push(Box::new(NamedPass("b")))
+            .unwrap();
+
+        assert_eq!(
+            pipeline.pass_names().collect::<Vec<_>>(),
+            vec![
+                BuildPipeline::BISECT_ROOMS,
+                BuildPipeline::MERGE_RANDOM_ROOMS,
+                "a",
+                BuildPipeline::STAMP_ROOM_TEMPLATES,
+                BuildPipeline::RECONNECT_ROOM_GROUPS,
+                BuildPipeline::MERGE_SINGLE_ROOMS,
+                BuildPipeline::MERGE_DOUBLE_ROOMS,
+                "b",
+            ]
+        );
+
+        assert!(pipeline.push(Box::new(NamedPass("a"))).is_err());
+        assert!(
+            pipeline
+                .insert_before("missing", Box::new(NamedPass("c")))
+                .is_err()
+        );
+        assert!(pipeline.remove("missing").is_none());
+    }
+}
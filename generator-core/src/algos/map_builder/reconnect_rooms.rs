@@ -6,7 +6,7 @@ use crate::{
 
 use std::{
     cmp::Reverse,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     ops::Deref,
 };
 
@@ -15,33 +15,50 @@ use rand::Rng;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 impl MapBuilder {
-    pub(super) fn reconnect_room_groups(map_region: &mut MapRegion, config: &MapBuilderConfig) {
+    // Returns the number of rooms removed as orphans and the number
+    // of rooms removed for being in a small group.
+    pub(super) fn reconnect_room_groups(
+        map_region: &mut MapRegion,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> (usize, usize) {
         let mut room_groups = Self::generate_room_groups(map_region);
 
         if room_groups.len() <= 1 {
-            return;
+            return (0, 0);
         }
 
-        // We remove groups with just 1 room
+        let group_count = room_groups.len();
+
+        // We remove groups with just 1 room, unless it is a pinned one
         room_groups.retain(|_, group| {
-            if group.len() > 1 {
+            if group.len() > 1 || Self::has_pinned_room(map_region, group) {
                 true
             } else {
-                let room_id = group.drain().next().unwrap();
+                let room_id = group.pop_first().unwrap();
                 map_region.mark_removed(room_id);
 
                 false
             }
         });
 
+        let orphan_rooms = group_count - room_groups.len();
+
         if room_groups.len() > 1 {
             // If there is more than one group, we need to connect them together
-            Self::connect_room_groups(room_groups, map_region, config);
+            let small_group_rooms =
+                Self::connect_room_groups(room_groups, map_region, config, rng_handler);
+
+            (orphan_rooms, small_group_rooms)
+        } else {
+            (orphan_rooms, 0)
         }
     }
 
-    fn generate_room_groups(map_region: &MapRegion) -> HashMap<usize, HashSet<RoomId>> {
-        let mut room_groups = HashMap::new();
+    // Groups are kept in ordered collections, so that iterating over them
+    // (and thus consuming random rolls) is stable between runs.
+    fn generate_room_groups(map_region: &MapRegion) -> BTreeMap<usize, BTreeSet<RoomId>> {
+        let mut room_groups = BTreeMap::new();
         let mut group_id = 0;
 
         let mut map_rooms = map_region
@@ -56,7 +73,7 @@ impl MapBuilder {
             }
 
             let mut rooms_to_visit = vec![room_id];
-            let mut group_visited_rooms = HashSet::new();
+            let mut group_visited_rooms = BTreeSet::new();
 
             while let Some(room_id) = rooms_to_visit.pop() {
                 group_visited_rooms.insert(room_id);
@@ -78,19 +95,23 @@ impl MapBuilder {
     }
 
     fn connect_room_groups(
-        mut room_groups: HashMap<usize, HashSet<RoomId>>,
+        mut room_groups: BTreeMap<usize, BTreeSet<RoomId>>,
         map_region: &mut MapRegion,
         config: &MapBuilderConfig,
-    ) {
+        rng_handler: RngHandler,
+    ) -> usize {
         // First we remove the lowest percentile sized groups
-        Self::remove_small_groups(map_region, &mut room_groups);
+        let small_group_rooms = Self::remove_small_groups(map_region, &mut room_groups);
 
         // Then we compute the center of each group and each room
         let (group_centers, room_centers) = Self::generate_group_centers(map_region, &room_groups);
 
         // We use the group centers to find the closest groups to each other
-        let closest_groups =
-            Self::generate_closest_groups(&group_centers, config.group_loop_connection_chance);
+        let closest_groups = Self::generate_closest_groups(
+            &group_centers,
+            config.group_loop_connection_chance,
+            rng_handler,
+        );
 
         let map_region = std::sync::Arc::new(std::sync::RwLock::new(map_region));
 
@@ -144,17 +165,25 @@ impl MapBuilder {
         for room_id in room_path.into_iter() {
             lock_write.mark_active(room_id);
         }
+
+        small_group_rooms
+    }
+
+    fn has_pinned_room(map_region: &MapRegion, rooms: &BTreeSet<RoomId>) -> bool {
+        rooms
+            .iter()
+            .any(|room_id| map_region.get_active(*room_id).pinned)
     }
 
     fn remove_small_groups(
         map_region: &mut MapRegion,
-        room_groups: &mut HashMap<usize, HashSet<usize>>,
-    ) {
+        room_groups: &mut BTreeMap<usize, BTreeSet<usize>>,
+    ) -> usize {
         let group_count = room_groups.len() as f32;
 
         let total_rooms = room_groups
-            .iter()
-            .map(|(_, group_rooms)| group_rooms.len() as f32)
+            .values()
+            .map(|group_rooms| group_rooms.len() as f32)
             .sum::<f32>();
 
         let group_size_cutoff = {
@@ -167,22 +196,28 @@ impl MapBuilder {
             }
         } as usize;
 
+        let mut removed_rooms = 0;
+
         room_groups.retain(|_, rooms| {
-            if rooms.len() > group_size_cutoff {
+            if rooms.len() > group_size_cutoff || Self::has_pinned_room(map_region, rooms) {
                 true
             } else {
-                for room_id in rooms.drain() {
+                removed_rooms += rooms.len();
+
+                for room_id in std::mem::take(rooms) {
                     map_region.mark_removed(room_id);
                 }
                 false
             }
         });
+
+        removed_rooms
     }
 
     fn generate_group_centers(
         map_region: &MapRegion,
-        room_groups: &HashMap<usize, HashSet<usize>>,
-    ) -> (HashMap<usize, Vector2>, HashMap<RoomId, Vector2>) {
+        room_groups: &BTreeMap<usize, BTreeSet<usize>>,
+    ) -> (BTreeMap<usize, Vector2>, HashMap<RoomId, Vector2>) {
         let room_centers = map_region
             .iter_rooms()
             .map(|(idx, room)| (idx, room.get_center()))
@@ -207,19 +242,20 @@ impl MapBuilder {
 
                 (*group_id, center)
             })
-            .collect::<HashMap<_, _>>();
+            .collect::<BTreeMap<_, _>>();
 
         (group_centers, room_centers)
     }
 
     fn generate_closest_groups(
-        group_centers: &HashMap<usize, Vector2>,
+        group_centers: &BTreeMap<usize, Vector2>,
         loop_connection_chance: f64,
+        rng_handler: RngHandler,
     ) -> Vec<(usize, usize)> {
         let mut closer_groups = Vec::new();
         let mut visited_links = HashSet::new();
 
-        let mut rng = RngHandler::rng();
+        let mut rng = rng_handler.rng();
 
         // We compute the closest groups to each other
         for (group_id, center) in group_centers.iter() {
@@ -263,6 +299,54 @@ impl MapBuilder {
             }
         }
 
+        // Linking each group to its closest ones can leave clusters of groups
+        // apart from each other, so we join the closest clusters until there is one left
+        let mut group_clusters = group_centers
+            .keys()
+            .map(|group_id| (*group_id, *group_id))
+            .collect::<BTreeMap<_, _>>();
+
+        let join_clusters = |group_clusters: &mut BTreeMap<usize, usize>, a, b| {
+            let (cluster_a, cluster_b) = (group_clusters[&a], group_clusters[&b]);
+
+            for cluster in group_clusters.values_mut() {
+                if *cluster == cluster_b {
+                    *cluster = cluster_a;
+                }
+            }
+        };
+
+        for (group_a, group_b) in closer_groups.iter() {
+            join_clusters(&mut group_clusters, *group_a, *group_b);
+        }
+
+        loop {
+            let mut min_distance = f32::MAX;
+            let mut maybe_closest_link = None;
+
+            for (group_id, center) in group_centers.iter() {
+                for (other_group_id, other_center) in group_centers.iter() {
+                    if group_clusters[group_id] == group_clusters[other_group_id] {
+                        continue;
+                    }
+
+                    let center_distance = center.distance(other_center);
+
+                    if center_distance < min_distance {
+                        min_distance = center_distance;
+                        maybe_closest_link = Some((*group_id, *other_group_id));
+                    }
+                }
+            }
+
+            let Some((group_a, group_b)) = maybe_closest_link else {
+                break;
+            };
+
+            closer_groups.push((group_a, group_b));
+            join_clusters(&mut group_clusters, group_a, group_b);
+        }
+
         closer_groups
     }
 
@@ -320,9 +404,8 @@ impl MapBuilder {
 
 #[cfg(test)]
 mod test {
-    use std::collections::BTreeSet;
-
     use super::*;
+    use crate::constants::TEST_SEED;
 
     #[test]
     fn test_generate_room_groups() {
@@ -333,7 +416,7 @@ mod test {
 
         let mut groups_vec = room_groups
             .values()
-            .map(|set| BTreeSet::from_iter(set))
+            .map(BTreeSet::from_iter)
             .collect::<Vec<_>>();
         groups_vec.sort();
 
@@ -377,7 +460,7 @@ mod test {
 
         let mut groups_vec = room_groups
             .values()
-            .map(|set| BTreeSet::from_iter(set))
+            .map(BTreeSet::from_iter)
             .collect::<Vec<_>>();
         groups_vec.sort();
 
@@ -386,14 +469,15 @@ mod test {
         assert_eq!(groups_vec[2], BTreeSet::from_iter(&[12, 13]));
         assert_eq!(groups_vec[3], BTreeSet::from_iter(&[15]));
 
-        MapBuilder::remove_small_groups(&mut map_region, &mut room_groups);
+        let removed_rooms = MapBuilder::remove_small_groups(&mut map_region, &mut room_groups);
+        assert_eq!(removed_rooms, 1, "Only room F should be removed");
 
         room_groups = MapBuilder::generate_room_groups(&map_region);
         assert!(room_groups.len() == 3, "There should be 4 room groups");
 
         groups_vec = room_groups
             .values()
-            .map(|set| BTreeSet::from_iter(set))
+            .map(BTreeSet::from_iter)
             .collect::<Vec<_>>();
         groups_vec.sort();
 
@@ -404,7 +488,7 @@ mod test {
 
     #[test]
     fn test_get_path_between_rooms() {
-        let mut map_region = MapRegion::new_test_region();
+        let map_region = MapRegion::new_test_region();
 
         let origin_idx = 13; // Room E
         let target_idx = 15; // Room F
@@ -413,12 +497,8 @@ mod test {
 
         let (_, room_centers) = MapBuilder::generate_group_centers(&map_region, &room_groups);
 
-        let path = MapBuilder::get_path_between_rooms(
-            origin_idx,
-            target_idx,
-            &mut map_region,
-            &room_centers,
-        );
+        let path =
+            MapBuilder::get_path_between_rooms(origin_idx, target_idx, &map_region, &room_centers);
 
         assert!(!path.is_empty(), "Path should not be empty");
         assert!(
@@ -446,7 +526,13 @@ mod test {
 
         assert!(room_groups.len() == 4, "There should be 4 room groups");
 
-        MapBuilder::reconnect_room_groups(&mut map_region, &MapBuilderConfig::default());
+        let (orphan_rooms, small_group_rooms) = MapBuilder::reconnect_room_groups(
+            &mut map_region,
+            &MapBuilderConfig::default(),
+            RngHandler::new(TEST_SEED),
+        );
+        assert_eq!(orphan_rooms, 1, "Room F should be removed as an orphan");
+        assert_eq!(small_group_rooms, 0);
 
         let room_groups = MapBuilder::generate_room_groups(&map_region);
 
@@ -0,0 +1,179 @@
+use super::MapBuilder;
+use crate::{
+    algos::RngHandler,
+    types::{Biome, MapRegion, MapStyle, Palette, RegionTheme},
+};
+
+use rand::{Rng, seq::SliceRandom};
+
+const BIOMES: [Biome; 8] = [
+    Biome::Hall,
+    Biome::Crypt,
+    Biome::Garden,
+    Biome::Cavern,
+    Biome::Water,
+    Biome::Lava,
+    Biome::Ice,
+    Biome::Machinery,
+];
+
+const CASTLE_ADJECTIVES: [&str; 12] = [
+    "Marble",
+    "Royal",
+    "Forgotten",
+    "Silent",
+    "Outer",
+    "Inner",
+    "Abandoned",
+    "Cursed",
+    "Grand",
+    "Hidden",
+    "Crimson",
+    "Ancient",
+];
+
+impl Biome {
+    // The places of a castle that can stand for the biome.
+    fn castle_places(&self) -> [&'static str; 3] {
+        match self {
+            Biome::Hall => ["Gallery", "Hall", "Keep"],
+            Biome::Crypt => ["Catacombs", "Chapel", "Crypt"],
+            Biome::Garden => ["Garden", "Courtyard", "Conservatory"],
+            Biome::Cavern => ["Caverns", "Mine", "Grotto"],
+            Biome::Water => ["Reservoir", "Aqueduct", "Cistern"],
+            Biome::Lava => ["Forge", "Furnace", "Smeltery"],
+            Biome::Ice => ["Cellar", "Icehouse", "Glacier"],
+            Biome::Machinery => ["Clock Tower", "Workshop", "Laboratory"],
+        }
+    }
+
+    // The short code used in the name of the sectors of a station.
+    fn sector_code(&self) -> &'static str {
+        match self {
+            Biome::Hall => "SRX",
+            Biome::Crypt => "NOC",
+            Biome::Garden => "TRO",
+            Biome::Cavern => "CAV",
+            Biome::Water => "AQA",
+            Biome::Lava => "PYR",
+            Biome::Ice => "ARC",
+            Biome::Machinery => "MCH",
+        }
+    }
+
+    fn palette(&self) -> Palette {
+        let (room_color, door_color, wall_color) = match self {
+            Biome::Hall => ("#0080ff", "#0080ff", "#c0c0c0"),
+            Biome::Crypt => ("#6040a0", "#8060c0", "#c0c0c0"),
+            Biome::Garden => ("#00a040", "#00e000", "#f8f8f8"),
+            Biome::Cavern => ("#806040", "#a08060", "#f8f8f8"),
+            Biome::Water => ("#0040c0", "#00c8c8", "#f8f8f8"),
+            Biome::Lava => ("#c03000", "#f88000", "#f8f8f8"),
+            Biome::Ice => ("#80c0f8", "#c0e0f8", "#f8f8f8"),
+            Biome::Machinery => ("#606060", "#f8f800", "#c0c0c0"),
+        };
+
+        Palette {
+            room_color: room_color.to_string(),
+            door_color: door_color.to_string(),
+            wall_color: wall_color.to_string(),
+        }
+    }
+}
+
+impl MapBuilder {
+    // Gives every region a theme with a name that no other region of the map uses.
+    //
+    // Castlevania regions are named after a part of a castle, like "Marble Gallery",
+    // while Metroid regions are numbered sectors, like "Sector 3 - PYR".
+    pub(super) fn add_region_themes<'a>(
+        map_regions: impl IntoIterator<Item = &'a mut MapRegion>,
+        style: MapStyle,
+        rng_handler: RngHandler,
+    ) {
+        let mut rng = rng_handler.rng();
+
+        let mut castle_names = BIOMES
+            .iter()
+            .flat_map(|biome| {
+                biome.castle_places().into_iter().flat_map(move |place| {
+                    CASTLE_ADJECTIVES
+                        .iter()
+                        .map(move |adjective| (*biome, format!("{} {}", adjective, place)))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        castle_names.shuffle(&mut rng);
+
+        for (region_idx, map_region) in map_regions.into_iter().enumerate() {
+            let (biome, name) = match style {
+                MapStyle::CastlevaniaSOTN
+                | MapStyle::CastlevaniaAOS
+                | MapStyle::CastlevaniaCOTM
+                | MapStyle::CastlevaniaHOD => {
+                    let (biome, name) = &castle_names[region_idx % castle_names.len()];
+
+                    // Huge maps run out of names, so they get numbered instead
+                    match region_idx / castle_names.len() {
+                        0 => (*biome, name.clone()),
+                        round => (*biome, format!("{} {}", name, round + 1)),
+                    }
+                }
+                MapStyle::MetroidZM | MapStyle::MetroidFS | MapStyle::MetroidSP => {
+                    let biome = BIOMES[rng.random_range(0..BIOMES.len())];
+
+                    (
+                        biome,
+                        format!("Sector {} - {}", region_idx + 1, biome.sector_code()),
+                    )
+                }
+            };
+
+            map_region.themes.push(RegionTheme {
+                rect: map_region.origin_rect,
+                name,
+                biome,
+                palette: biome.palette(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algos::map_builder::test_maps::empty_regions, constants::TEST_SEED};
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_add_region_themes_names_are_unique() {
+        for style in [MapStyle::CastlevaniaSOTN, MapStyle::MetroidFS] {
+            let mut map_regions = empty_regions(300);
+
+            MapBuilder::add_region_themes(&mut map_regions, style, RngHandler::new(TEST_SEED));
+
+            let names = map_regions
+                .iter()
+                .map(|map_region| {
+                    assert_eq!(map_region.themes.len(), 1);
+                    assert_eq!(map_region.themes[0].rect, map_region.origin_rect);
+
+                    map_region.themes[0].name.clone()
+                })
+                .collect::<HashSet<_>>();
+
+            assert_eq!(names.len(), map_regions.len());
+        }
+
+        let mut map_regions = empty_regions(3);
+        MapBuilder::add_region_themes(
+            &mut map_regions,
+            MapStyle::MetroidZM,
+            RngHandler::new(TEST_SEED),
+        );
+
+        assert!(map_regions[2].themes[0].name.starts_with("Sector 3 - "));
+    }
+}
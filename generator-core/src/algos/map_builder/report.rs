@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// The time spent on one of the generation passes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PassDuration {
+    pub name: String,
+    /// For passes that run once per region, this is the sum over all the regions.
+    pub duration: Duration,
+}
+
+/// Summary of the work done while generating a map.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct GenerationReport {
+    /// The seed the map was generated with.
+    pub seed: u64,
+    /// The number of regions produced by the partitioning that contain rooms.
+    pub region_count: usize,
+    /// The number of rooms in the final map.
+    pub room_count: usize,
+    pub rooms_merged: usize,
+    pub rooms_bisected: usize,
+    /// Rooms stamped from a room template.
+    pub template_rooms: usize,
+    /// Single rooms removed because they were not connected to any other room.
+    pub rooms_removed_as_orphans: usize,
+    /// Rooms removed because they belonged to groups too small to be kept.
+    pub rooms_removed_in_small_groups: usize,
+    pub doors_created: usize,
+    /// Doors added to bring the dead ends down to the configured ratio.
+    pub dead_end_doors: usize,
+    /// Doors added to reach the target number of loops.
+    pub loop_doors: usize,
+    /// Doors that need one of the abilities of the map to go through.
+    pub ability_doors: usize,
+    /// Doors locked behind a key, over all the lock tiers.
+    pub locked_doors: usize,
+    /// Rooms holding a boss, which can be fewer than requested on small maps.
+    pub boss_rooms: usize,
+    /// Rooms that are part of the warp network.
+    pub warp_rooms: usize,
+    /// Doors that can only be gone through in one direction.
+    pub one_way_doors: usize,
+    /// Removed rooms brought back as secret rooms, behind a secret door.
+    pub secret_rooms: usize,
+    /// Stairs and elevators between the floors of a layered map.
+    pub floor_links: usize,
+    /// Warp rooms linking the castles of an inverted or dual castle, counted once per pair.
+    pub castle_links: usize,
+    /// The passes in the order they first ran.
+    pub pass_durations: Vec<PassDuration>,
+    pub total_duration: Duration,
+}
+
+impl GenerationReport {
+    pub(crate) fn new(seed: u64) -> Self {
+        GenerationReport {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the time spent on the pass named `name`, if it ran.
+    pub fn pass_duration(&self, name: &str) -> Option<Duration> {
+        self.pass_durations
+            .iter()
+            .find(|pass| pass.name == name)
+            .map(|pass| pass.duration)
+    }
+
+    pub(crate) fn record_duration(&mut self, name: &str, duration: Duration) {
+        match self
+            .pass_durations
+            .iter_mut()
+            .find(|pass| pass.name == name)
+        {
+            Some(pass) => pass.duration += duration,
+            None => self.pass_durations.push(PassDuration {
+                name: name.to_string(),
+                duration,
+            }),
+        }
+    }
+
+    // Adds the counters and durations of a partial report to this one.
+    pub(crate) fn merge_from(&mut self, other: GenerationReport) {
+        self.region_count += other.region_count;
+        self.room_count += other.room_count;
+        self.rooms_merged += other.rooms_merged;
+        self.rooms_bisected += other.rooms_bisected;
+        self.template_rooms += other.template_rooms;
+        self.rooms_removed_as_orphans += other.rooms_removed_as_orphans;
+        self.rooms_removed_in_small_groups += other.rooms_removed_in_small_groups;
+        self.doors_created += other.doors_created;
+        self.dead_end_doors += other.dead_end_doors;
+        self.loop_doors += other.loop_doors;
+        self.ability_doors += other.ability_doors;
+        self.locked_doors += other.locked_doors;
+        self.boss_rooms += other.boss_rooms;
+        self.warp_rooms += other.warp_rooms;
+        self.one_way_doors += other.one_way_doors;
+        self.secret_rooms += other.secret_rooms;
+        self.floor_links += other.floor_links;
+        self.castle_links += other.castle_links;
+
+        for pass in other.pass_durations {
+            self.record_duration(&pass.name, pass.duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge_from_sums_counters_and_durations() {
+        let mut report = GenerationReport::new(3);
+        report.rooms_merged = 2;
+        report.record_duration("a", Duration::from_millis(5));
+
+        let mut other = GenerationReport::new(0);
+        other.rooms_merged = 1;
+        other.doors_created = 4;
+        other.record_duration("b", Duration::from_millis(1));
+        other.record_duration("a", Duration::from_millis(2));
+        other.record_duration("a", Duration::from_millis(3));
+
+        report.merge_from(other);
+
+        assert_eq!(report.seed, 3);
+        assert_eq!(report.rooms_merged, 3);
+        assert_eq!(report.doors_created, 4);
+        assert_eq!(
+            report
+                .pass_durations
+                .iter()
+                .map(|pass| pass.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(report.pass_duration("a"), Some(Duration::from_millis(10)));
+        assert_eq!(report.pass_duration("c"), None);
+    }
+}
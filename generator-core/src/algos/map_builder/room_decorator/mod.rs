@@ -1,11 +1,24 @@
 use super::MapBuilderConfig;
-use crate::types::{Door, MapRegion, MapStyle};
+use crate::{
+    algos::RngHandler,
+    types::{Cell, Door, MapRegion, MapStyle, Room, RoomId, RoomModifier},
+};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use rand::{Rng, seq::SliceRandom};
 
 mod castlevania;
 mod metroid;
 
 pub(super) trait RoomDecorator {
-    fn decorate(&self, map_region: &mut MapRegion, doors: &[Door], config: &MapBuilderConfig);
+    fn decorate(
+        &self,
+        map_region: &mut MapRegion,
+        doors: &[Door],
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    );
 }
 
 pub(super) struct RoomDecoratorFactory;
@@ -23,3 +36,402 @@ impl RoomDecoratorFactory {
         }
     }
 }
+
+// The number of searches run by `DoorGraph::distances_from` on this thread, so
+// the tests can check how often the passes search the graph.
+#[cfg(test)]
+thread_local! {
+    pub(super) static DISTANCE_SEARCHES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// The active rooms of a region, linked through the doors between them.
+pub(super) struct DoorGraph {
+    links: BTreeMap<RoomId, Vec<RoomId>>,
+}
+
+impl DoorGraph {
+    pub(super) fn new(map_region: &MapRegion, doors: &[Door]) -> Self {
+        Self::new_for(map_region, doors, |_| true)
+    }
+
+    // Builds the graph of the active rooms accepted by `keep`, leaving out the
+    // doors that lead to any other room.
+    pub(super) fn new_for(
+        map_region: &MapRegion,
+        doors: &[Door],
+        keep: impl Fn(&Room) -> bool,
+    ) -> Self {
+        let mut links = BTreeMap::<RoomId, Vec<RoomId>>::new();
+        let mut cell_owners = HashMap::<Cell, RoomId>::new();
+
+        for (room_id, room) in map_region.iter_active().filter(|(_, room)| keep(room)) {
+            links.insert(room_id, Vec::new());
+            cell_owners.extend(room.cells.iter().map(|cell| (*cell, room_id)));
+        }
+
+        for door in doors.iter() {
+            if let (Some(from), Some(to)) = (cell_owners.get(&door.from), cell_owners.get(&door.to))
+            {
+                if from != to {
+                    links.entry(*from).or_default().push(*to);
+                    links.entry(*to).or_default().push(*from);
+                }
+            }
+        }
+
+        DoorGraph { links }
+    }
+
+    // Returns the number of doors between `start` and every room reachable from it.
+    pub(super) fn distances_from(&self, start: RoomId) -> BTreeMap<RoomId, usize> {
+        #[cfg(test)]
+        DISTANCE_SEARCHES.with(|searches| searches.set(searches.get() + 1));
+
+        let mut distances = BTreeMap::from([(start, 0)]);
+        let mut rooms_to_visit = VecDeque::from([start]);
+
+        while let Some(room) = rooms_to_visit.pop_front() {
+            let distance = distances[&room];
+
+            for linked_room in self.links.get(&room).into_iter().flatten() {
+                if !distances.contains_key(linked_room) {
+                    distances.insert(*linked_room, distance + 1);
+                    rooms_to_visit.push_back(*linked_room);
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Returns the rooms with a single door, in order of their id.
+    pub(super) fn dead_ends(&self) -> Vec<RoomId> {
+        self.links
+            .iter()
+            .filter(|(_, linked_rooms)| linked_rooms.len() == 1)
+            .map(|(room, _)| *room)
+            .collect()
+    }
+
+    // Returns the number of independent loops, the links that could be removed
+    // without cutting off any room from the ones it can reach.
+    pub(super) fn loop_count(&self) -> usize {
+        let link_count = self.links.values().map(Vec::len).sum::<usize>() / 2;
+
+        let mut reached_rooms = BTreeSet::new();
+        let mut group_count = 0;
+
+        for room in self.links.keys() {
+            if !reached_rooms.contains(room) {
+                reached_rooms.extend(self.distances_from(*room).into_keys());
+                group_count += 1;
+            }
+        }
+
+        link_count + group_count - self.links.len()
+    }
+
+    pub(super) fn door_count(&self, room_id: RoomId) -> usize {
+        self.links.get(&room_id).map_or(0, Vec::len)
+    }
+
+    pub(super) fn is_linked(&self, room_a: RoomId, room_b: RoomId) -> bool {
+        self.links
+            .get(&room_a)
+            .is_some_and(|linked_rooms| linked_rooms.contains(&room_b))
+    }
+}
+
+// The rooms that can hold a save or navigation modifier, in order of preference.
+//
+// Only single cell rooms qualify, and the ones entered through a vertical door
+// are only used when the others are not enough.
+fn candidate_rooms(map_region: &MapRegion, doors: &[Door]) -> [Vec<RoomId>; 2] {
+    let door_map = doors
+        .iter()
+        .map(|door| (&door.from, &door.to))
+        .collect::<HashSet<_>>();
+
+    let mut preferred_rooms = Vec::new();
+    let mut fallback_rooms = Vec::new();
+
+    for (room_id, room) in map_region.iter_active() {
+        if room.cells.len() > 1 || room.modifier.is_some() {
+            continue;
+        }
+
+        let any_neighbour_is_vertical =
+            map_region
+                .iter_active_neighbours(room_id)
+                .any(|neighbour_id| {
+                    let neighbour = map_region.get_active(neighbour_id);
+                    room.get_neighbouring_cells_for(neighbour)
+                        .unwrap()
+                        .iter()
+                        .any(|(from, to, direction)| {
+                            (door_map.contains(&(from, to)) || door_map.contains(&(to, from)))
+                                && !direction.is_horizontal()
+                        })
+                });
+
+        if any_neighbour_is_vertical {
+            fallback_rooms.push(room_id);
+        } else {
+            preferred_rooms.push(room_id);
+        }
+    }
+
+    [preferred_rooms, fallback_rooms]
+}
+
+// Places save and navigation rooms spaced by the number of doors between them.
+//
+// The `navigation_rooms` navigation rooms of every region the rooms were
+// generated in are spread as far apart as possible, and then save rooms are
+// added until every room is at most `max_save_distance` doors away from one.
+// Both are limited by the single cell rooms available.
+fn place_save_and_navigation_rooms(
+    map_region: &mut MapRegion,
+    doors: &[Door],
+    config: &MapBuilderConfig,
+    rng: &mut impl Rng,
+) {
+    let door_graph = DoorGraph::new(map_region, doors);
+
+    // Merged maps keep the region of every room, so each one gets its own stations
+    let regions = map_region
+        .iter_active()
+        .map(|(_, room)| room.region)
+        .collect::<BTreeSet<_>>();
+
+    for region in regions {
+        let mut navigation_distances = door_graph
+            .links
+            .keys()
+            .map(|room_id| (*room_id, usize::MAX))
+            .collect::<BTreeMap<_, _>>();
+        let mut navigation_count = 0;
+
+        for candidates in candidate_rooms(map_region, doors) {
+            let mut candidates = candidates
+                .into_iter()
+                .filter(|room_id| map_region.get_active(*room_id).region == region)
+                .collect::<Vec<_>>();
+            candidates.shuffle(rng);
+
+            while navigation_count < config.navigation_rooms {
+                // Every navigation room goes as far as possible from the previous ones
+                let Some(room_id) = candidates
+                    .iter()
+                    .copied()
+                    .filter(|room_id| map_region.get_active(*room_id).modifier.is_none())
+                    .max_by_key(|room_id| navigation_distances[room_id])
+                else {
+                    break;
+                };
+
+                map_region.get_mut_room(room_id).modifier = Some(RoomModifier::Navigation);
+                navigation_count += 1;
+
+                for (other_id, distance) in door_graph.distances_from(room_id) {
+                    let navigation_distance = navigation_distances.get_mut(&other_id).unwrap();
+                    *navigation_distance = (*navigation_distance).min(distance);
+                }
+            }
+        }
+    }
+
+    let max_save_distance = config.max_save_distance as usize;
+
+    let mut save_distances = door_graph
+        .links
+        .keys()
+        .map(|room_id| (*room_id, usize::MAX))
+        .collect::<BTreeMap<_, _>>();
+
+    for candidates in candidate_rooms(map_region, doors) {
+        let mut candidates = candidates
+            .into_iter()
+            .map(|room_id| (room_id, door_graph.distances_from(room_id)))
+            .collect::<Vec<_>>();
+        candidates.shuffle(rng);
+
+        loop {
+            // The save room that gets the most rooms within reach goes first
+            let best_candidate = candidates
+                .iter()
+                .filter(|(room_id, _)| map_region.get_active(*room_id).modifier.is_none())
+                .map(|(room_id, distances)| {
+                    let covered_rooms = distances
+                        .iter()
+                        .filter(|(other_id, distance)| {
+                            **distance <= max_save_distance
+                                && save_distances[*other_id] > max_save_distance
+                        })
+                        .count();
+
+                    (room_id, distances, covered_rooms)
+                })
+                .max_by_key(|(_, _, covered_rooms)| *covered_rooms);
+
+            let Some((room_id, distances, covered_rooms)) = best_candidate else {
+                break;
+            };
+
+            if covered_rooms == 0 {
+                break;
+            }
+
+            map_region.get_mut_room(*room_id).modifier = Some(RoomModifier::Save);
+
+            for (other_id, distance) in distances.iter() {
+                let save_distance = save_distances.get_mut(other_id).unwrap();
+                *save_distance = (*save_distance).min(*distance);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{algos::map_builder::test_maps::corridor_region, constants::TEST_SEED};
+
+    #[test]
+    fn test_save_rooms_are_within_reach_of_every_room() {
+        let (mut map_region, doors) = corridor_region(12);
+
+        let config = MapBuilderConfig::builder()
+            .max_save_distance(2)
+            .navigation_rooms(2)
+            .build()
+            .unwrap();
+
+        place_save_and_navigation_rooms(
+            &mut map_region,
+            &doors,
+            &config,
+            &mut RngHandler::new(TEST_SEED).rng(),
+        );
+
+        let rooms_with = |modifier| {
+            map_region
+                .iter_active()
+                .filter(|(_, room)| room.modifier == Some(modifier))
+                .map(|(room_id, _)| room_id)
+                .collect::<Vec<_>>()
+        };
+
+        let save_rooms = rooms_with(RoomModifier::Save);
+        let door_graph = DoorGraph::new(&map_region, &doors);
+
+        for (room_id, _) in map_region.iter_active() {
+            let distances = door_graph.distances_from(room_id);
+
+            assert!(save_rooms.iter().any(|save_room| distances[save_room] <= 2));
+        }
+
+        assert!(save_rooms.len() < 6);
+        assert_eq!(rooms_with(RoomModifier::Navigation).len(), 2);
+    }
+
+    #[test]
+    fn test_generated_layouts_space_save_and_navigation_rooms() {
+        for style in [
+            MapStyle::CastlevaniaSOTN,
+            MapStyle::CastlevaniaAOS,
+            MapStyle::CastlevaniaCOTM,
+            MapStyle::CastlevaniaHOD,
+            MapStyle::MetroidZM,
+            MapStyle::MetroidFS,
+            MapStyle::MetroidSP,
+        ] {
+            let config = MapBuilderConfig::from_style(style);
+            let max_save_distance = config.max_save_distance as usize;
+
+            for seed in 0..8 {
+                let layout = crate::generate_layout(48, 32, style, seed).unwrap();
+
+                for map in layout.regions {
+                    let (map_region, doors) = MapRegion::from_map(map);
+                    let door_graph = DoorGraph::new(&map_region, &doors);
+
+                    let rooms_with = |modifier| {
+                        map_region
+                            .iter_active()
+                            .filter(|(_, room)| room.modifier == Some(modifier))
+                            .map(|(room_id, _)| room_id)
+                            .collect::<Vec<_>>()
+                    };
+
+                    let save_rooms = rooms_with(RoomModifier::Save);
+                    let navigation_rooms = rooms_with(RoomModifier::Navigation);
+
+                    // Secret rooms are only brought back once the save rooms are placed
+                    let secret_rooms = rooms_with(RoomModifier::Secret);
+
+                    // A room can only be far from a save room when there is no
+                    // single cell room close enough to hold one
+                    for (room_id, _) in map_region.iter_active() {
+                        let distances = door_graph.distances_from(room_id);
+                        let is_far = |other_id: &RoomId| {
+                            distances
+                                .get(other_id)
+                                .is_none_or(|distance| *distance > max_save_distance)
+                        };
+
+                        if secret_rooms.contains(&room_id) || !save_rooms.iter().all(is_far) {
+                            continue;
+                        }
+
+                        assert!(
+                            map_region.iter_active().all(|(other_id, other)| {
+                                is_far(&other_id)
+                                    || other.cells.len() > 1
+                                    || navigation_rooms.contains(&other_id)
+                                    || secret_rooms.contains(&other_id)
+                            }),
+                            "{:?} with seed {} has no save room close to {}",
+                            style,
+                            seed,
+                            room_id
+                        );
+                    }
+
+                    // Merged maps get their navigation rooms in every region they were made of
+                    let regions = map_region
+                        .iter_active()
+                        .map(|(_, room)| room.region)
+                        .collect::<BTreeSet<_>>();
+
+                    for region in regions {
+                        let region_rooms = map_region
+                            .iter_active()
+                            .filter(|(_, room)| room.region == region)
+                            .collect::<Vec<_>>();
+
+                        let single_cell_rooms = region_rooms
+                            .iter()
+                            .filter(|(_, room)| {
+                                room.cells.len() == 1 && room.modifier != Some(RoomModifier::Secret)
+                            })
+                            .count();
+                        let region_navigation_rooms = region_rooms
+                            .iter()
+                            .filter(|(room_id, _)| navigation_rooms.contains(room_id))
+                            .count();
+
+                        assert_eq!(
+                            region_navigation_rooms,
+                            single_cell_rooms.min(config.navigation_rooms as usize),
+                            "{:?} with seed {} has the wrong navigation room count in region {}",
+                            style,
+                            seed,
+                            region
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use super::{MapBuilder, MapBuilderConfig};
+use crate::{
+    MapStyle,
+    algos::RngHandler,
+    types::{Cell, MapRegion, Room, RoomTemplate},
+};
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use rand::{Rng, seq::IndexedRandom};
+
+/// The room templates stamped into the layout, each one picked with its weight.
+///
+/// Start from [`RoomTemplateLibrary::for_style`] to keep the built-in shapes,
+/// or from [`RoomTemplateLibrary::empty`] to only use your own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomTemplateLibrary {
+    templates: Vec<(RoomTemplate, f64)>,
+}
+
+impl RoomTemplateLibrary {
+    pub const L_HALL: &'static str = "l_hall";
+    pub const T_JUNCTION: &'static str = "t_junction";
+    pub const BOSS_ARENA: &'static str = "boss_arena";
+    pub const VERTICAL_SHAFT: &'static str = "vertical_shaft";
+
+    /// Creates a library without any template.
+    pub fn empty() -> Self {
+        RoomTemplateLibrary {
+            templates: Vec::new(),
+        }
+    }
+
+    /// Returns the built-in templates, weighted by how common each shape is in
+    /// the maps of the given style.
+    pub fn for_style(style: MapStyle) -> Self {
+        let (l_hall, t_junction, boss_arena, vertical_shaft) = match style {
+            MapStyle::CastlevaniaSOTN => (3.0, 2.0, 1.0, 2.0),
+            MapStyle::CastlevaniaAOS => (2.0, 2.0, 1.0, 3.0),
+            MapStyle::CastlevaniaCOTM => (2.0, 1.0, 1.0, 1.0),
+            MapStyle::CastlevaniaHOD => (3.0, 2.0, 1.0, 2.0),
+            MapStyle::MetroidZM => (2.0, 3.0, 1.0, 3.0),
+            MapStyle::MetroidFS => (2.0, 3.0, 1.0, 2.0),
+            MapStyle::MetroidSP => (3.0, 2.0, 2.0, 3.0),
+        };
+
+        let mut library = Self::empty();
+
+        for (name, rows, weight) in [
+            (Self::L_HALL, &["D..", "#..", "##D"][..], l_hall),
+            (Self::T_JUNCTION, &["D#D", ".#.", ".D."][..], t_junction),
+            (Self::BOSS_ARENA, &["####", "D##D", "####"][..], boss_arena),
+            (
+                Self::VERTICAL_SHAFT,
+                &["#", "#", "#", "#", "#"][..],
+                vertical_shaft,
+            ),
+        ] {
+            let template = RoomTemplate::from_pattern(name, rows)
+                .expect("Built-in room templates should be valid");
+
+            library
+                .register(template, weight)
+                .expect("Built-in room templates should have unique names");
+        }
+
+        library
+    }
+
+    /// Adds a template, picked with the given weight relative to the other ones.
+    pub fn register(&mut self, template: RoomTemplate, weight: f64) -> Result<&mut Self> {
+        template.validate()?;
+
+        if !weight.is_finite() || weight <= 0.0 {
+            return Err(anyhow::anyhow!(
+                "The weight of room template '{}' must be greater than zero, got {}",
+                template.name,
+                weight
+            ));
+        }
+
+        if self.contains(&template.name) {
+            return Err(anyhow::anyhow!(
+                "A room template named '{}' is already in the library",
+                template.name
+            ));
+        }
+
+        self.templates.push((template, weight));
+
+        Ok(self)
+    }
+
+    /// Removes the template named `name`, returning it along with its weight.
+    pub fn remove(&mut self, name: &str) -> Option<(RoomTemplate, f64)> {
+        self.templates
+            .iter()
+            .position(|(template, _)| template.name == name)
+            .map(|idx| self.templates.remove(idx))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates
+            .iter()
+            .any(|(template, _)| template.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Returns the templates along with their weights, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&RoomTemplate, f64)> {
+        self.templates
+            .iter()
+            .map(|(template, weight)| (template, *weight))
+    }
+
+    fn choose(&self, rng: &mut impl Rng) -> Option<&RoomTemplate> {
+        self.templates
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .ok()
+            .map(|(template, _)| template)
+    }
+}
+
+impl MapBuilder {
+    // Stamps room templates into the space of the region taken by its removed
+    // rooms, returning how many were stamped.
+    //
+    // A first template is stamped with `config.room_template_prob`, and every
+    // stamp tries another one with the same probability. Templates are placed
+    // next to an active room, so they are not removed as orphans, and take their
+    // cells away from the removed rooms under them.
+    pub(super) fn stamp_room_templates(
+        map_region: &mut MapRegion,
+        templates: &RoomTemplateLibrary,
+        config: &MapBuilderConfig,
+        rng_handler: RngHandler,
+    ) -> usize {
+        if templates.is_empty() || config.room_template_prob == 0.0 {
+            return 0;
+        }
+
+        let mut rng = rng_handler.rng();
+
+        let mut stamped_rooms = 0;
+
+        while rng.random_bool(config.room_template_prob) {
+            let Some(template) = templates.choose(&mut rng) else {
+                break;
+            };
+
+            let spots = Self::template_spots(map_region, template);
+
+            if spots.is_empty() {
+                break;
+            }
+
+            let origin = spots[rng.random_range(0..spots.len())];
+            Self::stamp_room(map_region, template.room_at(origin));
+
+            stamped_rooms += 1;
+        }
+
+        if stamped_rooms > 0 {
+            map_region.compact_buffers();
+        }
+
+        stamped_rooms
+    }
+
+    // Returns the top left corners at which the template only covers removed
+    // rooms, while one of its door anchors is next to a cell of an active room
+    // that can hold a door. Templates without anchors can touch it anywhere.
+    //
+    // Cells that no room holds are never stamped.
+    fn template_spots(map_region: &MapRegion, template: &RoomTemplate) -> Vec<Cell> {
+        let active_door_cells = map_region
+            .iter_active()
+            .flat_map(|(_, room)| room.door_cells().iter().copied())
+            .collect::<HashSet<_>>();
+        let removed_cells = map_region
+            .iter_removed()
+            .flat_map(|(_, room)| room.cells.iter().copied())
+            .collect::<HashSet<_>>();
+
+        let width = template.cells.iter().map(|cell| cell.col + 1).max();
+        let height = template.cells.iter().map(|cell| cell.row + 1).max();
+        let rect = map_region.origin_rect;
+
+        let (Some(width), Some(height)) = (width, height) else {
+            return Vec::new();
+        };
+
+        if width > rect.width || height > rect.height {
+            return Vec::new();
+        }
+
+        let mut spots = Vec::new();
+
+        for row in rect.origin.row..=(rect.origin.row + rect.height - height) {
+            for col in rect.origin.col..=(rect.origin.col + rect.width - width) {
+                let room = template.room_at(Cell::new(col, row));
+
+                let is_free = room.cells.iter().all(|cell| removed_cells.contains(cell));
+                let is_next_to_room = room.door_cells().iter().any(|cell| {
+                    cell.neighbours()
+                        .iter()
+                        .any(|neighbour| active_door_cells.contains(neighbour))
+                });
+
+                if is_free && is_next_to_room {
+                    spots.push(Cell::new(col, row));
+                }
+            }
+        }
+
+        spots
+    }
+
+    // Inserts the room as an active room, and splits the removed rooms it covers
+    // into the parts left around it.
+    fn stamp_room(map_region: &mut MapRegion, room: Room) {
+        let covered_rooms = map_region
+            .iter_removed()
+            .filter(|(_, removed_room)| {
+                removed_room
+                    .cells
+                    .iter()
+                    .any(|cell| room.cells.contains(cell))
+            })
+            .map(|(room_id, _)| room_id)
+            .collect::<Vec<_>>();
+
+        let mut new_rooms = Vec::new();
+
+        for room_id in covered_rooms {
+            let covered_room = map_region.take_removed(room_id);
+
+            for neighbour_id in map_region.take_neighbours(room_id).iter() {
+                map_region.get_mut_neighbours(neighbour_id).remove(room_id);
+            }
+
+            let left_cells = covered_room
+                .cells
+                .into_iter()
+                .filter(|cell| !room.cells.contains(cell))
+                .collect::<Vec<_>>();
+
+            for cells in connected_parts(left_cells) {
+                let part_id = map_region.insert_room(Room {
+                    cells,
+                    modifier: None,
+                    region: covered_room.region,
+                    door_anchors: Vec::new(),
+                    pinned: false,
+                });
+                map_region.mark_removed(part_id);
+
+                new_rooms.push(part_id);
+            }
+        }
+
+        new_rooms.push(map_region.insert_room(room));
+
+        for room_id in new_rooms {
+            map_region.link_adjacent_rooms(room_id);
+        }
+    }
+}
+
+// Splits the cells into groups of cells connected to each other.
+fn connected_parts(mut cells: Vec<Cell>) -> Vec<Vec<Cell>> {
+    let mut parts = Vec::new();
+
+    while let Some(first_cell) = cells.pop() {
+        let mut part = vec![first_cell];
+        let mut cells_to_visit = vec![first_cell];
+
+        while let Some(cell) = cells_to_visit.pop() {
+            for neighbour in cell.neighbours() {
+                if let Some(idx) = cells.iter().position(|other| *other == neighbour) {
+                    cells.swap_remove(idx);
+                    part.push(neighbour);
+                    cells_to_visit.push(neighbour);
+                }
+            }
+        }
+
+        part.sort();
+        parts.push(part);
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::TEST_SEED;
+
+    #[test]
+    fn test_register_rejects_invalid_templates() {
+        let mut library = RoomTemplateLibrary::for_style(MapStyle::CastlevaniaSOTN);
+        assert!(library.contains(RoomTemplateLibrary::BOSS_ARENA));
+
+        let u_hall = RoomTemplate::from_pattern("u_hall", &["D.D", "#.#", "###"]).unwrap();
+        assert!(library.register(u_hall.clone(), 2.0).is_ok());
+        assert!(library.register(u_hall.clone(), 2.0).is_err());
+
+        let mut renamed = u_hall;
+        renamed.name = "other_hall".to_string();
+        assert!(library.register(renamed, 0.0).is_err());
+
+        assert!(RoomTemplate::from_pattern("split", &["#.#"]).is_err());
+        assert!(RoomTemplate::from_pattern("unknown", &["#x#"]).is_err());
+    }
+
+    #[test]
+    fn test_stamp_room_templates_takes_free_space() {
+        let mut map_region = MapRegion::new_test_region();
+
+        let mut templates = RoomTemplateLibrary::empty();
+        templates
+            .register(
+                RoomTemplate::from_pattern("corner", &["D#", ".#"]).unwrap(),
+                1.0,
+            )
+            .unwrap();
+
+        let config = MapBuilderConfig::builder()
+            .room_template_prob(1.0)
+            .build()
+            .unwrap();
+
+        let active_rooms = map_region.iter_active().count();
+
+        let stamped_rooms = MapBuilder::stamp_room_templates(
+            &mut map_region,
+            &templates,
+            &config,
+            RngHandler::new(TEST_SEED),
+        );
+
+        assert!(stamped_rooms > 0);
+        assert_eq!(
+            map_region.iter_active().count(),
+            active_rooms + stamped_rooms
+        );
+
+        // No cell is taken by two rooms, removed or not
+        let mut taken_cells = HashSet::new();
+        for (_, room) in map_region.iter_rooms() {
+            for cell in room.cells.iter() {
+                assert!(taken_cells.insert(*cell));
+            }
+        }
+
+        for (room_id, room) in map_region.iter_active() {
+            if room.door_anchors.is_empty() {
+                continue;
+            }
+
+            assert_eq!(room.cells.len(), 3);
+            assert!(map_region.iter_active_neighbours(room_id).next().is_some());
+
+            // The room is stamped with its anchor next to another room
+            assert!(room.door_anchors.iter().any(|anchor| {
+                map_region.iter_active().any(|(other_id, other)| {
+                    other_id != room_id
+                        && anchor
+                            .neighbours()
+                            .iter()
+                            .any(|neighbour| other.door_cells().contains(neighbour))
+                })
+            }));
+
+            for (neighbour_id, neighbour) in map_region.iter_rooms() {
+                assert_eq!(
+                    room.is_neighbour_of(neighbour),
+                    map_region
+                        .iter_neighbours(room_id)
+                        .any(|id| id == neighbour_id)
+                );
+            }
+        }
+    }
+}
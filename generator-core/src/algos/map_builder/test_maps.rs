@@ -0,0 +1,116 @@
+// Small hand made maps shared by the tests of the build passes.
+
+use crate::types::{Cell, Door, Map, MapRegion, Rect, Room};
+
+use std::collections::HashMap;
+
+fn single_map(origin_rect: Rect, rooms: Vec<Room>, doors: Vec<Door>) -> Vec<Map> {
+    vec![Map {
+        origin_rect,
+        rooms,
+        doors,
+        themes: Vec::new(),
+    }]
+}
+
+// A corridor of single cell rooms along `row`, with a door between every pair of rooms
+pub(super) fn corridor_maps(length: u32, row: u32) -> Vec<Map> {
+    let rooms = (0..length)
+        .map(|col| Room::new_from_rect(Rect::new(col, row, 1, 1)))
+        .collect();
+
+    let doors = (1..length)
+        .map(|col| Door::new(Cell::new(col - 1, row), Cell::new(col, row)))
+        .collect();
+
+    single_map(Rect::new(0, 0, length, row + 1), rooms, doors)
+}
+
+// The corridor of `corridor_maps` as a region, along with its doors
+pub(super) fn corridor_region(length: u32) -> (MapRegion, Vec<Door>) {
+    let mut maps = corridor_maps(length, 0);
+
+    MapRegion::from_map(maps.remove(0))
+}
+
+// A grid of single cell rooms, each one neighbouring the ones around it
+pub(super) fn grid_region(cols: u32, rows: u32) -> MapRegion {
+    let rooms = (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| Rect::new(col, row, 1, 1)))
+        .map(Room::new_from_rect)
+        .collect();
+
+    let mut maps = single_map(Rect::new(0, 0, cols, rows), rooms, Vec::new());
+    let (map_region, _) = MapRegion::from_map(maps.remove(0));
+
+    map_region
+}
+
+// A corridor of single cell rooms with a column of `depth` rooms below each one
+pub(super) fn comb_maps(columns: u32, depth: u32) -> Vec<Map> {
+    let mut rooms = Vec::new();
+    let mut doors = Vec::new();
+
+    for col in 0..columns {
+        for row in 0..=depth {
+            rooms.push(Room::new_from_rect(Rect::new(col, row, 1, 1)));
+
+            if row > 0 {
+                doors.push(Door::new(Cell::new(col, row - 1), Cell::new(col, row)));
+            }
+        }
+
+        if col > 0 {
+            doors.push(Door::new(Cell::new(col - 1, 0), Cell::new(col, 0)));
+        }
+    }
+
+    single_map(Rect::new(0, 0, columns, depth + 1), rooms, doors)
+}
+
+// A ring of four single cell rooms with a dead end room hanging from it
+pub(super) fn ring_maps() -> Vec<Map> {
+    let rooms = [(0, 0), (1, 0), (1, 1), (0, 1), (2, 0)]
+        .into_iter()
+        .map(|(col, row)| Room::new_from_rect(Rect::new(col, row, 1, 1)))
+        .collect();
+
+    let doors = [
+        ((0, 0), (1, 0)),
+        ((1, 0), (1, 1)),
+        ((1, 1), (0, 1)),
+        ((0, 1), (0, 0)),
+        ((1, 0), (2, 0)),
+    ]
+    .into_iter()
+    .map(|((from_col, from_row), (to_col, to_row))| {
+        Door::new(Cell::new(from_col, from_row), Cell::new(to_col, to_row))
+    })
+    .collect();
+
+    single_map(Rect::new(0, 0, 3, 2), rooms, doors)
+}
+
+// Single cell rooms at the given cells of a 4x4 canvas, without any door
+pub(super) fn single_cell_maps(cells: &[Cell]) -> Vec<Map> {
+    let rooms = cells
+        .iter()
+        .map(|cell| Room::new_from_rect(Rect::new(cell.col, cell.row, 1, 1)))
+        .collect();
+
+    single_map(Rect::new(0, 0, 4, 4), rooms, Vec::new())
+}
+
+// Regions without any room, placed side by side
+pub(super) fn empty_regions(count: u32) -> Vec<MapRegion> {
+    (0..count)
+        .map(|idx| {
+            MapRegion::new(
+                Rect::new(idx * 4, 0, 4, 4),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+            )
+        })
+        .collect()
+}
@@ -2,7 +2,7 @@ use super::*;
 use crate::{
     algos::PolygonBuilder,
     constants::{MAP_SIZE_MARGIN, RECT_SIZE_MULTIPLIER},
-    types::{Cell, Door, DoorModifier, Edge, Map, Rect, Room, RoomModifier},
+    types::{Cell, Door, DoorModifier, Edge, Map, Room, RoomModifier},
 };
 
 use svg::{
@@ -20,7 +20,7 @@ pub(super) enum CastlevaniaMapDrawer {
 }
 
 impl MapDrawer for CastlevaniaMapDrawer {
-    fn draw(&self, maps: Vec<Map>, config: &DrawConfig) -> svg::Document {
+    fn draw(&self, maps: &[Map], config: &DrawConfig) -> svg::Document {
         let document_width = (config.canvas_width * RECT_SIZE_MULTIPLIER) + MAP_SIZE_MARGIN;
         let document_height = (config.canvas_height * RECT_SIZE_MULTIPLIER) + MAP_SIZE_MARGIN;
 
@@ -34,74 +34,101 @@ impl MapDrawer for CastlevaniaMapDrawer {
             .set("width", document_width)
             .set("height", document_height);
 
+        // Every second castle shares the palette of the second castle of HOD
         let (room_color, door_color, wall_color) = match self {
+            _ if config.options.second_castle => (DARK_RED, DARK_RED, LIGHT_WHITE),
             CastlevaniaMapDrawer::CastlevaniaSOTN => (LIGHT_BLUE, LIGHT_BLUE, LIGHT_GRAY),
             CastlevaniaMapDrawer::CastlevaniaAOS => (DEEP_BLUE, CYAN_BLUE, LIGHT_WHITE),
             CastlevaniaMapDrawer::CastlevaniaCOTN => (DARK_BLUE, DARK_BLUE, LIGHT_WHITE),
             CastlevaniaMapDrawer::CastlevaniaHOD => (LIME_GREEN, LIME_GREEN, LIGHT_WHITE),
         };
 
-        let merged_map = {
-            let mut map = Map {
-                origin_rect: Rect {
-                    origin: Cell::new(0, 0),
-                    width: config.canvas_width,
-                    height: config.canvas_height,
-                },
-                rooms: vec![],
-                doors: vec![],
-            };
-
-            for map_fragment in maps.into_iter() {
-                map.rooms.extend(map_fragment.rooms);
-                map.doors.extend(map_fragment.doors);
-            }
-
-            map
-        };
-
         let full_door = self == &CastlevaniaMapDrawer::CastlevaniaAOS;
 
-        for room_path in merged_map
-            .rooms
+        // All the regions are drawn over the same canvas
+        let rooms = maps
             .iter()
-            .map(|room| Self::draw_room(room, room_color, wall_color))
-        {
-            document = document.add(room_path);
+            .flat_map(|map| map.rooms.iter())
+            .filter(|room| !is_hidden_room(room, &config.options));
+        let doors = maps
+            .iter()
+            .flat_map(|map| map.doors.iter())
+            .filter(|door| !is_hidden_door(door, &config.options));
+
+        for map in maps.iter() {
+            for room in map
+                .rooms
+                .iter()
+                .filter(|room| !is_hidden_room(room, &config.options))
+            {
+                // The start, boss, stairs, elevator and castle link rooms are filled
+                // as a whole, since they can span many cells
+                let fill_color = match room.modifier {
+                    Some(RoomModifier::Start) if config.options.highlight_room_modifiers => MAGENTA,
+                    Some(RoomModifier::Boss) if config.options.highlight_room_modifiers => ORANGE,
+                    Some(RoomModifier::Stairs) if config.options.highlight_room_modifiers => BROWN,
+                    Some(RoomModifier::Elevator) if config.options.highlight_room_modifiers => CYAN,
+                    Some(RoomModifier::CastleLink(_))
+                        if config.options.highlight_room_modifiers =>
+                    {
+                        CRIMSON
+                    }
+                    _ => region_room_color(map, room, room_color, &config.options),
+                };
+
+                document = document.add(Self::draw_room(room, fill_color, wall_color));
+            }
         }
 
-        for door_path in merged_map
-            .doors
-            .iter()
-            .map(|door| Self::draw_door(door, door_color, full_door))
-        {
+        // Area transitions span the whole wall, like the doors between the areas of SOTN
+        for door_path in doors.map(|door| {
+            if door.area_transition {
+                Self::draw_door(door, GOLD, true)
+            } else {
+                Self::draw_door(door, door_color, full_door)
+            }
+        }) {
             document = document.add(door_path);
         }
 
-        for room in merged_map.rooms.iter() {
-            // We need to overlay a rect for the save and navigation rooms
-            // to avoid clipping artifacts with the doors.
-            if let Some(modifier) = room.modifier {
-                let point = room.cells[0]
-                    .stretched_by(RECT_SIZE_MULTIPLIER)
-                    .offset_by(MAP_SIZE_MARGIN / 2 + STROKE_WIDTH / 2);
-
-                let mut rect = Rectangle::new()
-                    .set("x", point.col)
-                    .set("y", point.row)
-                    .set("width", RECT_SIZE_MULTIPLIER - STROKE_WIDTH)
-                    .set("height", RECT_SIZE_MULTIPLIER - STROKE_WIDTH);
-
-                match modifier {
-                    RoomModifier::Navigation => {
-                        rect = rect.set("fill", YELLOW);
-                        document = document.add(rect);
+        if config.options.highlight_room_modifiers {
+            for room in rooms {
+                // We need to overlay a rect for the save, navigation and warp rooms
+                // to avoid clipping artifacts with the doors.
+                if let Some(modifier) = room.modifier {
+                    let point = room.cells[0]
+                        .stretched_by(RECT_SIZE_MULTIPLIER)
+                        .offset_by(MAP_SIZE_MARGIN / 2 + STROKE_WIDTH / 2);
+
+                    let mut rect = Rectangle::new()
+                        .set("x", point.col)
+                        .set("y", point.row)
+                        .set("width", RECT_SIZE_MULTIPLIER - STROKE_WIDTH)
+                        .set("height", RECT_SIZE_MULTIPLIER - STROKE_WIDTH);
+
+                    match modifier {
+                        RoomModifier::Navigation => {
+                            rect = rect.set("fill", YELLOW);
+                            document = document.add(rect);
+                        }
+                        RoomModifier::Save => {
+                            rect = rect.set("fill", RED);
+                            document = document.add(rect);
+                        }
+                        RoomModifier::Warp => {
+                            rect = rect.set("fill", PURPLE);
+                            document = document.add(rect);
+                        }
+                        _ => {}
                     }
-                    RoomModifier::Save => {
-                        rect = rect.set("fill", RED);
-                        document = document.add(rect);
-                    }
-                    _ => {}
+                }
+            }
+        }
+
+        if config.options.region_labels {
+            for map in maps.iter() {
+                for label in draw_region_labels(map, MAP_SIZE_MARGIN / 2, MAP_SIZE_MARGIN / 2) {
+                    document = document.add(label);
                 }
             }
         }
@@ -117,21 +144,25 @@ impl CastlevaniaMapDrawer {
         let mut vertices_to_visit = valid_vertices.clone();
         let mut vertex_path = Vec::with_capacity(valid_edges.len());
 
-        let mut vertex_stack = vec![*vertices_to_visit.iter().next().unwrap()];
+        // We always start from the smallest vertex and walk towards the smallest
+        // candidate, so the same room always produces the same path.
+        let mut vertex_stack = vec![*vertices_to_visit.iter().min().unwrap()];
 
         while let Some(vertex) = vertex_stack.pop() {
             vertices_to_visit.remove(&vertex);
 
             vertex_path.push(vertex);
 
-            for other_vertex in vertices_to_visit.iter() {
-                if vertex.distance(other_vertex) == 1 {
-                    let edge = Edge::new(vertex, *other_vertex);
-                    if valid_edges.contains(&edge) {
-                        vertex_stack.push(*other_vertex);
-                        break;
-                    }
-                }
+            let next_vertex = vertices_to_visit
+                .iter()
+                .filter(|other_vertex| {
+                    vertex.distance(other_vertex) == 1
+                        && valid_edges.contains(&Edge::new(vertex, **other_vertex))
+                })
+                .min();
+
+            if let Some(next_vertex) = next_vertex {
+                vertex_stack.push(*next_vertex);
             }
         }
 
@@ -176,8 +207,8 @@ impl CastlevaniaMapDrawer {
 
         let line_separation = match door.modifier {
             DoorModifier::Open => 16,
-            DoorModifier::Secret => 16,
-            DoorModifier::Locked => 16,
+            DoorModifier::Secret => SECRET_DOOR_SEPARATION,
+            DoorModifier::Locked(_) => 16,
             DoorModifier::None => 16,
         };
 
@@ -187,7 +218,7 @@ impl CastlevaniaMapDrawer {
             if from.row == to.row {
                 let x = if from.col > to.col { from.col } else { to.col };
 
-                let (from_y, to_y) = if full_door {
+                let (from_y, to_y) = if full_door && door.modifier != DoorModifier::Secret {
                     (
                         from.row + STROKE_WIDTH / 2,
                         from.row + RECT_SIZE_MULTIPLIER - STROKE_WIDTH / 2,
@@ -214,7 +245,7 @@ impl CastlevaniaMapDrawer {
                 // Horizontal door
                 let y = if from.row > to.row { from.row } else { to.row };
 
-                let (from_x, to_x) = if full_door {
+                let (from_x, to_x) = if full_door && door.modifier != DoorModifier::Secret {
                     (
                         from.col + STROKE_WIDTH / 2,
                         from.col + RECT_SIZE_MULTIPLIER - STROKE_WIDTH / 2,
@@ -245,13 +276,8 @@ impl CastlevaniaMapDrawer {
             );
         }
 
-        data = data.close();
-
         let extra_width = if full_door { 0 } else { 8 };
 
-        Path::new()
-            .set("stroke", door_color)
-            .set("stroke-width", STROKE_WIDTH + extra_width)
-            .set("d", data)
+        door_path(data, door, from, to, door_color, STROKE_WIDTH + extra_width)
     }
 }
@@ -1,6 +1,6 @@
 use super::DoorDrawer;
 use crate::{
-    algos::map_drawer::STROKE_WIDTH,
+    algos::map_drawer::{SECRET_DOOR_SEPARATION, STROKE_WIDTH, door_path},
     constants::RECT_SIZE_MULTIPLIER,
     types::{Cell, Door, DoorModifier},
 };
@@ -25,8 +25,8 @@ impl DoorDrawer for SuperMetroidDoorDrawer {
 
         let line_separation = match door.modifier {
             DoorModifier::Open => 16,
-            DoorModifier::Secret => 16,
-            DoorModifier::Locked => 16,
+            DoorModifier::Secret => SECRET_DOOR_SEPARATION,
+            DoorModifier::Locked(_) => 16,
             DoorModifier::None => 16,
         };
 
@@ -76,11 +76,6 @@ impl DoorDrawer for SuperMetroidDoorDrawer {
             );
         }
 
-        data = data.close();
-
-        Path::new()
-            .set("stroke", door_color)
-            .set("stroke-width", STROKE_WIDTH + 8)
-            .set("d", data)
+        door_path(data, door, from, to, door_color, STROKE_WIDTH + 8)
     }
 }
@@ -1,7 +1,11 @@
-use super::{DARK_BLUE, DrawConfig, LIME_GREEN, MapDrawer, RED, STROKE_WIDTH, YELLOW};
+use super::{
+    BROWN, CRIMSON, CYAN, DARK_BLUE, DrawConfig, DrawOptions, LIME_GREEN, MAGENTA, MapDrawer,
+    ORANGE, PURPLE, RED, STROKE_WIDTH, YELLOW, draw_region_labels, is_hidden_door, is_hidden_room,
+    region_room_color,
+};
 use crate::{
     algos::{
-        PolygonBuilder,
+        PolygonBuilder, RngHandler,
         map_drawer::{LIGHT_BLUE, LIGHT_GRAY},
     },
     constants::RECT_SIZE_MULTIPLIER,
@@ -29,8 +33,8 @@ pub(super) enum MetroidMapDrawer {
 }
 
 impl MapDrawer for MetroidMapDrawer {
-    fn draw(&self, maps: Vec<Map>, config: &DrawConfig) -> svg::Document {
-        let (region_matrix, offset_map) = Self::get_regions_matrix(&maps);
+    fn draw(&self, maps: &[Map], config: &DrawConfig) -> svg::Document {
+        let (region_matrix, offset_map) = Self::get_regions_matrix(maps);
 
         event!(
             tracing::Level::DEBUG,
@@ -63,7 +67,9 @@ impl MapDrawer for MetroidMapDrawer {
             .set("width", document_width)
             .set("height", document_height);
 
-        for (paths, polygons) in maps.iter().map(|map| {
+        let rng_handler = RngHandler::new(config.seed);
+
+        for (paths, polygons) in maps.iter().enumerate().map(|(region_idx, map)| {
             let region_origin = map.origin_rect.origin;
             let (region_col_offset, region_row_offset) = offset_map[&region_origin];
 
@@ -71,7 +77,14 @@ impl MapDrawer for MetroidMapDrawer {
             let row_offset = (region_row_offset * REGION_SEPRATION) + (REGION_SEPRATION / 2);
 
             self.draw_region(
-                map, col_offset, row_offset, room_color, door_color, wall_color,
+                map,
+                col_offset,
+                row_offset,
+                room_color,
+                door_color,
+                wall_color,
+                &config.options,
+                rng_handler.derive(region_idx as u64),
             )
         }) {
             for path in paths {
@@ -83,6 +96,20 @@ impl MapDrawer for MetroidMapDrawer {
             }
         }
 
+        // Labels go last, so no room is drawn over them
+        if config.options.region_labels {
+            for map in maps.iter() {
+                let (region_col_offset, region_row_offset) = offset_map[&map.origin_rect.origin];
+
+                let col_offset = (region_col_offset * REGION_SEPRATION) + (REGION_SEPRATION / 2);
+                let row_offset = (region_row_offset * REGION_SEPRATION) + (REGION_SEPRATION / 2);
+
+                for label in draw_region_labels(map, col_offset, row_offset) {
+                    document = document.add(label);
+                }
+            }
+        }
+
         document
     }
 }
@@ -136,6 +163,7 @@ impl MetroidMapDrawer {
         ((cols, rows), region_matrix)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn draw_region(
         &self,
         map: &Map,
@@ -144,20 +172,36 @@ impl MetroidMapDrawer {
         room_color: &str,
         door_color: &str,
         wall_color: &str,
+        options: &DrawOptions,
+        rng_handler: RngHandler,
     ) -> (Vec<Path>, Vec<Polygon>) {
         let mut path_vec = Vec::new();
         let mut polygon_vec = Vec::new();
 
         let connection_drawer = region_connector::RegionConnectorDrawerFactory::drawer_for(self);
 
-        for room in map.rooms.iter() {
+        for (room_idx, room) in map.rooms.iter().enumerate() {
+            if is_hidden_room(room, options) {
+                continue;
+            }
+
             path_vec.push(Self::draw_room(
-                room, col_offset, row_offset, room_color, wall_color,
+                room,
+                col_offset,
+                row_offset,
+                region_room_color(map, room, room_color, options),
+                wall_color,
             ));
 
             if let Some(RoomModifier::RegionConnection(_)) = room.modifier {
                 let (path, door, polygon) = connection_drawer.draw_region_connector(
-                    room, col_offset, row_offset, room_color, wall_color, door_color,
+                    room,
+                    col_offset,
+                    row_offset,
+                    room_color,
+                    wall_color,
+                    door_color,
+                    rng_handler.derive(room_idx as u64),
                 );
                 path_vec.push(path);
                 path_vec.push(door);
@@ -170,6 +214,7 @@ impl MetroidMapDrawer {
         for door_path in map
             .doors
             .iter()
+            .filter(|door| !is_hidden_door(door, options))
             .map(|door| door_drawer.draw_door(door, col_offset, row_offset, door_color))
         {
             path_vec.push(door_path);
@@ -190,21 +235,25 @@ impl MetroidMapDrawer {
         let mut vertices_to_visit = valid_vertices.clone();
         let mut vertex_path = Vec::with_capacity(valid_edges.len());
 
-        let mut vertex_stack = vec![*vertices_to_visit.iter().next().unwrap()];
+        // We always start from the smallest vertex and walk towards the smallest
+        // candidate, so the same room always produces the same path.
+        let mut vertex_stack = vec![*vertices_to_visit.iter().min().unwrap()];
 
         while let Some(vertex) = vertex_stack.pop() {
             vertices_to_visit.remove(&vertex);
 
             vertex_path.push(vertex);
 
-            for other_vertex in vertices_to_visit.iter() {
-                if vertex.distance(other_vertex) == 1 {
-                    let edge = Edge::new(vertex, *other_vertex);
-                    if valid_edges.contains(&edge) {
-                        vertex_stack.push(*other_vertex);
-                        break;
-                    }
-                }
+            let next_vertex = vertices_to_visit
+                .iter()
+                .filter(|other_vertex| {
+                    vertex.distance(other_vertex) == 1
+                        && valid_edges.contains(&Edge::new(vertex, **other_vertex))
+                })
+                .min();
+
+            if let Some(next_vertex) = next_vertex {
+                vertex_stack.push(*next_vertex);
             }
         }
 
@@ -233,6 +282,12 @@ impl MetroidMapDrawer {
             Some(RoomModifier::RegionConnection(Direction::South)) => LIME_GREEN,
             Some(RoomModifier::RegionConnection(Direction::East)) => YELLOW,
             Some(RoomModifier::RegionConnection(Direction::West)) => DARK_BLUE,
+            Some(RoomModifier::Start) => MAGENTA,
+            Some(RoomModifier::Boss) => ORANGE,
+            Some(RoomModifier::Warp) => PURPLE,
+            Some(RoomModifier::Stairs) => BROWN,
+            Some(RoomModifier::Elevator) => CYAN,
+            Some(RoomModifier::CastleLink(_)) => CRIMSON,
             _ => room_color,
         };
 
@@ -1,5 +1,5 @@
 use super::MetroidMapDrawer;
-use crate::types::Room;
+use crate::{algos::RngHandler, types::Room};
 
 use svg::node::element::{Path, Polygon};
 
@@ -8,6 +8,7 @@ mod super_metroid;
 mod zero_mission;
 
 pub(super) trait RegionConnectorDrawer {
+    #[allow(clippy::too_many_arguments)]
     fn draw_region_connector(
         &self,
         room: &Room,
@@ -16,6 +17,7 @@ pub(super) trait RegionConnectorDrawer {
         room_color: &str,
         wall_color: &str,
         door_color: &str,
+        rng_handler: RngHandler,
     ) -> (Path, Path, Polygon);
 }
 
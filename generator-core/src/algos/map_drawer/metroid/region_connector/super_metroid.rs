@@ -20,11 +20,12 @@ impl RegionConnectorDrawer for SuperMetroidRegionConnectorDrawer {
         room_color: &str,
         wall_color: &str,
         door_color: &str,
+        rng_handler: RngHandler,
     ) -> (Path, Path, Polygon) {
         let mut data = Data::new();
         let mut arrow_points = vec![];
 
-        let mut rng = RngHandler::rng();
+        let mut rng = rng_handler.rng();
 
         let (selected_cell, origin_cell, direction) = match room.modifier {
             Some(RoomModifier::RegionConnection(Direction::North)) => {
@@ -1,6 +1,12 @@
-use crate::types::{Map, MapStyle};
+use crate::{
+    constants::RECT_SIZE_MULTIPLIER,
+    types::{Cell, Door, DoorModifier, Map, MapStyle, Room, RoomModifier},
+};
 
-use svg::Document;
+use svg::{
+    Document,
+    node::element::{Path, Text, path::Data},
+};
 
 mod castlevania;
 mod metroid;
@@ -16,16 +22,197 @@ const LIGHT_GRAY: &str = "#c0c0c0";
 
 const RED: &str = "#f80000";
 const YELLOW: &str = "#f8f800";
+const ORANGE: &str = "#f88000";
+const MAGENTA: &str = "#f800f8";
+const PURPLE: &str = "#8000f8";
+const GOLD: &str = "#f8c000";
+const BROWN: &str = "#a06000";
+const CYAN: &str = "#00f8f8";
+const CRIMSON: &str = "#c00040";
+const DARK_RED: &str = "#a00000";
 
 const STROKE_WIDTH: u32 = 12;
+// Secret doors are drawn narrower than the rest when revealed.
+const SECRET_DOOR_SEPARATION: u32 = 20;
+const ONE_WAY_ARROW_SIZE: u32 = 10;
+const LABEL_FONT_SIZE: u32 = 40;
+
+/// Options controlling how a [`MapLayout`](crate::MapLayout) gets rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DrawOptions {
+    /// Whether special rooms, like save, navigation, warp or boss rooms, are highlighted.
+    pub highlight_room_modifiers: bool,
+    /// Whether secret rooms, and the secret doors leading into them, are drawn.
+    pub reveal_secrets: bool,
+    /// Whether the name of every region is drawn over its rooms.
+    pub region_labels: bool,
+    /// Whether rooms are filled with the palette of the region they belong to.
+    pub tint_regions: bool,
+    /// Whether the map is drawn with the palette of the second castle of a
+    /// [`DualCastle`](crate::DualCastle). Only the Castlevania styles have one.
+    pub second_castle: bool,
+}
+
+impl Default for DrawOptions {
+    fn default() -> Self {
+        DrawOptions {
+            highlight_room_modifiers: true,
+            reveal_secrets: false,
+            region_labels: false,
+            tint_regions: false,
+            second_castle: false,
+        }
+    }
+}
 
 pub(crate) struct DrawConfig {
     pub canvas_width: u32,
     pub canvas_height: u32,
+    // Seed for the few random choices made while drawing.
+    pub seed: u64,
+    pub options: DrawOptions,
+}
+
+// Secret rooms and doors are only drawn when the secrets are revealed.
+fn is_hidden_room(room: &Room, options: &DrawOptions) -> bool {
+    !options.reveal_secrets && room.modifier == Some(RoomModifier::Secret)
+}
+
+fn is_hidden_door(door: &Door, options: &DrawOptions) -> bool {
+    !options.reveal_secrets && door.modifier == DoorModifier::Secret
+}
+
+// The colour a room is filled with, which is the one of its region when tinting them.
+fn region_room_color<'a>(
+    map: &'a Map,
+    room: &Room,
+    room_color: &'a str,
+    options: &DrawOptions,
+) -> &'a str {
+    match map.themes.get(room.region) {
+        Some(theme) if options.tint_regions => &theme.palette.room_color,
+        _ => room_color,
+    }
+}
+
+// Draws the name of every region of the map at the centroid of the cells
+// its rooms cover, or at the centre of the region when it has none.
+fn draw_region_labels(map: &Map, col_offset: u32, row_offset: u32) -> Vec<Text> {
+    map.themes
+        .iter()
+        .map(|theme| {
+            let rect_end = Cell::new(
+                theme.rect.origin.col + theme.rect.width,
+                theme.rect.origin.row + theme.rect.height,
+            );
+
+            let cells = map
+                .rooms
+                .iter()
+                .flat_map(|room| room.cells.iter())
+                .filter(|cell| {
+                    (theme.rect.origin.col..rect_end.col).contains(&cell.col)
+                        && (theme.rect.origin.row..rect_end.row).contains(&cell.row)
+                })
+                .collect::<Vec<_>>();
+
+            let (col, row) = if cells.is_empty() {
+                (
+                    (theme.rect.origin.col + rect_end.col) as f32 / 2.0,
+                    (theme.rect.origin.row + rect_end.row) as f32 / 2.0,
+                )
+            } else {
+                let cell_count = cells.len() as f32;
+                (
+                    cells.iter().map(|cell| cell.col as f32 + 0.5).sum::<f32>() / cell_count,
+                    cells.iter().map(|cell| cell.row as f32 + 0.5).sum::<f32>() / cell_count,
+                )
+            };
+
+            Text::new(theme.name.as_str())
+                .set("x", col * RECT_SIZE_MULTIPLIER as f32 + col_offset as f32)
+                .set("y", row * RECT_SIZE_MULTIPLIER as f32 + row_offset as f32)
+                .set("text-anchor", "middle")
+                .set("dominant-baseline", "middle")
+                .set("font-family", "sans-serif")
+                .set("font-size", LABEL_FONT_SIZE)
+                .set("fill", LIGHT_WHITE)
+                .set("stroke", "#000000")
+                .set("stroke-width", STROKE_WIDTH / 2)
+                .set("paint-order", "stroke")
+        })
+        .collect()
 }
 
 pub(crate) trait MapDrawer {
-    fn draw(&self, maps: Vec<Map>, config: &DrawConfig) -> Document;
+    fn draw(&self, maps: &[Map], config: &DrawConfig) -> Document;
+}
+
+// Closes the path of a door and strokes it, adding a filled arrow when the
+// door is one-way. Both cells are expected to be already stretched to the canvas.
+fn door_path(
+    mut data: Data,
+    door: &Door,
+    from: Cell,
+    to: Cell,
+    door_color: &str,
+    stroke_width: u32,
+) -> Path {
+    if door.one_way {
+        data = add_one_way_arrow(data, from, to);
+    }
+
+    let path = Path::new()
+        .set("stroke", door_color)
+        .set("stroke-width", stroke_width)
+        .set("d", data.close());
+
+    if door.one_way {
+        path.set("fill", door_color)
+    } else {
+        path
+    }
+}
+
+// Adds an arrow in the middle of a one-way door, pointing into the cell it
+// leads to. Both cells are expected to be already stretched to the canvas.
+fn add_one_way_arrow(data: Data, from: Cell, to: Cell) -> Data {
+    let half_cell = RECT_SIZE_MULTIPLIER / 2;
+
+    let (tip, left, right) = if from.row == to.row {
+        let x = from.col.max(to.col);
+        let y = from.row + half_cell;
+        let tip_x = if to.col > from.col {
+            x + ONE_WAY_ARROW_SIZE
+        } else {
+            x - ONE_WAY_ARROW_SIZE
+        };
+
+        (
+            (tip_x, y),
+            (x, y - ONE_WAY_ARROW_SIZE),
+            (x, y + ONE_WAY_ARROW_SIZE),
+        )
+    } else {
+        let x = from.col + half_cell;
+        let y = from.row.max(to.row);
+        let tip_y = if to.row > from.row {
+            y + ONE_WAY_ARROW_SIZE
+        } else {
+            y - ONE_WAY_ARROW_SIZE
+        };
+
+        (
+            (x, tip_y),
+            (x - ONE_WAY_ARROW_SIZE, y),
+            (x + ONE_WAY_ARROW_SIZE, y),
+        )
+    };
+
+    data.move_to::<(u32, u32)>(left)
+        .line_to::<(u32, u32)>(tip)
+        .line_to::<(u32, u32)>(right)
 }
 
 pub(crate) struct MapDrawerFactory;
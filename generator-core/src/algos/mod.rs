@@ -1,25 +1,103 @@
+mod layout_validator;
 mod map_builder;
 mod map_drawer;
 mod polygon_builder;
+mod progression;
+mod room_graph;
 
-pub(crate) use map_builder::{MapBuilder, MapBuilderConfig};
+pub use layout_validator::{Violation, validate, validate_dual_castle};
+pub(crate) use map_builder::MapBuilder;
+pub use map_builder::{
+    BinarySpacePartitioningConfig, BinarySpacePartitioningConfigBuilder, BuildPass, BuildPipeline,
+    ConfigFormat, GenerationReport, LoopTargetScope, MapBuilderConfig, MapBuilderConfigBuilder,
+    PassContext, PassDuration, RoomTemplateLibrary,
+};
+pub use map_drawer::DrawOptions;
 pub(crate) use map_drawer::{DrawConfig, MapDrawer, MapDrawerFactory};
 pub(crate) use polygon_builder::PolygonBuilder;
+pub use progression::{ProgressionSolution, ProgressionSphere, solve_progression};
+pub(crate) use room_graph::warp_links;
 
-pub(crate) struct RngHandler;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Handles the creation of the random streams used while generating a map.
+///
+/// Every region and every pass derives its own stream from the root seed,
+/// so the output only depends on the seed and not on the order in which
+/// the work gets scheduled across threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RngHandler {
+    seed: u64,
+}
 
 impl RngHandler {
-    #[cfg(not(test))]
-    pub fn rng() -> impl rand::Rng {
-        rand::rng()
+    pub fn new(seed: u64) -> Self {
+        RngHandler { seed }
+    }
+
+    // Derives a new independent handler for the given stream identifier.
+    pub fn derive(&self, stream: u64) -> Self {
+        RngHandler {
+            seed: Self::mix(self.seed ^ Self::mix(stream.wrapping_add(1))),
+        }
+    }
+
+    // Derives a new independent handler for the given stream name.
+    pub fn derive_named(&self, name: &str) -> Self {
+        // FNV-1a, so the stream does not depend on the hasher of the platform
+        let stream = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+        self.derive(stream)
+    }
+
+    pub fn rng(&self) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.seed)
+    }
+
+    // SplitMix64 finalizer, used to spread the bits of the derived seeds.
+    fn mix(value: u64) -> u64 {
+        let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::Rng;
+
+    #[test]
+    fn test_rng_handler_is_reproducible() {
+        let handler = RngHandler::new(42);
+
+        let values_a = (0..8)
+            .map(|_| handler.rng().random::<u64>())
+            .collect::<Vec<_>>();
+        let values_b = (0..8)
+            .map(|_| RngHandler::new(42).rng().random::<u64>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(values_a, values_b);
     }
 
-    #[cfg(test)]
-    pub fn rng() -> impl rand::Rng {
-        use crate::constants::TEST_RANDOM_INCREMENT;
-        use crate::constants::TEST_RANDOM_INITIAL;
-        use rand::rngs::mock::StepRng;
+    #[test]
+    fn test_rng_handler_derived_streams_differ() {
+        let handler = RngHandler::new(42);
 
-        StepRng::new(TEST_RANDOM_INITIAL, TEST_RANDOM_INCREMENT)
+        assert_eq!(handler.derive(3), handler.derive(3));
+        assert_ne!(handler.derive(3), handler.derive(4));
+        assert_ne!(handler.derive(0), handler);
+        assert_eq!(handler.derive_named("a"), handler.derive_named("a"));
+        assert_ne!(handler.derive_named("a"), handler.derive_named("b"));
+        assert_ne!(
+            handler.derive(1).rng().random::<u64>(),
+            handler.derive(2).rng().random::<u64>()
+        );
     }
 }
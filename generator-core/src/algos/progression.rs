@@ -0,0 +1,226 @@
+use super::room_graph::{RoomGraph, RoomLink, start_room};
+use crate::types::{
+    Ability, AbilitySet, Door, DoorModifier, Map, MapLayout, RoomModifier, RoomRef,
+};
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+/// The rooms that become reachable once the pickups of the previous spheres are collected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProgressionSphere {
+    pub rooms: Vec<RoomRef>,
+    /// The tiers of the keys found in the rooms of this sphere.
+    pub keys: Vec<u8>,
+    /// The abilities found in the rooms of this sphere.
+    pub abilities: Vec<Ability>,
+}
+
+/// The result of playing through a layout from its start room.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProgressionSolution {
+    pub start: RoomRef,
+    /// The first sphere holds the rooms reachable without any key.
+    pub spheres: Vec<ProgressionSphere>,
+    /// The rooms that cannot be reached with everything that can be collected.
+    pub unreachable: Vec<RoomRef>,
+}
+
+impl ProgressionSolution {
+    /// Whether every room of the layout can be reached from the start room.
+    pub fn is_completable(&self) -> bool {
+        self.unreachable.is_empty()
+    }
+}
+
+/// Plays through the layout from its start room, collecting every key and ability
+/// as soon as it can be reached and opening the doors that need it.
+///
+/// Returns `None` if the layout has no rooms.
+pub fn solve_progression(layout: &MapLayout) -> Option<ProgressionSolution> {
+    solve_regions(&layout.regions)
+}
+
+// The keys and abilities collected so far.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Inventory {
+    pub(crate) keys: BTreeSet<u8>,
+    pub(crate) abilities: AbilitySet,
+}
+
+impl Inventory {
+    pub(crate) fn can_open(&self, door: &Door) -> bool {
+        let has_key = match door.modifier {
+            DoorModifier::Locked(tier) => self.keys.contains(&tier),
+            _ => true,
+        };
+
+        let has_ability = door
+            .requirement
+            .is_none_or(|ability| self.abilities.contains(ability));
+
+        has_key && has_ability
+    }
+
+    pub(crate) fn can_cross(&self, regions: &[Map], link: RoomLink) -> bool {
+        match link {
+            RoomLink::Door { region, door } => self.can_open(&regions[region].doors[door]),
+            RoomLink::Region | RoomLink::Castle => true,
+            // Warping never reaches a room that could not be reached before
+            RoomLink::Warp => false,
+        }
+    }
+}
+
+pub(crate) fn solve_regions(regions: &[Map]) -> Option<ProgressionSolution> {
+    let start = start_room(regions)?;
+
+    let room_graph = RoomGraph::new(regions);
+
+    let mut inventory = Inventory::default();
+    let mut reached_rooms = BTreeSet::new();
+    let mut spheres = Vec::new();
+
+    loop {
+        let distances = room_graph.distances_from(start, |link| inventory.can_cross(regions, link));
+
+        let new_rooms = distances
+            .into_keys()
+            .filter(|room| !reached_rooms.contains(room))
+            .collect::<Vec<_>>();
+
+        if new_rooms.is_empty() {
+            break;
+        }
+
+        let mut keys = Vec::new();
+        let mut abilities = Vec::new();
+
+        for room in new_rooms.iter() {
+            match regions[room.region].rooms[room.room].modifier {
+                Some(RoomModifier::Key(tier)) => keys.push(tier),
+                Some(RoomModifier::Item(Some(ability))) => abilities.push(ability),
+                _ => {}
+            }
+        }
+
+        inventory.keys.extend(keys.iter().copied());
+        abilities
+            .iter()
+            .for_each(|ability| inventory.abilities.insert(*ability));
+        reached_rooms.extend(new_rooms.iter().copied());
+
+        spheres.push(ProgressionSphere {
+            rooms: new_rooms,
+            keys,
+            abilities,
+        });
+    }
+
+    let unreachable = room_graph
+        .iter_rooms()
+        .filter(|room| !reached_rooms.contains(room))
+        .collect();
+
+    Some(ProgressionSolution {
+        start,
+        spheres,
+        unreachable,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{Cell, Rect, Room};
+
+    // Three rooms in a row, the last one behind a locked door
+    fn locked_layout(key_room: usize) -> MapLayout {
+        let mut rooms = (0..3)
+            .map(|col| Room::new_from_rect(Rect::new(col, 0, 1, 1)))
+            .collect::<Vec<_>>();
+        rooms[key_room].modifier = Some(RoomModifier::Key(0));
+
+        let mut locked_door = Door::new(Cell::new(1, 0), Cell::new(2, 0));
+        locked_door.modifier = DoorModifier::Locked(0);
+
+        MapLayout {
+            columns: 3,
+            rows: 1,
+            seed: 0,
+            regions: vec![Map {
+                origin_rect: Rect::new(0, 0, 3, 1),
+                rooms,
+                doors: vec![Door::new(Cell::new(0, 0), Cell::new(1, 0)), locked_door],
+                themes: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_solve_progression_collects_keys_by_sphere() {
+        let solution = solve_progression(&locked_layout(1)).unwrap();
+
+        let room = |room| RoomRef { region: 0, room };
+
+        assert!(solution.is_completable());
+        assert_eq!(solution.start, room(0));
+        assert_eq!(
+            solution.spheres,
+            vec![
+                ProgressionSphere {
+                    rooms: vec![room(0), room(1)],
+                    keys: vec![0],
+                    abilities: vec![],
+                },
+                ProgressionSphere {
+                    rooms: vec![room(2)],
+                    keys: vec![],
+                    abilities: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_solve_progression_detects_key_behind_its_lock() {
+        let solution = solve_progression(&locked_layout(2)).unwrap();
+
+        assert!(!solution.is_completable());
+        assert_eq!(solution.spheres.len(), 1);
+        assert_eq!(solution.unreachable, vec![RoomRef { region: 0, room: 2 }]);
+    }
+
+    #[test]
+    fn test_solve_progression_requires_abilities() {
+        let mut layout = locked_layout(1);
+
+        // The key room now needs the morph ball found in the start room
+        layout.regions[0].doors[0].requirement = Some(Ability::MorphBall);
+        layout.regions[0].rooms[0].modifier = Some(RoomModifier::Item(Some(Ability::MorphBall)));
+
+        let solution = solve_progression(&layout).unwrap();
+
+        assert!(solution.is_completable());
+        assert_eq!(
+            solution
+                .spheres
+                .iter()
+                .map(|sphere| (
+                    sphere.rooms.len(),
+                    sphere.keys.clone(),
+                    sphere.abilities.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, vec![], vec![Ability::MorphBall]),
+                (1, vec![0], vec![]),
+                (1, vec![], vec![]),
+            ]
+        );
+
+        layout.regions[0].rooms[0].modifier = None;
+        assert!(!solve_progression(&layout).unwrap().is_completable());
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    constants::DIRECTIONS,
+    types::{Cell, Map, RoomModifier, RoomRef},
+};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// How two rooms of a [`RoomGraph`] are linked together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RoomLink {
+    /// The door at the given index of the doors of `region`.
+    Door { region: usize, door: usize },
+    /// A pair of matching region connections between neighbouring regions.
+    Region,
+    /// Two warp rooms, which only take the player to warp rooms visited before.
+    Warp,
+    /// The rooms with the same link in both castles of a dual castle.
+    Castle,
+}
+
+/// The rooms of a set of regions, linked through their doors and region connections.
+///
+/// Doors that do not join two different rooms are left out, and one-way doors
+/// only link the room they lead out of to the room they lead into.
+pub(crate) struct RoomGraph {
+    links: BTreeMap<RoomRef, Vec<(RoomRef, RoomLink)>>,
+    incoming_links: BTreeMap<RoomRef, Vec<(RoomRef, RoomLink)>>,
+}
+
+impl RoomGraph {
+    pub(crate) fn new(regions: &[Map]) -> Self {
+        let mut links = BTreeMap::<RoomRef, Vec<(RoomRef, RoomLink)>>::new();
+
+        for room in iter_room_refs(regions) {
+            links.insert(room, Vec::new());
+        }
+
+        let mut add_link = |from: RoomRef, to: RoomRef, link: RoomLink| {
+            links.entry(from).or_default().push((to, link));
+        };
+
+        let cell_owners = cell_owners(regions);
+
+        for (region_idx, region) in regions.iter().enumerate() {
+            for (door_idx, door) in region.doors.iter().enumerate() {
+                if door.from.is_neighbour_of(&door.to).is_none() {
+                    continue;
+                }
+
+                if let (Some(from), Some(to)) =
+                    (cell_owners.get(&door.from), cell_owners.get(&door.to))
+                {
+                    if from == to {
+                        continue;
+                    }
+
+                    let link = RoomLink::Door {
+                        region: region_idx,
+                        door: door_idx,
+                    };
+
+                    add_link(*from, *to, link);
+                    if !door.one_way {
+                        add_link(*to, *from, link);
+                    }
+                }
+            }
+        }
+
+        let (region_links, _) = region_connections(regions);
+
+        for (from, to) in region_links {
+            add_link(from, to, RoomLink::Region);
+            add_link(to, from, RoomLink::Region);
+        }
+
+        for (from, to) in warp_links(regions) {
+            add_link(from, to, RoomLink::Warp);
+            add_link(to, from, RoomLink::Warp);
+        }
+
+        for (from, to) in castle_links(regions) {
+            add_link(from, to, RoomLink::Castle);
+            add_link(to, from, RoomLink::Castle);
+        }
+
+        let mut incoming_links = links
+            .keys()
+            .map(|room| (*room, Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+
+        for (from, room_links) in links.iter() {
+            for (to, link) in room_links.iter() {
+                incoming_links.entry(*to).or_default().push((*from, *link));
+            }
+        }
+
+        RoomGraph {
+            links,
+            incoming_links,
+        }
+    }
+
+    pub(crate) fn iter_rooms(&self) -> impl Iterator<Item = RoomRef> + '_ {
+        self.links.keys().copied()
+    }
+
+    pub(crate) fn iter_links(
+        &self,
+        room: RoomRef,
+    ) -> impl Iterator<Item = (RoomRef, RoomLink)> + '_ {
+        self.links.get(&room).into_iter().flatten().copied()
+    }
+
+    /// Iterates over the links that lead into `room`, together with the room
+    /// they come from.
+    pub(crate) fn iter_incoming_links(
+        &self,
+        room: RoomRef,
+    ) -> impl Iterator<Item = (RoomRef, RoomLink)> + '_ {
+        self.incoming_links
+            .get(&room)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// Iterates over the links going out of `room` and the ones coming into it.
+    pub(crate) fn iter_all_links(
+        &self,
+        room: RoomRef,
+    ) -> impl Iterator<Item = (RoomRef, RoomLink)> + '_ {
+        self.iter_links(room).chain(self.iter_incoming_links(room))
+    }
+
+    /// Returns the door distance from every room that can reach `target` to it,
+    /// only going through the links accepted by `can_cross`.
+    pub(crate) fn distances_to(
+        &self,
+        target: RoomRef,
+        can_cross: impl Fn(RoomLink) -> bool,
+    ) -> BTreeMap<RoomRef, usize> {
+        Self::search(&self.incoming_links, target, can_cross).0
+    }
+
+    /// Returns the door distance from `start` to every room reachable from it,
+    /// only going through the links accepted by `can_cross`.
+    pub(crate) fn distances_from(
+        &self,
+        start: RoomRef,
+        can_cross: impl Fn(RoomLink) -> bool,
+    ) -> BTreeMap<RoomRef, usize> {
+        self.search_from(start, can_cross).0
+    }
+
+    /// Like [`RoomGraph::distances_from`], also returning the room every reached
+    /// room was first entered from, which forms a shortest path tree.
+    pub(crate) fn search_from(
+        &self,
+        start: RoomRef,
+        can_cross: impl Fn(RoomLink) -> bool,
+    ) -> (BTreeMap<RoomRef, usize>, BTreeMap<RoomRef, RoomRef>) {
+        Self::search(&self.links, start, can_cross)
+    }
+
+    fn search(
+        links: &BTreeMap<RoomRef, Vec<(RoomRef, RoomLink)>>,
+        start: RoomRef,
+        can_cross: impl Fn(RoomLink) -> bool,
+    ) -> (BTreeMap<RoomRef, usize>, BTreeMap<RoomRef, RoomRef>) {
+        let mut distances = BTreeMap::new();
+        let mut parents = BTreeMap::new();
+
+        distances.insert(start, 0);
+
+        let mut rooms_to_visit = VecDeque::from([start]);
+
+        while let Some(room) = rooms_to_visit.pop_front() {
+            let distance = distances[&room];
+
+            for (linked_room, link) in links.get(&room).into_iter().flatten().copied() {
+                if distances.contains_key(&linked_room) || !can_cross(link) {
+                    continue;
+                }
+
+                distances.insert(linked_room, distance + 1);
+                parents.insert(linked_room, room);
+                rooms_to_visit.push_back(linked_room);
+            }
+        }
+
+        (distances, parents)
+    }
+}
+
+pub(crate) fn iter_room_refs(regions: &[Map]) -> impl Iterator<Item = RoomRef> + '_ {
+    regions.iter().enumerate().flat_map(|(region_idx, region)| {
+        (0..region.rooms.len()).map(move |room_idx| RoomRef {
+            region: region_idx,
+            room: room_idx,
+        })
+    })
+}
+
+/// The room progression starts from, the start room of the layout or its
+/// first room if it has none.
+pub(crate) fn start_room(regions: &[Map]) -> Option<RoomRef> {
+    iter_room_refs(regions)
+        .find(|room| regions[room.region].rooms[room.room].modifier == Some(RoomModifier::Start))
+        .or_else(|| iter_room_refs(regions).next())
+}
+
+/// Pairs every warp room with all the warp rooms after it.
+pub(crate) fn warp_links(regions: &[Map]) -> Vec<(RoomRef, RoomRef)> {
+    let warp_rooms = iter_room_refs(regions)
+        .filter(|room| regions[room.region].rooms[room.room].modifier == Some(RoomModifier::Warp))
+        .collect::<Vec<_>>();
+
+    warp_rooms
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, from)| warp_rooms[idx + 1..].iter().map(|to| (*from, *to)))
+        .collect()
+}
+
+/// Pairs the rooms that share the same castle link.
+pub(crate) fn castle_links(regions: &[Map]) -> Vec<(RoomRef, RoomRef)> {
+    let mut castle_rooms = BTreeMap::<u8, Vec<RoomRef>>::new();
+
+    for room in iter_room_refs(regions) {
+        if let Some(RoomModifier::CastleLink(link)) = regions[room.region].rooms[room.room].modifier
+        {
+            castle_rooms.entry(link).or_default().push(room);
+        }
+    }
+
+    castle_rooms
+        .into_values()
+        .flat_map(|rooms| {
+            rooms
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, from)| rooms[idx + 1..].iter().map(|to| (*from, *to)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Maps every cell to the first room that contains it.
+pub(crate) fn cell_owners(regions: &[Map]) -> HashMap<Cell, RoomRef> {
+    let mut cell_owners = HashMap::new();
+
+    for room_ref in iter_room_refs(regions) {
+        for cell in regions[room_ref.region].rooms[room_ref.room].cells.iter() {
+            cell_owners.entry(*cell).or_insert(room_ref);
+        }
+    }
+
+    cell_owners
+}
+
+/// Pairs the rooms of neighbouring regions that have matching region connections.
+///
+/// Also returns the rooms with a region connection that has no match.
+pub(crate) fn region_connections(regions: &[Map]) -> (Vec<(RoomRef, RoomRef)>, Vec<RoomRef>) {
+    let mut connections = HashMap::<(usize, _), Vec<RoomRef>>::new();
+
+    for room_ref in iter_room_refs(regions) {
+        if let Some(RoomModifier::RegionConnection(direction)) =
+            regions[room_ref.region].rooms[room_ref.room].modifier
+        {
+            connections
+                .entry((room_ref.region, direction))
+                .or_default()
+                .push(room_ref);
+        }
+    }
+
+    let mut links = Vec::new();
+    let mut unmatched_rooms = Vec::new();
+
+    for (region_idx, direction) in (0..regions.len())
+        .flat_map(|region_idx| DIRECTIONS.map(|direction| (region_idx, direction)))
+    {
+        let Some(rooms) = connections.get(&(region_idx, direction)) else {
+            continue;
+        };
+
+        let origin_rect = &regions[region_idx].origin_rect;
+
+        let matching_room = regions
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| origin_rect.is_neighbour_of(&other.origin_rect) == Some(direction))
+            .find_map(|(other_idx, _)| {
+                connections
+                    .get(&(other_idx, direction.reverse()))
+                    .and_then(|other_rooms| other_rooms.first())
+            });
+
+        match matching_room {
+            Some(other_room) => {
+                links.extend(rooms.iter().map(|room| (*room, *other_room)));
+            }
+            None => unmatched_rooms.extend(rooms.iter().copied()),
+        }
+    }
+
+    (links, unmatched_rooms)
+}
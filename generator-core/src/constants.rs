@@ -3,6 +3,9 @@ use crate::types::Direction;
 pub(crate) const MIN_RECT_WIDTH: u32 = 4;
 pub(crate) const MIN_RECT_HEIGHT: u32 = 4;
 
+pub(crate) const MAX_MAP_COLUMNS: u32 = 512;
+pub(crate) const MAX_MAP_ROWS: u32 = 512;
+
 pub(crate) const RECT_SIZE_MULTIPLIER: u32 = 48;
 pub(crate) const REGION_SPLIT_FACTOR: u32 = 684;
 
@@ -10,10 +13,20 @@ pub(crate) const MAP_SIZE_MARGIN: u32 = 96;
 
 pub(crate) const MIN_BISECT_SIZE: usize = 5;
 
+// Bump whenever the serialized layout format changes
+pub(crate) const LAYOUT_SCHEMA_VERSION: u32 = 14;
+
+pub(crate) const MAX_LOCK_TIERS: u8 = 8;
+pub(crate) const MIN_GATED_AREA_ROOMS: usize = 3;
+
+pub(crate) const MAX_BOSS_ROOMS: u8 = 8;
+pub(crate) const MAX_WARP_ROOMS: u8 = 16;
+pub(crate) const MAX_FLOORS: u8 = 8;
+pub(crate) const MAX_CASTLE_LINKS: u8 = 16;
+pub(crate) const MAX_DUAL_CASTLE_ATTEMPTS: u64 = 8;
+
 #[cfg(test)]
-pub(crate) const TEST_RANDOM_INITIAL: u64 = 13;
-#[cfg(test)]
-pub(crate) const TEST_RANDOM_INCREMENT: u64 = 3;
+pub(crate) const TEST_SEED: u64 = 13;
 
 pub(crate) const DIRECTIONS: [Direction; 4] = [
     Direction::North,
@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+/// The reasons why a map could not be generated.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum GenerateError {
+    #[error(
+        "map dimensions {columns}x{rows} are too small, the minimum is {min_columns}x{min_rows}"
+    )]
+    DimensionsTooSmall {
+        columns: u32,
+        rows: u32,
+        min_columns: u32,
+        min_rows: u32,
+    },
+    #[error(
+        "map dimensions {columns}x{rows} are too large, the maximum is {max_columns}x{max_rows}"
+    )]
+    DimensionsTooLarge {
+        columns: u32,
+        rows: u32,
+        max_columns: u32,
+        max_rows: u32,
+    },
+    #[error("the generated map does not contain any room")]
+    EmptyLayout,
+    #[error("invalid generation config: {0}")]
+    InvalidConfig(String),
+    #[error("invalid pinned room {index}: {reason}")]
+    InvalidPinnedRoom { index: usize, reason: String },
+    #[error("no gate of the first castle could get its pickup inside the second castle")]
+    UnlinkedCastles,
+}
@@ -1,20 +1,1572 @@
 mod algos;
 mod constants;
+mod error;
 mod types;
 
-pub use types::MapStyle;
+pub use algos::{
+    BinarySpacePartitioningConfig, BinarySpacePartitioningConfigBuilder, BuildPass, BuildPipeline,
+    ConfigFormat, DrawOptions, GenerationReport, LoopTargetScope, MapBuilderConfig,
+    MapBuilderConfigBuilder, PassContext, PassDuration, ProgressionSolution, ProgressionSphere,
+    RoomTemplateLibrary, Violation, solve_progression, validate, validate_dual_castle,
+};
+pub use error::GenerateError;
+pub use types::{
+    Ability, AbilitySet, Biome, Cell, CellMask, Direction, Door, DoorModifier, DualCastle,
+    FloorLink, FloorLinkKind, FloorPlan, InvertedCastle, LayoutCanvas, Map, MapLayout, MapRegion,
+    MapStyle, Palette, PinnedRoom, Rect, RegionTheme, Room, RoomId, RoomModifier, RoomRef,
+    RoomTemplate,
+};
 
-pub fn create_map(columns: u32, rows: u32, style: types::MapStyle) -> svg::Document {
-    let build_config = algos::MapBuilderConfig::from_style(style);
-    let builder = algos::MapBuilder::new(columns, rows).unwrap();
+/// Creates a map using a random seed.
+///
+/// Use [`create_map_with_seed`] to be able to reproduce the generated map.
+pub fn create_map(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+) -> Result<svg::Document, GenerateError> {
+    create_map_with_seed(columns, rows, style, rand::random())
+}
+
+/// Creates a map using the given seed.
+///
+/// The same dimensions, style and seed will always produce the same map.
+pub fn create_map_with_seed(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    seed: u64,
+) -> Result<svg::Document, GenerateError> {
+    let layout = generate_layout(columns, rows, style, seed)?;
+
+    Ok(render(&layout, style, &DrawOptions::default()))
+}
+
+/// Creates a map using the given generation parameters instead of the style preset.
+///
+/// The style is still used to decorate and draw the map.
+pub fn create_map_with_config(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    seed: u64,
+) -> Result<svg::Document, GenerateError> {
+    let layout = generate_layout_with_config(columns, rows, style, config, seed)?;
+
+    Ok(render(&layout, style, &DrawOptions::default()))
+}
+
+/// Generates the structured layout of a map, without drawing it.
+///
+/// The same dimensions, style and seed will always produce the same layout.
+pub fn generate_layout(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    seed: u64,
+) -> Result<MapLayout, GenerateError> {
+    let build_config = MapBuilderConfig::from_style(style);
+
+    generate_layout_with_config(columns, rows, style, &build_config, seed)
+}
+
+/// Generates the structured layout of a map using the given generation parameters.
+///
+/// Fails if the config contains values outside of their valid ranges, or asks
+/// for more than one floor, an inverted castle or a dual castle.
+pub fn generate_layout_with_config(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    seed: u64,
+) -> Result<MapLayout, GenerateError> {
+    generate_layout_with_pipeline(
+        columns,
+        rows,
+        style,
+        config,
+        &BuildPipeline::default(),
+        seed,
+    )
+}
+
+/// Generates the structured layout of a map, running the given passes over every region.
+///
+/// Fails if the config contains values outside of their valid ranges.
+pub fn generate_layout_with_pipeline(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    pipeline: &BuildPipeline,
+    seed: u64,
+) -> Result<MapLayout, GenerateError> {
+    let canvas = LayoutCanvas::new(columns, rows);
+
+    generate_layout_with_report(&canvas, style, config, pipeline, seed).map(|(layout, _)| layout)
+}
+
+/// Generates the structured layout of a map on the given canvas, along with a report
+/// of the work done by each pass.
+///
+/// The map is as large as the canvas, and its rooms only take cells inside of its mask.
+/// Fails if a pinned room of the canvas is invalid or does not fit inside of it.
+pub fn generate_layout_with_report(
+    canvas: &LayoutCanvas,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    pipeline: &BuildPipeline,
+    seed: u64,
+) -> Result<(MapLayout, GenerationReport), GenerateError> {
+    validate_layout_config(config)?;
+
+    let builder = match canvas.mask() {
+        Some(mask) => algos::MapBuilder::new_masked(mask.clone())?,
+        None => algos::MapBuilder::new(canvas.columns(), canvas.rows())?,
+    }
+    .with_pinned_rooms(canvas.pinned_rooms().to_vec())?;
+
+    let (regions, report) = builder.build(config, pipeline, style, seed)?;
+
+    let layout = MapLayout {
+        columns: canvas.columns(),
+        rows: canvas.rows(),
+        seed,
+        regions,
+    };
+
+    Ok((layout, report))
+}
+
+// Validates the config of the entry points generating a single layout, which
+// can not hold the floors of the layered mode or the castles of the castle modes.
+fn validate_layout_config(config: &MapBuilderConfig) -> Result<(), GenerateError> {
+    config
+        .validate()
+        .map_err(|e| GenerateError::InvalidConfig(e.to_string()))?;
+
+    if config.floors > 1 {
+        return Err(GenerateError::InvalidConfig(format!(
+            "floors ({}) must be generated with generate_floor_plan",
+            config.floors
+        )));
+    }
+
+    if config.inverted_castle {
+        return Err(GenerateError::InvalidConfig(
+            "inverted_castle must be generated with generate_inverted_castle".to_string(),
+        ));
+    }
+
+    if config.dual_castle {
+        return Err(GenerateError::InvalidConfig(
+            "dual_castle must be generated with generate_dual_castle".to_string(),
+        ));
+    }
+
+    Ok(())
+}
 
-    let maps = builder.build(&build_config, style);
+/// Generates a map made of `config.floors` stacked floors, connected through
+/// stairs and elevators.
+///
+/// Every floor fills the whole `columns` by `rows` canvas, masks and pinned rooms
+/// are only supported by [`generate_layout_with_report`].
+pub fn generate_floor_plan(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    seed: u64,
+) -> Result<FloorPlan, GenerateError> {
+    generate_floor_plan_with_report(
+        columns,
+        rows,
+        style,
+        config,
+        &BuildPipeline::default(),
+        seed,
+    )
+    .map(|(floor_plan, _)| floor_plan)
+}
+
+/// Generates a map made of several floors, along with a report of the work done
+/// by each pass on all of them.
+pub fn generate_floor_plan_with_report(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    pipeline: &BuildPipeline,
+    seed: u64,
+) -> Result<(FloorPlan, GenerationReport), GenerateError> {
+    config
+        .validate()
+        .map_err(|e| GenerateError::InvalidConfig(e.to_string()))?;
+
+    let builder = algos::MapBuilder::new(columns, rows)?;
+
+    let (floors, links, report) = builder.build_floors(config, pipeline, style, seed)?;
+
+    let floors = floors
+        .into_iter()
+        .map(|(seed, regions)| MapLayout {
+            columns,
+            rows,
+            seed,
+            regions,
+        })
+        .collect();
+
+    Ok((FloorPlan { floors, links }, report))
+}
+
+/// Generates a castle along with its inverted castle, the same castle turned upside down
+/// with its save, navigation and item rooms placed again.
+///
+/// Both castles fill the whole `columns` by `rows` canvas, masks and pinned rooms
+/// are only supported by [`generate_layout_with_report`].
+pub fn generate_inverted_castle(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    seed: u64,
+) -> Result<InvertedCastle, GenerateError> {
+    generate_inverted_castle_with_report(
+        columns,
+        rows,
+        style,
+        config,
+        &BuildPipeline::default(),
+        seed,
+    )
+    .map(|(inverted_castle, _)| inverted_castle)
+}
+
+/// Generates a castle along with its inverted castle, and a report of the work done
+/// by each pass.
+pub fn generate_inverted_castle_with_report(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    pipeline: &BuildPipeline,
+    seed: u64,
+) -> Result<(InvertedCastle, GenerationReport), GenerateError> {
+    config
+        .validate()
+        .map_err(|e| GenerateError::InvalidConfig(e.to_string()))?;
+
+    let builder = algos::MapBuilder::new(columns, rows)?;
+
+    let (castle, inverted, report) =
+        builder.build_inverted_castle(config, pipeline, style, seed)?;
+
+    let into_layout = |regions| MapLayout {
+        columns,
+        rows,
+        seed,
+        regions,
+    };
 
+    let inverted_castle = InvertedCastle {
+        castle: into_layout(castle),
+        inverted: into_layout(inverted),
+    };
+
+    Ok((inverted_castle, report))
+}
+
+/// Generates two different castles over the same canvas, linked through warp rooms
+/// at the same cells in both of them, like the castles of HOD.
+///
+/// Both castles fill the whole `columns` by `rows` canvas, masks and pinned rooms
+/// are only supported by [`generate_layout_with_report`].
+pub fn generate_dual_castle(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    seed: u64,
+) -> Result<DualCastle, GenerateError> {
+    generate_dual_castle_with_report(
+        columns,
+        rows,
+        style,
+        config,
+        &BuildPipeline::default(),
+        seed,
+    )
+    .map(|(dual_castle, _)| dual_castle)
+}
+
+/// Generates two linked castles, along with a report of the work done by each pass
+/// on both of them.
+pub fn generate_dual_castle_with_report(
+    columns: u32,
+    rows: u32,
+    style: types::MapStyle,
+    config: &MapBuilderConfig,
+    pipeline: &BuildPipeline,
+    seed: u64,
+) -> Result<(DualCastle, GenerationReport), GenerateError> {
+    config
+        .validate()
+        .map_err(|e| GenerateError::InvalidConfig(e.to_string()))?;
+
+    let builder = algos::MapBuilder::new(columns, rows)?;
+
+    let ([castle_a, castle_b], report) =
+        builder.build_dual_castle(config, pipeline, style, seed)?;
+
+    let into_layout = |regions| MapLayout {
+        columns,
+        rows,
+        seed,
+        regions,
+    };
+
+    let dual_castle = DualCastle {
+        castle_a: into_layout(castle_a),
+        castle_b: into_layout(castle_b),
+    };
+
+    Ok((dual_castle, report))
+}
+
+/// Draws a previously generated layout as an SVG document.
+pub fn render(
+    layout: &MapLayout,
+    style: types::MapStyle,
+    draw_options: &DrawOptions,
+) -> svg::Document {
     let draw_config = algos::DrawConfig {
-        canvas_width: columns,
-        canvas_height: rows,
+        canvas_width: layout.columns,
+        canvas_height: layout.rows,
+        seed: layout.seed,
+        options: *draw_options,
     };
     let drawer = algos::MapDrawerFactory::create_drawer(style);
 
-    algos::MapDrawer::draw(drawer.as_ref(), maps, &draw_config)
+    algos::MapDrawer::draw(drawer.as_ref(), &layout.regions, &draw_config)
+}
+
+/// Draws every floor of a previously generated floor plan as its own SVG document,
+/// from the lowest floor up.
+pub fn render_floor_plan(
+    floor_plan: &FloorPlan,
+    style: types::MapStyle,
+    draw_options: &DrawOptions,
+) -> Vec<svg::Document> {
+    floor_plan
+        .floors
+        .iter()
+        .map(|layout| render(layout, style, draw_options))
+        .collect()
+}
+
+/// Draws both castles of a previously generated inverted castle as separate SVG
+/// documents, the castle first.
+pub fn render_inverted_castle(
+    inverted_castle: &InvertedCastle,
+    style: types::MapStyle,
+    draw_options: &DrawOptions,
+) -> [svg::Document; 2] {
+    [
+        render(&inverted_castle.castle, style, draw_options),
+        render(&inverted_castle.inverted, style, draw_options),
+    ]
+}
+
+/// Draws both castles of a previously generated dual castle as separate SVG documents,
+/// the second castle with its own palette.
+pub fn render_dual_castle(
+    dual_castle: &DualCastle,
+    style: types::MapStyle,
+    draw_options: &DrawOptions,
+) -> [svg::Document; 2] {
+    let second_castle_options = DrawOptions {
+        second_castle: true,
+        ..*draw_options
+    };
+
+    [
+        render(&dual_castle.castle_a, style, draw_options),
+        render(&dual_castle.castle_b, style, &second_castle_options),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STYLES: [MapStyle; 7] = [
+        MapStyle::CastlevaniaSOTN,
+        MapStyle::CastlevaniaAOS,
+        MapStyle::CastlevaniaCOTM,
+        MapStyle::CastlevaniaHOD,
+        MapStyle::MetroidZM,
+        MapStyle::MetroidFS,
+        MapStyle::MetroidSP,
+    ];
+
+    #[test]
+    fn test_create_map_with_seed_is_reproducible() {
+        for style in STYLES {
+            let map_a = create_map_with_seed(48, 32, style, 1234)
+                .unwrap()
+                .to_string();
+            let map_b = create_map_with_seed(48, 32, style, 1234)
+                .unwrap()
+                .to_string();
+
+            assert_eq!(map_a, map_b, "Same seed should produce the same map");
+        }
+    }
+
+    #[test]
+    fn test_create_map_with_seed_depends_on_seed() {
+        for style in STYLES {
+            let map_a = create_map_with_seed(48, 32, style, 1234)
+                .unwrap()
+                .to_string();
+            let map_b = create_map_with_seed(48, 32, style, 4321)
+                .unwrap()
+                .to_string();
+
+            assert_ne!(
+                map_a, map_b,
+                "Different seeds should produce different maps"
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_layout_matches_rendered_map() {
+        for style in STYLES {
+            let layout = generate_layout(48, 32, style, 1234).unwrap();
+
+            assert!(!layout.regions.is_empty());
+            assert!(
+                layout
+                    .regions
+                    .iter()
+                    .all(|region| region.rooms.iter().all(|room| !room.cells.is_empty()))
+            );
+
+            let rendered = render(&layout, style, &DrawOptions::default()).to_string();
+            let created = create_map_with_seed(48, 32, style, 1234)
+                .unwrap()
+                .to_string();
+
+            assert_eq!(rendered, created);
+        }
+    }
+
+    #[test]
+    fn test_render_reveals_secret_rooms_when_asked() {
+        for style in STYLES {
+            let layout = generate_layout(48, 32, style, 1234).unwrap();
+
+            let mut draw_options = DrawOptions::default();
+            let hidden = render(&layout, style, &draw_options).to_string();
+
+            draw_options.reveal_secrets = true;
+            let revealed = render(&layout, style, &draw_options).to_string();
+
+            // Every secret room adds its own path and the one of its secret door
+            let secret_rooms = layout
+                .regions
+                .iter()
+                .flat_map(|map| map.rooms.iter())
+                .filter(|room| room.modifier == Some(RoomModifier::Secret))
+                .count();
+
+            assert!(secret_rooms > 0);
+            assert_eq!(
+                revealed.matches("<path").count(),
+                hidden.matches("<path").count() + secret_rooms * 2
+            );
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_keep_region_themes() {
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+            let (layout, report) = generate_layout_with_report(
+                &LayoutCanvas::new(48, 32),
+                style,
+                &config,
+                &BuildPipeline::default(),
+                1234,
+            )
+            .unwrap();
+
+            // Merged maps keep the themes of every region they were made of
+            let themes = layout
+                .regions
+                .iter()
+                .flat_map(|map| map.themes.iter())
+                .collect::<Vec<_>>();
+            assert_eq!(themes.len(), report.region_count);
+
+            let names = themes
+                .iter()
+                .map(|theme| theme.name.as_str())
+                .collect::<std::collections::HashSet<_>>();
+            assert_eq!(names.len(), themes.len());
+
+            let mut draw_options = DrawOptions::default();
+            assert!(
+                !render(&layout, style, &draw_options)
+                    .to_string()
+                    .contains("<text")
+            );
+
+            draw_options.region_labels = true;
+            let labelled = render(&layout, style, &draw_options).to_string();
+            assert_eq!(labelled.matches("<text").count(), themes.len());
+            for theme in themes {
+                assert!(labelled.contains(&theme.name));
+            }
+        }
+    }
+
+    #[test]
+    fn test_merged_layouts_keep_room_regions() {
+        use std::collections::HashMap;
+
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+            if !config.merge_regions {
+                continue;
+            }
+
+            for seed in 0..4 {
+                let layout = generate_layout(48, 32, style, seed).unwrap();
+                let map = &layout.regions[0];
+
+                let cell_regions = map
+                    .rooms
+                    .iter()
+                    .flat_map(|room| room.cells.iter().map(|cell| (*cell, room.region)))
+                    .collect::<HashMap<_, _>>();
+
+                for room in map.rooms.iter() {
+                    assert!(room.region < map.themes.len());
+                }
+
+                for door in map.doors.iter() {
+                    assert_eq!(
+                        door.area_transition,
+                        cell_regions[&door.from] != cell_regions[&door.to]
+                    );
+                }
+
+                if map.themes.len() > 1 {
+                    assert!(map.doors.iter().any(|door| door.area_transition));
+                }
+
+                let draw_options = DrawOptions {
+                    tint_regions: true,
+                    ..Default::default()
+                };
+                let tinted = render(&layout, style, &draw_options).to_string();
+
+                for theme in map.themes.iter() {
+                    assert!(tinted.contains(&format!(r#"fill="{}""#, theme.palette.room_color)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_floor_plan_links_line_up() {
+        for style in STYLES {
+            let config = MapBuilderConfig::builder().floors(3).build().unwrap();
+            let (floor_plan, report) = generate_floor_plan_with_report(
+                48,
+                32,
+                style,
+                &config,
+                &BuildPipeline::default(),
+                1234,
+            )
+            .unwrap();
+
+            assert_eq!(floor_plan.floors.len(), 3);
+            assert!(!floor_plan.links.is_empty());
+            assert_eq!(report.floor_links, floor_plan.links.len());
+
+            // The lowest floor has the rooms a single floor generation gets
+            let single_floor = config.to_builder().floors(1).build().unwrap();
+            let room_cells = |layout: &MapLayout| {
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.rooms.iter().map(|room| room.cells.clone()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                room_cells(&floor_plan.floors[0]),
+                room_cells(
+                    &generate_layout_with_config(48, 32, style, &single_floor, 1234).unwrap()
+                )
+            );
+
+            for link in floor_plan.links.iter() {
+                let modifier = match link.kind {
+                    FloorLinkKind::Stairs => RoomModifier::Stairs,
+                    FloorLinkKind::Elevator => RoomModifier::Elevator,
+                };
+
+                for floor in link.lower_floor..=link.upper_floor {
+                    let room = floor_plan.floors[floor]
+                        .regions
+                        .iter()
+                        .flat_map(|map| map.rooms.iter())
+                        .find(|room| room.cells.contains(&link.cell))
+                        .unwrap();
+
+                    assert_eq!(room.modifier, Some(modifier));
+                }
+            }
+
+            let documents = render_floor_plan(&floor_plan, style, &DrawOptions::default());
+            assert_eq!(documents.len(), floor_plan.floors.len());
+        }
+    }
+
+    #[test]
+    fn test_inverted_castle_report_counts_both_castles() {
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style)
+                .to_builder()
+                .inverted_castle(true)
+                .build()
+                .unwrap();
+            let (inverted_castle, report) = generate_inverted_castle_with_report(
+                48,
+                32,
+                style,
+                &config,
+                &BuildPipeline::default(),
+                1234,
+            )
+            .unwrap();
+
+            let count_doors = |is_counted: fn(&Door) -> bool| {
+                [&inverted_castle.castle, &inverted_castle.inverted]
+                    .into_iter()
+                    .flat_map(|layout| layout.regions.iter())
+                    .flat_map(|map| map.doors.iter())
+                    .filter(|door| is_counted(door))
+                    .count()
+            };
+
+            assert_eq!(report.one_way_doors, count_doors(|door| door.one_way));
+            assert_eq!(
+                report.ability_doors,
+                count_doors(|door| door.requirement.is_some())
+            );
+            assert_eq!(
+                report.locked_doors,
+                count_doors(|door| matches!(door.modifier, DoorModifier::Locked(_)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_inverted_castle_is_turned_and_redecorated() {
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style)
+                .to_builder()
+                .inverted_castle(true)
+                .build()
+                .unwrap();
+            let inverted_castle = generate_inverted_castle(48, 32, style, &config, 1234).unwrap();
+
+            let rooms_with = |layout: &MapLayout, modifier: fn(&Option<RoomModifier>) -> bool| {
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.rooms.iter())
+                    .filter(|room| modifier(&room.modifier))
+                    .map(|room| {
+                        let mut cells = room.cells.clone();
+                        cells.sort();
+                        cells
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            let turned = |cells: Vec<Cell>| {
+                let mut cells = cells
+                    .into_iter()
+                    .map(|cell| Cell::new(47 - cell.col, 31 - cell.row))
+                    .collect::<Vec<_>>();
+                cells.sort();
+                cells
+            };
+
+            // Every room of the castle has its counterpart turned upside down
+            let all_rooms = |layout: &MapLayout| {
+                let mut rooms = rooms_with(layout, |_| true);
+                rooms.sort();
+                rooms
+            };
+            let mut turned_rooms = all_rooms(&inverted_castle.castle)
+                .into_iter()
+                .map(turned)
+                .collect::<Vec<_>>();
+            turned_rooms.sort();
+            assert_eq!(turned_rooms, all_rooms(&inverted_castle.inverted));
+
+            let castle_links = rooms_with(&inverted_castle.castle, |modifier| {
+                *modifier == Some(RoomModifier::CastleLink(0))
+            });
+            let inverted_links = rooms_with(&inverted_castle.inverted, |modifier| {
+                *modifier == Some(RoomModifier::CastleLink(0))
+            });
+            assert_eq!(castle_links.len(), 1);
+            assert_eq!(inverted_links, vec![turned(castle_links[0].clone())]);
+
+            let turned_save_rooms = rooms_with(&inverted_castle.castle, |modifier| {
+                *modifier == Some(RoomModifier::Save)
+            })
+            .into_iter()
+            .map(turned)
+            .collect::<Vec<_>>();
+            assert_ne!(
+                turned_save_rooms,
+                rooms_with(&inverted_castle.inverted, |modifier| {
+                    *modifier == Some(RoomModifier::Save)
+                })
+            );
+
+            // Vertical one-way doors are drops in both castles
+            for layout in [&inverted_castle.castle, &inverted_castle.inverted] {
+                assert!(
+                    layout
+                        .regions
+                        .iter()
+                        .flat_map(|map| map.doors.iter())
+                        .filter(|door| door.one_way && door.is_vertical())
+                        .all(|door| door.to.row > door.from.row)
+                );
+            }
+            assert!(
+                inverted_castle
+                    .inverted
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.doors.iter())
+                    .any(|door| door.one_way)
+            );
+
+            assert!(validate(&inverted_castle.inverted).is_empty());
+            assert!(solve_progression(&inverted_castle.inverted).is_some());
+        }
+    }
+
+    #[test]
+    fn test_dual_castle_shares_castle_links() {
+        for style in [MapStyle::CastlevaniaHOD, MapStyle::CastlevaniaSOTN] {
+            let config = MapBuilderConfig::from_style(style)
+                .to_builder()
+                .dual_castle(true)
+                .build()
+                .unwrap();
+            let dual_castle = generate_dual_castle(48, 32, style, &config, 1234).unwrap();
+
+            let rooms_with = |layout: &MapLayout, is_wanted: fn(Option<RoomModifier>) -> bool| {
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.rooms.iter())
+                    .filter(|room| is_wanted(room.modifier))
+                    .map(|room| (room.modifier, room.cells.clone()))
+                    .collect::<Vec<_>>()
+            };
+
+            let is_castle_link = |modifier| matches!(modifier, Some(RoomModifier::CastleLink(_)));
+            let castle_b_links = rooms_with(&dual_castle.castle_b, is_castle_link);
+
+            assert!(!castle_b_links.is_empty());
+
+            for (link, cells) in rooms_with(&dual_castle.castle_a, is_castle_link) {
+                let (_, other_cells) = castle_b_links
+                    .iter()
+                    .find(|(other_link, _)| *other_link == link)
+                    .unwrap();
+
+                assert!(cells.iter().any(|cell| other_cells.contains(cell)));
+            }
+
+            // The second castle is only entered through the castle links
+            let is_start = |modifier| modifier == Some(RoomModifier::Start);
+            assert_eq!(rooms_with(&dual_castle.castle_a, is_start).len(), 1);
+            assert!(rooms_with(&dual_castle.castle_b, is_start).is_empty());
+
+            assert!(validate_dual_castle(&dual_castle).is_empty());
+
+            let [castle_a, castle_b] =
+                render_dual_castle(&dual_castle, style, &DrawOptions::default());
+            assert_ne!(
+                castle_a.to_string().contains(r##"fill="#a00000""##),
+                castle_b.to_string().contains(r##"fill="#a00000""##)
+            );
+        }
+    }
+
+    #[test]
+    fn test_dual_castle_report_covers_every_attempt() {
+        let style = MapStyle::CastlevaniaHOD;
+        let config = MapBuilderConfig::from_style(style)
+            .to_builder()
+            .dual_castle(true)
+            .build()
+            .unwrap();
+
+        let (_, report) = generate_dual_castle_with_report(
+            48,
+            32,
+            style,
+            &config,
+            &BuildPipeline::default(),
+            1234,
+        )
+        .unwrap();
+
+        let link_duration = report.pass_duration("add_dual_castle").unwrap();
+        let discarded_duration = report
+            .pass_duration("discarded_castle_attempts")
+            .unwrap_or_default();
+
+        assert!(report.castle_links > 0);
+        assert!(report.total_duration >= link_duration + discarded_duration);
+    }
+
+    #[test]
+    fn test_generated_layout_json_round_trip() {
+        for style in STYLES {
+            let layout = generate_layout(48, 32, style, 1234).unwrap();
+
+            let loaded = MapLayout::from_json(&layout.to_json().unwrap()).unwrap();
+            assert_eq!(loaded, layout);
+
+            let rendered = render(&loaded, style, &DrawOptions::default()).to_string();
+            assert_eq!(
+                rendered,
+                create_map_with_seed(48, 32, style, 1234)
+                    .unwrap()
+                    .to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_map_with_style_config_matches_preset() {
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+
+            let map = create_map_with_config(48, 32, style, &config, 99).unwrap();
+            assert_eq!(
+                map.to_string(),
+                create_map_with_seed(48, 32, style, 99).unwrap().to_string()
+            );
+        }
+
+        let config = MapBuilderConfig::from_style(MapStyle::CastlevaniaSOTN)
+            .to_builder()
+            .bisect_room_prob(0.9)
+            .bsp_config(|bsp| bsp.rect_survival_prob(0.9))
+            .build()
+            .unwrap();
+        let layout =
+            generate_layout_with_config(48, 32, MapStyle::CastlevaniaSOTN, &config, 99).unwrap();
+        assert_ne!(
+            layout,
+            generate_layout(48, 32, MapStyle::CastlevaniaSOTN, 99).unwrap()
+        );
+
+        let mut invalid = config;
+        invalid.door_loop_connection_chance = 1.5;
+        assert!(matches!(
+            create_map_with_config(48, 32, MapStyle::CastlevaniaSOTN, &invalid, 99),
+            Err(GenerateError::InvalidConfig(_))
+        ));
+
+        let floors = config.to_builder().floors(2).build().unwrap();
+        let inverted_castle = config.to_builder().inverted_castle(true).build().unwrap();
+        let dual_castle = config.to_builder().dual_castle(true).build().unwrap();
+        for config in [floors, inverted_castle, dual_castle] {
+            assert!(matches!(
+                generate_layout_with_config(48, 32, MapStyle::CastlevaniaSOTN, &config, 99),
+                Err(GenerateError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_create_map_rejects_invalid_dimensions() {
+        for (columns, rows) in [(0, 0), (0, 10), (10, 0), (4, 10), (10, 4)] {
+            assert!(matches!(
+                create_map_with_seed(columns, rows, MapStyle::CastlevaniaSOTN, 1),
+                Err(GenerateError::DimensionsTooSmall { .. })
+            ));
+        }
+
+        assert!(matches!(
+            generate_layout(10, 100_000, MapStyle::MetroidZM, 1),
+            Err(GenerateError::DimensionsTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_create_map_with_small_dimensions_does_not_panic() {
+        for style in STYLES {
+            for size in 5..12 {
+                for seed in 0..8 {
+                    match create_map_with_seed(size, size, style, seed) {
+                        Ok(_) | Err(GenerateError::EmptyLayout) => {}
+                        Err(e) => panic!("Unexpected error for {}x{}: {}", size, size, e),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_hold_invariants() {
+        for style in STYLES {
+            for seed in 0..32 {
+                let layout = generate_layout(48, 32, style, seed).unwrap();
+
+                let violations = validate(&layout);
+                assert!(
+                    violations.is_empty(),
+                    "{:?} with seed {} has violations: {:?}",
+                    style,
+                    seed,
+                    violations
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_are_completable() {
+        for style in STYLES {
+            for seed in 0..8 {
+                let layout = generate_layout(48, 32, style, seed).unwrap();
+
+                let solution = solve_progression(&layout).unwrap();
+                assert!(
+                    solution.is_completable(),
+                    "{:?} with seed {} cannot be completed",
+                    style,
+                    seed
+                );
+
+                let locked_tiers = layout
+                    .regions
+                    .iter()
+                    .flat_map(|region| region.doors.iter())
+                    .filter_map(|door| match door.modifier {
+                        DoorModifier::Locked(tier) => Some(tier),
+                        _ => None,
+                    })
+                    .collect::<std::collections::BTreeSet<_>>();
+                let key_tiers = solution
+                    .spheres
+                    .iter()
+                    .flat_map(|sphere| sphere.keys.iter().copied())
+                    .collect::<std::collections::BTreeSet<_>>();
+
+                assert!(!locked_tiers.is_empty());
+                assert_eq!(locked_tiers, key_tiers);
+
+                let required_abilities = layout
+                    .regions
+                    .iter()
+                    .flat_map(|region| region.doors.iter())
+                    .filter_map(|door| door.requirement)
+                    .collect::<AbilitySet>();
+                let found_abilities = solution
+                    .spheres
+                    .iter()
+                    .flat_map(|sphere| sphere.abilities.iter().copied())
+                    .collect::<AbilitySet>();
+
+                assert!(!required_abilities.is_empty());
+                assert_eq!(required_abilities, found_abilities);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_have_start_and_boss_rooms() {
+        for style in STYLES {
+            let boss_rooms = MapBuilderConfig::from_style(style).boss_rooms as usize;
+
+            for seed in 0..8 {
+                let layout = generate_layout(48, 32, style, seed).unwrap();
+
+                let rooms_with = |modifier| {
+                    layout
+                        .regions
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(region_idx, region)| {
+                            region
+                                .rooms
+                                .iter()
+                                .enumerate()
+                                .map(move |(room_idx, room)| (region_idx, room_idx, room))
+                        })
+                        .filter(move |(_, _, room)| room.modifier == Some(modifier))
+                };
+
+                let start_rooms = rooms_with(RoomModifier::Start).collect::<Vec<_>>();
+                assert_eq!(start_rooms.len(), 1);
+
+                let (region, room, _) = start_rooms[0];
+                assert_eq!(
+                    solve_progression(&layout).unwrap().start,
+                    RoomRef { region, room }
+                );
+
+                let bosses = rooms_with(RoomModifier::Boss).collect::<Vec<_>>();
+                assert_eq!(bosses.len(), boss_rooms, "{:?} with seed {}", style, seed);
+
+                // Every boss has a save room next door
+                for (region_idx, _, boss) in bosses {
+                    let region = &layout.regions[region_idx];
+
+                    assert!(region.doors.iter().any(|door| {
+                        rooms_with(RoomModifier::Save).any(|(save_region, _, save)| {
+                            save_region == region_idx
+                                && (boss.cells.contains(&door.from)
+                                    && save.cells.contains(&door.to)
+                                    || boss.cells.contains(&door.to)
+                                        && save.cells.contains(&door.from))
+                        })
+                    }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_link_warp_rooms() {
+        for style in STYLES {
+            let warp_rooms = MapBuilderConfig::from_style(style).warp_rooms as usize;
+
+            for seed in 0..4 {
+                let layout = generate_layout(48, 32, style, seed).unwrap();
+
+                let warp_links = layout.warp_links();
+                assert_eq!(
+                    warp_links.len(),
+                    warp_rooms * warp_rooms.saturating_sub(1) / 2
+                );
+
+                for (from, to) in warp_links {
+                    assert_ne!(from, to);
+                    for room in [from, to] {
+                        assert_eq!(
+                            layout.regions[room.region].rooms[room.room].modifier,
+                            Some(RoomModifier::Warp)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_layout_with_custom_pipeline() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        };
+
+        struct CountingPass(Arc<AtomicUsize>);
+
+        impl BuildPass for CountingPass {
+            fn name(&self) -> &str {
+                "counting"
+            }
+
+            fn run(&self, _: &mut MapRegion, _: &PassContext) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let style = MapStyle::MetroidZM;
+        let config = MapBuilderConfig::from_style(style);
+
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let mut pipeline = BuildPipeline::default();
+        pipeline
+            .push(Box::new(CountingPass(Arc::clone(&runs))))
+            .unwrap();
+
+        // A pass without random rolls does not change the generated layout
+        let layout = generate_layout_with_pipeline(48, 32, style, &config, &pipeline, 7).unwrap();
+        assert_eq!(layout, generate_layout(48, 32, style, 7).unwrap());
+
+        // The pass runs once per region
+        assert!(runs.load(Ordering::Relaxed) >= layout.regions.len());
+
+        let empty =
+            generate_layout_with_pipeline(48, 32, style, &config, &BuildPipeline::empty(), 7)
+                .unwrap();
+        assert_ne!(empty, layout);
+        assert!(empty.regions.iter().all(|region| !region.rooms.is_empty()));
+    }
+
+    #[test]
+    fn test_generate_layout_with_room_templates() {
+        let style = MapStyle::CastlevaniaSOTN;
+        let config = MapBuilderConfig::from_style(style)
+            .to_builder()
+            .room_template_prob(0.9)
+            .build()
+            .unwrap();
+
+        let mut room_templates = RoomTemplateLibrary::empty();
+        room_templates
+            .register(
+                RoomTemplate::from_pattern("u_hall", &["D.D", "#.#", "###"]).unwrap(),
+                1.0,
+            )
+            .unwrap();
+
+        let mut pipeline = BuildPipeline::default();
+        pipeline.set_room_templates(room_templates);
+
+        let (layout, report) =
+            generate_layout_with_report(&LayoutCanvas::new(48, 32), style, &config, &pipeline, 7)
+                .unwrap();
+
+        assert!(report.template_rooms > 0);
+        assert!(validate(&layout).is_empty());
+
+        let rooms = layout
+            .regions
+            .iter()
+            .flat_map(|region| region.rooms.iter())
+            .collect::<Vec<_>>();
+        let doors = layout
+            .regions
+            .iter()
+            .flat_map(|region| region.doors.iter())
+            .collect::<Vec<_>>();
+
+        let template_rooms = rooms
+            .iter()
+            .filter(|room| room.cells.len() == 7 && room.door_anchors.len() == 2)
+            .collect::<Vec<_>>();
+
+        assert!(!template_rooms.is_empty());
+
+        // Every door of a template room, secret ones included, is at one of its anchors
+        for room in template_rooms {
+            for door in doors.iter() {
+                for cell in [door.from, door.to] {
+                    if room.cells.contains(&cell) {
+                        assert!(room.door_anchors.contains(&cell));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_layouts_keep_doors_at_anchors() {
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style)
+                .to_builder()
+                .room_template_prob(0.9)
+                .build()
+                .unwrap();
+
+            for seed in 0..4 {
+                let (layout, report) = generate_layout_with_report(
+                    &LayoutCanvas::new(48, 32),
+                    style,
+                    &config,
+                    &BuildPipeline::default(),
+                    seed,
+                )
+                .unwrap();
+
+                assert!(report.template_rooms > 0);
+
+                for map in layout.regions.iter() {
+                    for room in map
+                        .rooms
+                        .iter()
+                        .filter(|room| !room.door_anchors.is_empty())
+                    {
+                        for door in map.doors.iter() {
+                            for cell in [door.from, door.to] {
+                                assert!(
+                                    !room.cells.contains(&cell)
+                                        || room.door_anchors.contains(&cell),
+                                    "{:?} with seed {} has a door away from the anchors at {}",
+                                    style,
+                                    seed,
+                                    cell
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_layout_with_pinned_rooms_keeps_them() {
+        let pinned_rooms = [
+            PinnedRoom::new(vec![Cell::new(0, 20)]).with_modifier(RoomModifier::Start),
+            PinnedRoom::from_rect(Rect::new(28, 0, 8, 3)).with_modifier(RoomModifier::Boss),
+            PinnedRoom::from_rect(Rect::new(40, 30, 2, 2)),
+        ];
+
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+
+            let canvas = LayoutCanvas::new(64, 45).with_pinned_rooms(pinned_rooms.to_vec());
+
+            let (layout, report) =
+                generate_layout_with_report(&canvas, style, &config, &BuildPipeline::default(), 7)
+                    .unwrap();
+
+            assert!(validate(&layout).is_empty());
+            assert!(report.boss_rooms >= 1);
+
+            let rooms = layout
+                .regions
+                .iter()
+                .flat_map(|region| region.rooms.iter())
+                .collect::<Vec<_>>();
+
+            for pinned_room in pinned_rooms.iter() {
+                let room = rooms
+                    .iter()
+                    .find(|room| room.cells.contains(&pinned_room.cells[0]))
+                    .unwrap();
+
+                assert!(room.pinned);
+                assert_eq!(room.cells, pinned_room.room().cells);
+
+                if pinned_room.modifier.is_some() {
+                    assert_eq!(room.modifier, pinned_room.modifier);
+                }
+            }
+
+            let start_rooms = rooms
+                .iter()
+                .filter(|room| room.modifier == Some(RoomModifier::Start))
+                .count();
+            assert_eq!(start_rooms, 1);
+        }
+    }
+
+    #[test]
+    fn test_generate_layout_with_mask_keeps_rooms_inside() {
+        // An island with a bay cut into its right side
+        let mask = CellMask::from_polygon(
+            48,
+            32,
+            &[
+                (4.0, 16.0),
+                (16.0, 2.0),
+                (40.0, 4.0),
+                (46.0, 12.0),
+                (30.0, 16.0),
+                (44.0, 24.0),
+                (24.0, 30.0),
+                (8.0, 26.0),
+            ],
+        )
+        .unwrap();
+
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+
+            let canvas = LayoutCanvas::from_mask(mask.clone());
+
+            let (layout, _) =
+                generate_layout_with_report(&canvas, style, &config, &BuildPipeline::default(), 7)
+                    .unwrap();
+
+            assert_eq!((layout.columns, layout.rows), (48, 32));
+            assert!(validate(&layout).is_empty());
+
+            for room in layout.regions.iter().flat_map(|region| region.rooms.iter()) {
+                assert!(room.cells.iter().all(|cell| mask.contains(*cell)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_layout_with_mask_and_pinned_rooms() {
+        let mask = CellMask::from_polygon(
+            48,
+            32,
+            &[(24.0, 0.0), (48.0, 16.0), (24.0, 32.0), (0.0, 16.0)],
+        )
+        .unwrap();
+        let pinned_rooms = vec![
+            PinnedRoom::new(vec![Cell::new(10, 16)]).with_modifier(RoomModifier::Start),
+            PinnedRoom::from_rect(Rect::new(20, 4, 8, 3)).with_modifier(RoomModifier::Boss),
+        ];
+
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+
+            let canvas =
+                LayoutCanvas::from_mask(mask.clone()).with_pinned_rooms(pinned_rooms.clone());
+
+            let (layout, _) =
+                generate_layout_with_report(&canvas, style, &config, &BuildPipeline::default(), 7)
+                    .unwrap();
+
+            assert!(validate(&layout).is_empty());
+
+            let rooms = layout
+                .regions
+                .iter()
+                .flat_map(|region| region.rooms.iter())
+                .collect::<Vec<_>>();
+
+            assert!(
+                rooms
+                    .iter()
+                    .all(|room| room.cells.iter().all(|cell| mask.contains(*cell)))
+            );
+
+            for pinned_room in pinned_rooms.iter() {
+                assert!(rooms.iter().any(|room| room.pinned
+                    && room.cells == pinned_room.room().cells
+                    && room.modifier == pinned_room.modifier));
+            }
+        }
+
+        // The corner of the canvas is outside of the mask
+        let canvas = LayoutCanvas::from_mask(mask)
+            .with_pinned_rooms(vec![PinnedRoom::new(vec![Cell::new(0, 0)])]);
+
+        assert!(matches!(
+            generate_layout_with_report(
+                &canvas,
+                MapStyle::CastlevaniaSOTN,
+                &MapBuilderConfig::from_style(MapStyle::CastlevaniaSOTN),
+                &BuildPipeline::default(),
+                7,
+            ),
+            Err(GenerateError::InvalidPinnedRoom { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_generate_layout_rejects_disconnected_pinned_rooms() {
+        // The pinned room is cut off from the rest of the mask
+        let mut cells = vec![true; 48 * 32];
+        for (col, row) in [(39, 28), (41, 28), (40, 27), (40, 29)] {
+            cells[row * 48 + col] = false;
+        }
+        let mask = CellMask::from_bitmap(48, 32, cells).unwrap();
+
+        let canvas = LayoutCanvas::from_mask(mask)
+            .with_pinned_rooms(vec![PinnedRoom::new(vec![Cell::new(40, 28)])]);
+
+        for style in STYLES {
+            assert!(matches!(
+                generate_layout_with_report(
+                    &canvas,
+                    style,
+                    &MapBuilderConfig::from_style(style),
+                    &BuildPipeline::default(),
+                    7,
+                ),
+                Err(GenerateError::InvalidPinnedRoom { index: 0, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_multi_layout_modes_ignore_the_canvas() {
+        let style = MapStyle::CastlevaniaSOTN;
+        let config = MapBuilderConfig::from_style(style).to_builder();
+
+        let floor_plan =
+            generate_floor_plan(48, 32, style, &config.floors(2).build().unwrap(), 7).unwrap();
+        let inverted_castle = generate_inverted_castle(
+            48,
+            32,
+            style,
+            &config.inverted_castle(true).build().unwrap(),
+            7,
+        )
+        .unwrap();
+        let dual_castle =
+            generate_dual_castle(48, 32, style, &config.dual_castle(true).build().unwrap(), 7)
+                .unwrap();
+
+        let layouts = floor_plan.floors.iter().chain([
+            &inverted_castle.castle,
+            &inverted_castle.inverted,
+            &dual_castle.castle_a,
+            &dual_castle.castle_b,
+        ]);
+
+        // Every layout is generated on a plain canvas, without any pinned room
+        for layout in layouts {
+            assert_eq!((layout.columns, layout.rows), (48, 32));
+            assert!(
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|region| region.rooms.iter())
+                    .all(|room| !room.pinned)
+            );
+        }
+    }
+
+    #[test]
+    fn test_generation_report_matches_layout() {
+        for style in STYLES {
+            let config = MapBuilderConfig::from_style(style);
+            let pipeline = BuildPipeline::default();
+
+            let (layout, report) = generate_layout_with_report(
+                &LayoutCanvas::new(64, 45),
+                style,
+                &config,
+                &pipeline,
+                21,
+            )
+            .unwrap();
+
+            assert_eq!(report.seed, 21);
+            assert!(report.region_count >= layout.regions.len());
+            assert_eq!(
+                report.room_count,
+                layout
+                    .regions
+                    .iter()
+                    .map(|map| map.rooms.len())
+                    .sum::<usize>()
+            );
+            assert_eq!(
+                report.doors_created,
+                layout
+                    .regions
+                    .iter()
+                    .map(|map| map.doors.len())
+                    .sum::<usize>()
+            );
+            assert_eq!(
+                report.ability_doors,
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.doors.iter())
+                    .filter(|door| door.requirement.is_some())
+                    .count()
+            );
+            assert_eq!(
+                report.locked_doors,
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.doors.iter())
+                    .filter(|door| matches!(door.modifier, DoorModifier::Locked(_)))
+                    .count()
+            );
+            assert_eq!(
+                report.one_way_doors,
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.doors.iter())
+                    .filter(|door| door.one_way)
+                    .count()
+            );
+            assert!(report.one_way_doors > 0);
+            assert_eq!(
+                report.secret_rooms,
+                layout
+                    .regions
+                    .iter()
+                    .flat_map(|map| map.rooms.iter())
+                    .filter(|room| room.modifier == Some(RoomModifier::Secret))
+                    .count()
+            );
+            assert!(report.rooms_merged > 0);
+
+            for name in pipeline.pass_names() {
+                assert!(report.pass_duration(name).is_some());
+            }
+            assert!(report.pass_duration("partition").is_some());
+            assert!(report.pass_duration("generate_doors").is_some());
+            assert!(report.total_duration >= report.pass_duration("partition").unwrap());
+
+            // The counters do not depend on the timings
+            let (_, other_report) = generate_layout_with_report(
+                &LayoutCanvas::new(64, 45),
+                style,
+                &config,
+                &pipeline,
+                21,
+            )
+            .unwrap();
+            assert_eq!(
+                (
+                    report.rooms_merged,
+                    report.rooms_bisected,
+                    report.region_count
+                ),
+                (
+                    other_report.rooms_merged,
+                    other_report.rooms_bisected,
+                    other_report.region_count
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_map_with_seed_is_independent_of_thread_count() {
+        let single_thread = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let multi_thread = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        for style in STYLES {
+            let map_a = single_thread
+                .install(|| create_map_with_seed(64, 45, style, 99).unwrap())
+                .to_string();
+            let map_b = multi_thread
+                .install(|| create_map_with_seed(64, 45, style, 99).unwrap())
+                .to_string();
+
+            assert_eq!(map_a, map_b);
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::constants::DIRECTIONS;
+use crate::constants::{DIRECTIONS, LAYOUT_SCHEMA_VERSION};
 
 use std::{
     collections::{HashMap, HashSet},
@@ -7,7 +7,7 @@ use std::{
 };
 
 use anyhow::Result;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub(crate) struct Vector2 {
@@ -50,8 +50,9 @@ impl Display for Vector2 {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub(crate) struct Cell {
+/// A single cell of the map grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Cell {
     pub col: u32,
     pub row: u32,
 }
@@ -91,7 +92,7 @@ impl Cell {
         ]
     }
 
-    pub fn get_edges(&self) -> Vec<Edge> {
+    pub(crate) fn get_edges(&self) -> Vec<Edge> {
         vec![
             // North
             Edge {
@@ -310,9 +311,15 @@ impl RoomEntry {
 
 pub(crate) type NeighbourSet = tinyset::SetUsize;
 
+/// A region of the map while it is being built.
+///
+/// Rooms are addressed by their [`RoomId`], and can be either active or removed.
+/// Removed rooms are not part of the final map, but they are kept around since
+/// some passes use them to reconnect isolated groups of rooms.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct MapRegion {
+pub struct MapRegion {
     pub origin_rect: Rect,
+    pub themes: Vec<RegionTheme>,
     room_buffer: Vec<RoomEntry>,
     neighbour_buffer: Vec<Option<NeighbourSet>>,
 }
@@ -320,7 +327,7 @@ pub(crate) struct MapRegion {
 #[allow(dead_code)]
 impl MapRegion {
     #[cfg(test)]
-    pub fn new_test_region() -> MapRegion {
+    pub(crate) fn new_test_region() -> MapRegion {
         /*
            Creates the following map:
 
@@ -393,7 +400,7 @@ impl MapRegion {
     }
 
     #[cfg(test)]
-    pub fn new_test_small_region() -> Self {
+    pub(crate) fn new_test_small_region() -> Self {
         /*
            Creates the following map:
 
@@ -427,7 +434,7 @@ impl MapRegion {
         MapRegion::new(origin_rect, rooms, removed_rooms, neighbours)
     }
 
-    pub fn new(
+    pub(crate) fn new(
         origin_rect: Rect,
         mut rooms: RoomTable,
         mut removed: RoomTable,
@@ -458,12 +465,13 @@ impl MapRegion {
 
         MapRegion {
             origin_rect,
+            themes: Vec::new(),
             room_buffer,
             neighbour_buffer,
         }
     }
 
-    pub fn into_map(self, doors: Vec<Door>) -> Map {
+    pub(crate) fn into_map(self, doors: Vec<Door>) -> Map {
         Map {
             origin_rect: self.origin_rect,
             rooms: self
@@ -475,15 +483,52 @@ impl MapRegion {
                 })
                 .collect(),
             doors,
+            themes: self.themes,
         }
     }
 
-    /// This function returns the number of room slots in the region.
+    // Turns a map back into a region where all of its rooms are active, keeping
+    // their order, and returns the doors of the map apart.
+    pub(crate) fn from_map(map: Map) -> (Self, Vec<Door>) {
+        let cell_owners = map
+            .rooms
+            .iter()
+            .enumerate()
+            .flat_map(|(room_id, room)| room.cells.iter().map(move |cell| (*cell, room_id)))
+            .collect::<HashMap<_, _>>();
+
+        let neighbours = map
+            .rooms
+            .iter()
+            .enumerate()
+            .map(|(room_id, room)| {
+                let neighbour_set = room
+                    .cells
+                    .iter()
+                    .flat_map(|cell| cell.neighbours())
+                    .filter_map(|cell| cell_owners.get(&cell).copied())
+                    .filter(|neighbour_id| *neighbour_id != room_id)
+                    .collect::<NeighbourSet>();
+
+                (room_id, neighbour_set)
+            })
+            .collect::<NeighbourTable>();
+
+        let rooms = map.rooms.into_iter().enumerate().collect::<RoomTable>();
+
+        let mut map_region = MapRegion::new(map.origin_rect, rooms, HashMap::new(), neighbours);
+        map_region.themes = map.themes;
+
+        (map_region, map.doors)
+    }
+
+    /// Returns the number of room slots in the region.
     /// It does not count the number of active and removed rooms.
     pub fn room_slots(&self) -> usize {
         self.room_buffer.len()
     }
 
+    /// Returns true if the room with id `room_id` is active.
     pub fn is_active(&self, room_id: RoomId) -> bool {
         self.room_buffer[room_id].is_active()
     }
@@ -496,8 +541,8 @@ impl MapRegion {
         self.room_buffer[room_id].is_empty()
     }
 
-    // Gets the room with id `room_id`.
-    // Panics if the id does not point to an active or removed room.
+    /// Gets the room with id `room_id`.
+    /// Panics if the id does not point to an active or removed room.
     pub fn get_room(&self, room_id: RoomId) -> &Room {
         match &self.room_buffer[room_id] {
             RoomEntry::Active(room) | RoomEntry::Removed(room) => room,
@@ -512,6 +557,7 @@ impl MapRegion {
         }
     }
 
+    /// Inserts a new active room without any neighbours.
     pub fn insert_room(&mut self, room: Room) -> RoomId {
         let room_id = self.room_buffer.len();
         self.room_buffer.push(RoomEntry::Active(room));
@@ -519,8 +565,8 @@ impl MapRegion {
         room_id
     }
 
-    // Gets the active room with id `room_id`.
-    // Panics if the id does not point to an active room.
+    /// Gets the active room with id `room_id`.
+    /// Panics if the id does not point to an active room.
     pub fn get_active(&self, room_id: RoomId) -> &Room {
         if let RoomEntry::Active(room) = &self.room_buffer[room_id] {
             room
@@ -529,8 +575,8 @@ impl MapRegion {
         }
     }
 
-    // Gets the removed room with id `room_id`.
-    // Panics if the id does not point to a removed room.
+    /// Gets the removed room with id `room_id`.
+    /// Panics if the id does not point to a removed room.
     pub fn get_removed(&self, room_id: RoomId) -> &Room {
         if let RoomEntry::Removed(room) = &self.room_buffer[room_id] {
             room
@@ -597,7 +643,7 @@ impl MapRegion {
             })
     }
 
-    pub fn take_room(&mut self, room_id: RoomId) -> Room {
+    pub(crate) fn take_room(&mut self, room_id: RoomId) -> Room {
         let room_entry = &mut self.room_buffer[room_id];
 
         if room_entry.is_empty() {
@@ -607,7 +653,7 @@ impl MapRegion {
         }
     }
 
-    pub fn take_active(&mut self, room_id: RoomId) -> Room {
+    pub(crate) fn take_active(&mut self, room_id: RoomId) -> Room {
         let room_entry = &mut self.room_buffer[room_id];
 
         if room_entry.is_active() {
@@ -617,7 +663,7 @@ impl MapRegion {
         }
     }
 
-    pub fn take_removed(&mut self, room_id: RoomId) -> Room {
+    pub(crate) fn take_removed(&mut self, room_id: RoomId) -> Room {
         let room_entry = &mut self.room_buffer[room_id];
 
         if room_entry.is_removed() {
@@ -629,7 +675,7 @@ impl MapRegion {
 
     // Gets the neighbours of the room with id `room_id`.
     // Panics if the id does not point to a valid room.
-    pub fn get_neighbours(&self, room_id: RoomId) -> &NeighbourSet {
+    pub(crate) fn get_neighbours(&self, room_id: RoomId) -> &NeighbourSet {
         if self.neighbour_buffer[room_id].is_none() {
             panic!("Room with ID {} is not valid!", room_id);
         }
@@ -637,7 +683,7 @@ impl MapRegion {
         self.neighbour_buffer[room_id].as_ref().unwrap()
     }
 
-    pub fn get_mut_neighbours(&mut self, room_id: RoomId) -> &mut NeighbourSet {
+    pub(crate) fn get_mut_neighbours(&mut self, room_id: RoomId) -> &mut NeighbourSet {
         if self.neighbour_buffer[room_id].is_none() {
             panic!("Room with ID {} is not valid!", room_id);
         }
@@ -665,7 +711,7 @@ impl MapRegion {
             .filter(|&neighbour_id| self.is_removed(neighbour_id))
     }
 
-    pub fn take_neighbours(&mut self, room_id: RoomId) -> NeighbourSet {
+    pub(crate) fn take_neighbours(&mut self, room_id: RoomId) -> NeighbourSet {
         if self.neighbour_buffer[room_id].is_none() {
             panic!("Room with ID {} is not valid!", room_id);
         }
@@ -673,8 +719,42 @@ impl MapRegion {
         std::mem::take(&mut self.neighbour_buffer[room_id]).unwrap()
     }
 
-    // Merges two active rooms into one.
-    // Panics if either of the rooms does not exist.
+    /// Marks the rooms with ids `room_id_a` and `room_id_b` as neighbours of each other.
+    /// Panics if either of the ids does not point to a valid room.
+    pub fn add_neighbours(&mut self, room_id_a: RoomId, room_id_b: RoomId) {
+        self.get_mut_neighbours(room_id_a).insert(room_id_b);
+        self.get_mut_neighbours(room_id_b).insert(room_id_a);
+    }
+
+    /// Removes the rooms with ids `room_id_a` and `room_id_b` from the neighbours of
+    /// each other.
+    /// Panics if either of the ids does not point to a valid room.
+    pub fn remove_neighbours(&mut self, room_id_a: RoomId, room_id_b: RoomId) {
+        self.get_mut_neighbours(room_id_a).remove(room_id_b);
+        self.get_mut_neighbours(room_id_b).remove(room_id_a);
+    }
+
+    /// Marks every other room, active or removed, sharing an edge with the room
+    /// with id `room_id` as its neighbour.
+    /// Panics if the id does not point to a valid room.
+    pub fn link_adjacent_rooms(&mut self, room_id: RoomId) {
+        let room = self.get_room(room_id);
+
+        let neighbours = self
+            .iter_rooms()
+            .filter(|(other_id, other_room)| {
+                *other_id != room_id && room.is_neighbour_of(other_room)
+            })
+            .map(|(other_id, _)| other_id)
+            .collect::<Vec<_>>();
+
+        for neighbour_id in neighbours {
+            self.add_neighbours(room_id, neighbour_id);
+        }
+    }
+
+    /// Merges two active rooms into one, keeping the id of the first one.
+    /// Panics if either of the rooms does not exist.
     pub fn merge_active_rooms(&mut self, room_id_a: RoomId, room_id_b: RoomId) -> Result<()> {
         let from_room = self.take_active(room_id_a);
         let to_room = self.take_active(room_id_b);
@@ -714,7 +794,7 @@ impl MapRegion {
     // Compacts the buffers by removing empty entries
     // and shifting the indices of the remaining rooms.
     // WARNING: After this operation, the room IDs will change!
-    pub fn compact_buffers(&mut self) {
+    pub(crate) fn compact_buffers(&mut self) {
         let mut empty_slots = self
             .room_buffer
             .iter()
@@ -785,12 +865,23 @@ impl MapRegion {
     /// This method will increase the indexes of the rooms in the other region
     /// by the number of rooms in the current region.
     /// So as to avoid index collisions.
-    pub fn merge_with(&mut self, other: MapRegion) {
+    pub(crate) fn merge_with(&mut self, other: MapRegion) {
         let offset = self.room_buffer.len();
 
+        // The rooms keep pointing to the theme of the region they came from
+        let region_offset = self.themes.len();
+        self.themes.extend(other.themes);
+
         // Merging the rooms is as simple as extending the room buffer
         // with the other region's room buffer
-        self.room_buffer.extend(other.room_buffer);
+        self.room_buffer
+            .extend(other.room_buffer.into_iter().map(|mut entry| {
+                if let RoomEntry::Active(room) | RoomEntry::Removed(room) = &mut entry {
+                    room.region += region_offset;
+                }
+
+                entry
+            }));
 
         // First we apply the offset to the room IDs in the neighbour buffer
         let offset_neighbours = other
@@ -808,7 +899,7 @@ impl MapRegion {
         self.neighbour_buffer.extend(offset_neighbours);
     }
 
-    pub fn shrink_buffers(&mut self) {
+    pub(crate) fn shrink_buffers(&mut self) {
         self.room_buffer.shrink_to_fit();
         self.neighbour_buffer.shrink_to_fit();
     }
@@ -825,8 +916,9 @@ impl Display for MapRegion {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub(crate) struct Rect {
+/// An axis aligned rectangle of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rect {
     pub origin: Cell,
     pub width: u32,
     pub height: u32,
@@ -863,7 +955,7 @@ impl Rect {
         }
     }
 
-    pub fn try_split_at(self, axis: SplitAxis, at: u32) -> Result<(Rect, Rect)> {
+    pub(crate) fn try_split_at(self, axis: SplitAxis, at: u32) -> Result<(Rect, Rect)> {
         match axis {
             SplitAxis::Horizontal => {
                 if self.height < 2 || at >= self.height || at == 0 {
@@ -945,7 +1037,7 @@ impl Rect {
         cells
     }
 
-    pub fn get_edge(&self, direction: Direction) -> Edge {
+    pub(crate) fn get_edge(&self, direction: Direction) -> Edge {
         match direction {
             Direction::North => Edge {
                 from: Cell::new(self.origin.col, self.origin.row),
@@ -966,24 +1058,240 @@ impl Rect {
         }
     }
 
+    pub(crate) fn contains(&self, cell: Cell) -> bool {
+        (self.origin.col..self.origin.col + self.width).contains(&cell.col)
+            && (self.origin.row..self.origin.row + self.height).contains(&cell.row)
+    }
+
     pub fn area(&self) -> u32 {
         self.width * self.height
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub(crate) enum DoorModifier {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DoorModifier {
     Open,
     Secret,
-    Locked,
+    /// Opened by the key of the given tier.
+    Locked(u8),
     None,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub(crate) struct Door {
+/// The cells of the canvas that can hold rooms, giving the map its silhouette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellMask {
+    columns: u32,
+    rows: u32,
+    cells: Vec<bool>,
+}
+
+impl CellMask {
+    /// Creates a mask from a bitmap of `columns` by `rows` values, given row by row.
+    pub fn from_bitmap(columns: u32, rows: u32, cells: Vec<bool>) -> Result<Self> {
+        let Some(cell_count) = (columns as usize).checked_mul(rows as usize) else {
+            return Err(anyhow::anyhow!(
+                "A {}x{} mask has more cells than can be held",
+                columns,
+                rows
+            ));
+        };
+
+        if cells.len() != cell_count {
+            return Err(anyhow::anyhow!(
+                "A {}x{} mask needs {} cells, got {}",
+                columns,
+                rows,
+                cell_count,
+                cells.len()
+            ));
+        }
+
+        if !cells.contains(&true) {
+            return Err(anyhow::anyhow!("The mask does not contain any cell"));
+        }
+
+        Ok(CellMask {
+            columns,
+            rows,
+            cells,
+        })
+    }
+
+    /// Creates a mask from an ASCII grid, where `#` is a cell inside the mask and
+    /// `.` a cell outside of it. Empty lines are ignored.
+    pub fn from_ascii(grid: &str) -> Result<Self> {
+        let lines = grid
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+
+        let columns = lines.first().map_or(0, |line| line.chars().count());
+        let mut cells = Vec::with_capacity(columns * lines.len());
+
+        for line in lines.iter() {
+            if line.chars().count() != columns {
+                return Err(anyhow::anyhow!(
+                    "Every row of the mask must be {} cells long",
+                    columns
+                ));
+            }
+
+            for symbol in line.chars() {
+                match symbol {
+                    '#' => cells.push(true),
+                    '.' => cells.push(false),
+                    _ => {
+                        return Err(anyhow::anyhow!("Unknown symbol '{}' in mask", symbol));
+                    }
+                }
+            }
+        }
+
+        Self::from_bitmap(columns as u32, lines.len() as u32, cells)
+    }
+
+    /// Creates a mask holding the cells whose center falls inside the polygon,
+    /// given by its vertices in cell units.
+    pub fn from_polygon(columns: u32, rows: u32, vertices: &[(f32, f32)]) -> Result<Self> {
+        if vertices.len() < 3 {
+            return Err(anyhow::anyhow!(
+                "A polygon needs at least 3 vertices, got {}",
+                vertices.len()
+            ));
+        }
+
+        let cells = (0..rows)
+            .flat_map(|row| (0..columns).map(move |col| (col as f32 + 0.5, row as f32 + 0.5)))
+            .map(|(x, y)| {
+                // Even-odd rule, counting the edges crossed by a ray going right
+                let mut is_inside = false;
+
+                for (idx, (x_a, y_a)) in vertices.iter().enumerate() {
+                    let (x_b, y_b) = vertices[(idx + 1) % vertices.len()];
+
+                    if (*y_a > y) != (y_b > y) && x < x_a + (y - y_a) * (x_b - x_a) / (y_b - y_a) {
+                        is_inside = !is_inside;
+                    }
+                }
+
+                is_inside
+            })
+            .collect();
+
+        Self::from_bitmap(columns, rows, cells)
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Returns true if the cell is inside the mask.
+    pub fn contains(&self, cell: Cell) -> bool {
+        cell.col < self.columns
+            && cell.row < self.rows
+            && self.cells[cell.row as usize * self.columns as usize + cell.col as usize]
+    }
+
+    // Creates a mask holding every cell of the canvas.
+    pub(crate) fn full(columns: u32, rows: u32) -> Self {
+        CellMask {
+            columns,
+            rows,
+            cells: vec![true; columns as usize * rows as usize],
+        }
+    }
+
+    // Leaves the cell out of the mask, if it is part of the canvas.
+    pub(crate) fn remove(&mut self, cell: Cell) {
+        if cell.col < self.columns && cell.row < self.rows {
+            self.cells[cell.row as usize * self.columns as usize + cell.col as usize] = false;
+        }
+    }
+
+    // Returns how many of the cells of the rect are inside the mask.
+    pub(crate) fn cells_inside(&self, rect: &Rect) -> usize {
+        rect.get_cells()
+            .into_iter()
+            .filter(|cell| self.contains(*cell))
+            .count()
+    }
+}
+
+/// The canvas a layout is generated on, either a full rectangle or shaped by a mask,
+/// along with the rooms placed on it by hand.
+///
+/// Only single layouts are generated on a canvas, the floor plans and the castle
+/// modes always fill a full rectangle without any pinned room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutCanvas {
+    columns: u32,
+    rows: u32,
+    mask: Option<CellMask>,
+    pinned_rooms: Vec<PinnedRoom>,
+}
+
+impl LayoutCanvas {
+    /// Creates a rectangular canvas of `columns` by `rows` cells.
+    pub fn new(columns: u32, rows: u32) -> Self {
+        LayoutCanvas {
+            columns,
+            rows,
+            mask: None,
+            pinned_rooms: Vec::new(),
+        }
+    }
+
+    /// Creates a canvas as large as the mask, whose rooms only take cells inside of it.
+    pub fn from_mask(mask: CellMask) -> Self {
+        LayoutCanvas {
+            columns: mask.columns(),
+            rows: mask.rows(),
+            mask: Some(mask),
+            pinned_rooms: Vec::new(),
+        }
+    }
+
+    /// Sets the rooms placed by hand, which keep their cells and modifier while the
+    /// rest of the map is generated in the space left around them.
+    pub fn with_pinned_rooms(mut self, pinned_rooms: Vec<PinnedRoom>) -> Self {
+        self.pinned_rooms = pinned_rooms;
+        self
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn mask(&self) -> Option<&CellMask> {
+        self.mask.as_ref()
+    }
+
+    pub fn pinned_rooms(&self) -> &[PinnedRoom] {
+        &self.pinned_rooms
+    }
+}
+
+/// A door between two neighbouring cells that belong to different rooms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Door {
     pub from: Cell,
     pub to: Cell,
     pub modifier: DoorModifier,
+    /// The ability needed to go through the door, if any.
+    pub requirement: Option<Ability>,
+    /// Whether the door can only be gone through from `from` to `to`.
+    pub one_way: bool,
+    /// Whether the door joins rooms of different regions.
+    pub area_transition: bool,
 }
 
 impl Door {
@@ -992,12 +1300,108 @@ impl Door {
             from,
             to,
             modifier: DoorModifier::Open,
+            requirement: None,
+            one_way: false,
+            area_transition: false,
         }
     }
+
+    /// Whether the door joins two cells on top of each other.
+    pub fn is_vertical(&self) -> bool {
+        self.from.col == self.to.col
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub(crate) enum Direction {
+/// A movement upgrade that opens the doors requiring it once picked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ability {
+    DoubleJump,
+    HighJump,
+    MorphBall,
+    WaterSuit,
+    GrappleBeam,
+}
+
+impl Ability {
+    pub const ALL: [Ability; 5] = [
+        Ability::DoubleJump,
+        Ability::HighJump,
+        Ability::MorphBall,
+        Ability::WaterSuit,
+        Ability::GrappleBeam,
+    ];
+
+    /// Whether the ability only gates vertical connections, e.g. a ledge
+    /// too high to be reached without it.
+    pub fn gates_vertical_doors(&self) -> bool {
+        match self {
+            Ability::DoubleJump | Ability::HighJump => true,
+            Ability::MorphBall | Ability::WaterSuit | Ability::GrappleBeam => false,
+        }
+    }
+
+    fn bit(&self) -> u8 {
+        1 << (*self as u8)
+    }
+}
+
+/// A set of [`Ability`] values, serialized as a list of their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<Ability>", into = "Vec<Ability>")]
+pub struct AbilitySet(u8);
+
+impl AbilitySet {
+    pub fn empty() -> Self {
+        AbilitySet(0)
+    }
+
+    pub fn insert(&mut self, ability: Ability) {
+        self.0 |= ability.bit();
+    }
+
+    pub fn contains(&self, ability: Ability) -> bool {
+        self.0 & ability.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the abilities of the set in their declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = Ability> + '_ {
+        Ability::ALL
+            .into_iter()
+            .filter(|ability| self.contains(*ability))
+    }
+}
+
+impl FromIterator<Ability> for AbilitySet {
+    fn from_iter<T: IntoIterator<Item = Ability>>(iter: T) -> Self {
+        let mut set = AbilitySet::empty();
+
+        for ability in iter {
+            set.insert(ability);
+        }
+
+        set
+    }
+}
+
+impl From<Vec<Ability>> for AbilitySet {
+    fn from(abilities: Vec<Ability>) -> Self {
+        abilities.into_iter().collect()
+    }
+}
+
+impl From<AbilitySet> for Vec<Ability> {
+    fn from(set: AbilitySet) -> Self {
+        set.iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
     North,
     South,
     East,
@@ -1022,25 +1426,51 @@ impl Direction {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
-pub(crate) enum RoomModifier {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash, Serialize, Deserialize)]
+pub enum RoomModifier {
     #[default]
     None,
     Navigation,
     Save,
-    Item,
+    /// An item pickup, which can grant one of the abilities of the map.
+    Item(Option<Ability>),
     RegionConnection(Direction),
+    /// Holds the key that opens the doors locked with the given tier.
+    Key(u8),
+    /// The room the map is entered from.
+    Start,
+    /// Holds one of the bosses of the map.
+    Boss,
+    /// Part of the warp network, see [`MapLayout::warp_links`].
+    Warp,
+    /// A hidden treasure room, only entered through a single secret door.
+    Secret,
+    /// Leads to the floor right above or below, see [`FloorPlan::links`].
+    Stairs,
+    /// Part of an elevator shaft going through one or more floors, see [`FloorPlan::links`].
+    Elevator,
+    /// A warp room leading to the room with the same link in the other castle
+    /// of an [`InvertedCastle`] or a [`DualCastle`].
+    CastleLink(u8),
 }
 
 pub(crate) type RoomTable = HashMap<RoomId, Room>;
 pub(crate) type NeighbourTable = HashMap<RoomId, NeighbourSet>;
 
-pub(crate) type RoomId = usize;
+/// The identifier of a room inside a [`MapRegion`].
+pub type RoomId = usize;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Room {
+/// A room made of one or more connected cells.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Room {
     pub cells: Vec<Cell>,
     pub modifier: Option<RoomModifier>,
+    /// The index, in [`Map::themes`], of the region the room was generated in.
+    pub region: usize,
+    /// The cells the doors of the room are placed in, any of them when empty.
+    pub door_anchors: Vec<Cell>,
+    /// Placed by hand, so generation never merges, splits or removes it.
+    pub pinned: bool,
 }
 
 impl Room {
@@ -1048,16 +1478,30 @@ impl Room {
         Self {
             cells: rect.get_cells(),
             modifier: None,
+            region: 0,
+            door_anchors: Vec::new(),
+            pinned: false,
         }
     }
 
+    /// Returns the cells of the room that can hold a door.
+    pub fn door_cells(&self) -> &[Cell] {
+        if self.door_anchors.is_empty() {
+            &self.cells
+        } else {
+            &self.door_anchors
+        }
+    }
+
+    /// Whether both rooms share a wall that can hold a door, between their
+    /// [`door_cells`](Room::door_cells).
     pub fn is_neighbour_of(&self, other: &Room) -> bool {
         if self == other {
             return false;
         }
 
-        for cell in self.cells.iter() {
-            for other_cell in other.cells.iter() {
+        for cell in self.door_cells().iter() {
+            for other_cell in other.door_cells().iter() {
                 if cell.is_neighbour_of(other_cell).is_some() {
                     return true;
                 }
@@ -1096,13 +1540,37 @@ impl Room {
             merged_cells.insert(*cell);
         }
 
+        // Cells are sorted so the resulting room does not depend on the
+        // iteration order of the set
+        let mut cells = merged_cells.into_iter().collect::<Vec<_>>();
+        cells.sort();
+
+        // The doors of the part without anchors can still go anywhere in it
+        let door_anchors = if self.door_anchors.is_empty() && other.door_anchors.is_empty() {
+            Vec::new()
+        } else {
+            let mut door_anchors = self
+                .door_cells()
+                .iter()
+                .chain(other.door_cells())
+                .copied()
+                .collect::<Vec<_>>();
+            door_anchors.sort();
+            door_anchors.dedup();
+
+            door_anchors
+        };
+
         Room {
-            cells: merged_cells.into_iter().collect(),
+            cells,
             modifier: self.modifier,
+            region: self.region,
+            door_anchors,
+            pinned: self.pinned || other.pinned,
         }
     }
 
-    pub fn get_center(&self) -> Vector2 {
+    pub(crate) fn get_center(&self) -> Vector2 {
         let mut center = Vector2::ZERO;
 
         let cell_count = self.cells.len() as f32;
@@ -1119,9 +1587,202 @@ impl Room {
     }
 }
 
+/// A room shape stamped into the layout as a single room, like an L shaped hall
+/// or a boss arena.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomTemplate {
+    /// The name of the template, unique within its library.
+    pub name: String,
+    /// The cells of the room, relative to the top left corner of the template.
+    pub cells: Vec<Cell>,
+    /// The cells the doors of the room are placed in, any of them when empty.
+    pub door_anchors: Vec<Cell>,
+}
+
+impl RoomTemplate {
+    /// Creates a template from its rows, where `#` is a cell of the room, `D` is
+    /// a cell of the room holding a door anchor and `.` is left out.
+    pub fn from_pattern(name: impl Into<String>, rows: &[&str]) -> Result<Self> {
+        let mut cells = Vec::new();
+        let mut door_anchors = Vec::new();
+
+        for (row, line) in rows.iter().enumerate() {
+            for (col, symbol) in line.chars().enumerate() {
+                let cell = Cell::new(col as u32, row as u32);
+
+                match symbol {
+                    '#' => cells.push(cell),
+                    'D' => {
+                        cells.push(cell);
+                        door_anchors.push(cell);
+                    }
+                    '.' => {}
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Unknown symbol '{}' in room template pattern",
+                            symbol
+                        ));
+                    }
+                }
+            }
+        }
+
+        let template = RoomTemplate {
+            name: name.into(),
+            cells,
+            door_anchors,
+        };
+        template.validate()?;
+
+        Ok(template)
+    }
+
+    /// Checks that the cells form a single connected room holding every anchor.
+    pub fn validate(&self) -> Result<()> {
+        if self.cells.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Room template '{}' has no cells",
+                self.name
+            ));
+        }
+
+        if let Some(anchor) = self
+            .door_anchors
+            .iter()
+            .find(|anchor| !self.cells.contains(anchor))
+        {
+            return Err(anyhow::anyhow!(
+                "Door anchor {} of room template '{}' is not one of its cells",
+                anchor,
+                self.name
+            ));
+        }
+
+        if !are_connected(&self.cells) {
+            return Err(anyhow::anyhow!(
+                "The cells of room template '{}' are not connected",
+                self.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Returns the room the template becomes with its top left corner at `origin`.
+    pub(crate) fn room_at(&self, origin: Cell) -> Room {
+        let mut cells = self
+            .cells
+            .iter()
+            .map(|cell| cell.offset_by_two(origin.col, origin.row))
+            .collect::<Vec<_>>();
+        cells.sort();
+
+        let door_anchors = self
+            .door_anchors
+            .iter()
+            .map(|cell| cell.offset_by_two(origin.col, origin.row))
+            .collect();
+
+        Room {
+            cells,
+            modifier: None,
+            region: 0,
+            door_anchors,
+            pinned: false,
+        }
+    }
+}
+
+/// A room placed by hand, which the rest of the map is generated around.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedRoom {
+    pub cells: Vec<Cell>,
+    pub modifier: Option<RoomModifier>,
+}
+
+impl PinnedRoom {
+    pub fn new(cells: Vec<Cell>) -> Self {
+        PinnedRoom {
+            cells,
+            modifier: None,
+        }
+    }
+
+    pub fn from_rect(rect: Rect) -> Self {
+        Self::new(rect.get_cells())
+    }
+
+    pub fn with_modifier(mut self, modifier: RoomModifier) -> Self {
+        self.modifier = Some(modifier);
+        self
+    }
+
+    /// Checks that the cells form a single connected room, and that its modifier
+    /// can be given to a room placed by hand.
+    pub fn validate(&self) -> Result<()> {
+        if self.cells.is_empty() {
+            return Err(anyhow::anyhow!("The pinned room has no cells"));
+        }
+
+        if !are_connected(&self.cells) {
+            return Err(anyhow::anyhow!(
+                "The cells of the pinned room are not connected"
+            ));
+        }
+
+        match self.modifier {
+            None | Some(RoomModifier::Start | RoomModifier::Boss | RoomModifier::Item(None)) => {
+                Ok(())
+            }
+            Some(RoomModifier::Save | RoomModifier::Navigation) if self.cells.len() == 1 => Ok(()),
+            Some(modifier) => Err(anyhow::anyhow!(
+                "The modifier {:?} can not be given to a pinned room with {} cells",
+                modifier,
+                self.cells.len()
+            )),
+        }
+    }
+
+    // Returns the room placed in the layout for the pinned room.
+    pub(crate) fn room(&self) -> Room {
+        let mut cells = self.cells.clone();
+        cells.sort();
+        cells.dedup();
+
+        Room {
+            cells,
+            modifier: self.modifier,
+            region: 0,
+            door_anchors: Vec::new(),
+            pinned: true,
+        }
+    }
+}
+
+// Returns true if every cell can be reached from the first one without leaving
+// the cells.
+fn are_connected(cells: &[Cell]) -> bool {
+    let Some(first_cell) = cells.first() else {
+        return true;
+    };
+
+    let mut reached_cells = HashSet::from([*first_cell]);
+    let mut cells_to_visit = vec![*first_cell];
+
+    while let Some(cell) = cells_to_visit.pop() {
+        for neighbour in cell.neighbours() {
+            if cells.contains(&neighbour) && reached_cells.insert(neighbour) {
+                cells_to_visit.push(neighbour);
+            }
+        }
+    }
+
+    reached_cells.len() == cells.iter().collect::<HashSet<_>>().len()
+}
+
 #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 #[cfg_attr(feature = "style-ord-hash", derive(PartialOrd, Ord, Hash))]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum MapStyle {
     #[default]
     CastlevaniaSOTN,
@@ -1149,11 +1810,173 @@ impl MapStyle {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct Map {
+/// The kind of place a region of the map stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Biome {
+    Hall,
+    Crypt,
+    Garden,
+    Cavern,
+    Water,
+    Lava,
+    Ice,
+    Machinery,
+}
+
+/// The colours a themed region is drawn with, as SVG colour strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Palette {
+    pub room_color: String,
+    pub door_color: String,
+    pub wall_color: String,
+}
+
+/// The name, biome and palette of one of the regions the map was partitioned into.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RegionTheme {
+    /// The area covered by the region, before its rooms were generated.
+    pub rect: Rect,
+    pub name: String,
+    pub biome: Biome,
+    pub palette: Palette,
+}
+
+/// A region of a generated map, with its rooms and the doors between them.
+///
+/// Styles that merge their regions produce a single [`Map`] covering the
+/// whole canvas, which keeps the themes of all the merged regions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Map {
     pub origin_rect: Rect,
     pub rooms: Vec<Room>,
     pub doors: Vec<Door>,
+    pub themes: Vec<RegionTheme>,
+}
+
+/// Identifies a room of a [`MapLayout`] by the index of its region and
+/// its index inside that region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RoomRef {
+    pub region: usize,
+    pub room: usize,
+}
+
+impl Display for RoomRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.region, self.room)
+    }
+}
+
+/// The structured result of a map generation, independent of how it is drawn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapLayout {
+    pub columns: u32,
+    pub rows: u32,
+    /// The seed the layout was generated with.
+    pub seed: u64,
+    pub regions: Vec<Map>,
+}
+
+/// How the floors of a [`FloorPlan`] are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FloorLinkKind {
+    Stairs,
+    Elevator,
+}
+
+/// A connection between the rooms holding `cell` on every floor from
+/// `lower_floor` up to `upper_floor`.
+///
+/// Stairs always join two consecutive floors, while elevators can go through many.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FloorLink {
+    pub kind: FloorLinkKind,
+    pub cell: Cell,
+    pub lower_floor: usize,
+    pub upper_floor: usize,
+}
+
+/// A map made of several floors stacked on top of each other, each one a full layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FloorPlan {
+    /// The floors, from the lowest one up.
+    pub floors: Vec<MapLayout>,
+    pub links: Vec<FloorLink>,
+}
+
+/// A castle along with its inverted counterpart, the same castle turned upside down.
+///
+/// Both castles share their rooms and doors, but each one has its own save,
+/// navigation and item rooms.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvertedCastle {
+    pub castle: MapLayout,
+    pub inverted: MapLayout,
+}
+
+/// Two different castles over the same canvas, like the castles of HOD, linked
+/// through warp rooms at the same cells in both of them.
+///
+/// The second castle is only entered through those warp rooms, and some of the
+/// pickups needed in the first castle are found in the second one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DualCastle {
+    pub castle_a: MapLayout,
+    pub castle_b: MapLayout,
+}
+
+// The JSON document wraps the layout together with the schema version,
+// so that older documents can be detected when loading them.
+#[derive(Serialize)]
+struct VersionedLayoutRef<'a> {
+    version: u32,
+    layout: &'a MapLayout,
+}
+
+#[derive(Deserialize)]
+struct VersionedLayout {
+    version: u32,
+    layout: serde_json::Value,
+}
+
+impl MapLayout {
+    /// The version of the JSON schema written by [`MapLayout::to_json`].
+    pub const SCHEMA_VERSION: u32 = LAYOUT_SCHEMA_VERSION;
+
+    /// Returns every pair of warp rooms of the layout.
+    ///
+    /// The warp network lets the player travel between any two warp rooms,
+    /// once both of them have been visited.
+    pub fn warp_links(&self) -> Vec<(RoomRef, RoomRef)> {
+        crate::algos::warp_links(&self.regions)
+    }
+
+    /// Serializes the layout into a versioned JSON document.
+    pub fn to_json(&self) -> Result<String> {
+        let document = VersionedLayoutRef {
+            version: Self::SCHEMA_VERSION,
+            layout: self,
+        };
+
+        Ok(serde_json::to_string(&document)?)
+    }
+
+    /// Loads a layout from a JSON document created by [`MapLayout::to_json`].
+    ///
+    /// Fails if the document was written with a different schema version.
+    pub fn from_json(json: &str) -> Result<MapLayout> {
+        let document: VersionedLayout = serde_json::from_str(json)?;
+
+        if document.version != Self::SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported layout schema version {}, expected {}",
+                document.version,
+                Self::SCHEMA_VERSION
+            ));
+        }
+
+        Ok(serde_json::from_value(document.layout)?)
+    }
 }
 
 #[cfg(test)]
@@ -1298,6 +2121,9 @@ mod test {
                 .chain(rect_1_2.get_cells())
                 .collect(),
             modifier: None,
+            region: 0,
+            door_anchors: Vec::new(),
+            pinned: false,
         };
 
         let rect_2 = Rect {
@@ -1309,11 +2135,79 @@ mod test {
         let room_2 = Room {
             cells: rect_2.get_cells(),
             modifier: None,
+            region: 0,
+            door_anchors: Vec::new(),
+            pinned: false,
         };
 
         assert!(room_1.is_neighbour_of(&room_2));
     }
 
+    #[test]
+    fn test_cell_mask_rejects_wrong_dimensions() {
+        assert!(CellMask::from_bitmap(3, 2, vec![true; 6]).is_ok());
+        assert!(CellMask::from_bitmap(3, 2, vec![true; 5]).is_err());
+
+        // The cell count of huge masks is not computed in u32, so it can't wrap around
+        assert!(CellMask::from_bitmap(u32::MAX, 2, vec![true; 2]).is_err());
+        assert!(CellMask::from_bitmap(1 << 16, 1 << 16, vec![true]).is_err());
+    }
+
+    #[test]
+    fn test_map_layout_json_round_trip() {
+        let mut room = Room::new_from_rect(Rect::new(0, 0, 2, 1));
+        room.modifier = Some(RoomModifier::RegionConnection(Direction::West));
+        let mut door = Door::new(Cell::new(1, 0), Cell::new(1, 1));
+        door.modifier = DoorModifier::Secret;
+
+        let layout = MapLayout {
+            columns: 4,
+            rows: 3,
+            seed: u64::MAX,
+            regions: vec![Map {
+                origin_rect: Rect::new(0, 0, 4, 3),
+                rooms: vec![
+                    room,
+                    Room::new_from_rect(Rect::new(1, 1, 1, 2)),
+                    Room {
+                        cells: vec![Cell::new(3, 0)],
+                        modifier: Some(RoomModifier::Save),
+                        region: 0,
+                        door_anchors: vec![Cell::new(3, 0)],
+                        pinned: true,
+                    },
+                ],
+                doors: vec![door],
+                themes: Vec::new(),
+            }],
+        };
+
+        let json = layout.to_json().unwrap();
+        assert!(json.starts_with(&format!(r#"{{"version":{}"#, MapLayout::SCHEMA_VERSION)));
+
+        let loaded = MapLayout::from_json(&json).unwrap();
+        assert_eq!(loaded, layout);
+    }
+
+    #[test]
+    fn test_map_layout_from_json_rejects_other_versions() {
+        let layout = MapLayout {
+            columns: 1,
+            rows: 1,
+            seed: 0,
+            regions: vec![],
+        };
+
+        let json = layout.to_json().unwrap().replacen(
+            &format!(r#""version":{}"#, MapLayout::SCHEMA_VERSION),
+            r#""version":0"#,
+            1,
+        );
+
+        assert!(MapLayout::from_json(&json).is_err());
+        assert!(MapLayout::from_json("not json").is_err());
+    }
+
     #[test]
     fn test_map_region_compact_buffers() {
         let mut map_region = MapRegion::new_test_small_region();
@@ -1,4 +1,4 @@
-use generator_core::{MapStyle, create_map};
+use generator_core::{GenerateError, MapStyle, create_map};
 
 use std::env;
 
@@ -38,6 +38,18 @@ fn get_random_style() -> MapStyle {
     }
 }
 
+// Errors caused by the requested map are reported as an invalid payload, while the
+// ones raised during generation are reported as internal errors.
+fn error_type_for(err: &GenerateError) -> &'static str {
+    match err {
+        GenerateError::DimensionsTooSmall { .. }
+        | GenerateError::DimensionsTooLarge { .. }
+        | GenerateError::InvalidConfig(_)
+        | GenerateError::InvalidPinnedRoom { .. } => "InvalidPayload",
+        _ => "InternalError",
+    }
+}
+
 async fn instantiate_s3_client() -> S3Client {
     let region = env::var(AWS_REGION).expect("AWS_REGION environment variable not set");
     let config = aws_config_defaults(BehaviorVersion::latest())
@@ -92,7 +104,10 @@ pub(crate) async fn function_handler(
     );
 
     let map_bytes = {
-        let svg = create_map(columns, rows, style);
+        let svg = create_map(columns, rows, style).map_err(|err| Diagnostic {
+            error_type: error_type_for(&err).to_string(),
+            error_message: format!("Failed to generate map: {}", err),
+        })?;
         svg.to_string().into_bytes()
     };
 
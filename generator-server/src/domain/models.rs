@@ -1,4 +1,4 @@
-use generator_core::MapStyle;
+use generator_core::{GenerateError, MapStyle};
 
 use derive_more::From;
 use thiserror::Error;
@@ -99,6 +99,8 @@ impl CreateMapRequest {
 pub enum CreateMapError {
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
+    #[error("Failed to generate map: {0}")]
+    GenerationError(#[from] GenerateError),
     // to be extended as new error scenarios are introduced
     #[error("Failed to persist map: {0}")]
     FileSystemError(#[from] std::io::Error),
@@ -21,8 +21,8 @@ pub trait MapService: Clone + Send + Sync + 'static {
     ///
     /// # Errors
     ///
-    /// - [CreateMapError::InvalidColumnSize] if a [Map] has an invalid column size.
-    /// - [CreateMapError::InvalidRowSize] if a [Map] has an invalid row size.
+    /// - [CreateMapError::GenerationError] if a [Map] cannot be generated with the requested
+    ///   dimensions.
     fn create_map(
         &self,
         req: &CreateMapRequest,
@@ -48,9 +48,16 @@ where
     ///
     /// # Errors
     ///
+    /// - [CreateMapError::GenerationError] if the map cannot be generated.
     /// - Propagates any [CreateMapError] returned by the [MapRepository].
     async fn create_map(&self, req: &CreateMapRequest) -> Result<Map, CreateMapError> {
-        let map_data = create_map(req.raw_columns(), req.raw_rows(), req.style());
+        let map_data = match create_map(req.raw_columns(), req.raw_rows(), req.style()) {
+            Ok(map_data) => map_data,
+            Err(e) => {
+                self.metrics.record_map_creation_failure().await;
+                return Err(e.into());
+            }
+        };
 
         let result = self.repository.persist_map(req, map_data).await;
 
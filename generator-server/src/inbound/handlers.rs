@@ -66,7 +66,8 @@ impl CreateMapHttpRequestBody {
 /// # Responses
 ///
 /// - 201 Created: the [Map] was successfully created.
-/// - 422 Unprocessable entity: The [Map] creation request had invalid parameters.
+/// - 422 Unprocessable entity: The [Map] creation request had invalid parameters, or no
+///   [Map] can be generated with them.
 pub(super) async fn create_map_handler<MS: MapService>(
     State(state): State<AppState<MS>>,
     Json(body): Json<CreateMapHttpRequestBody>,
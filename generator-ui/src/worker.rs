@@ -48,7 +48,17 @@ impl Worker {
     }
 
     fn generate_and_draw_new_map(&mut self, cols: u32, rows: u32, style: MapStyle) {
-        let map = create_map(cols, rows, style);
+        let map = match create_map(cols, rows, style) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("Couldn't generate map: {}", e);
+
+                let _ = self.ui_handle.upgrade_in_event_loop(|handle| {
+                    handle.invoke_enable_generate_button();
+                });
+                return;
+            }
+        };
         let map_str = map.to_string();
 
         self.last_map = Some(map);